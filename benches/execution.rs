@@ -0,0 +1,40 @@
+// Measures the instructions a hart executes per second in a loop of arithmetic, which the hart
+// runs from its basic blocks. Run with `cargo bench --bench execution`.
+use five::emulator::{Emulator, StopConditions};
+use std::time::Instant;
+
+const BASE: u64 = 0x8000_0000;
+const INSTRUCTIONS: u64 = 30_000_000;
+
+fn main() {
+    // addi a0, a0, 1; xor a1, a1, a0; slli a2, a0, 3; add a3, a3, a2; addi t0, t0, -1;
+    // bnez t0, -20
+    let program = [
+        0x00150513u32,
+        0x00a5c5b3,
+        0x00351613,
+        0x00c686b3,
+        0xfff28293,
+        0xfe0296e3,
+    ];
+    let mut emulator = Emulator::default();
+    for (i, instruction) in program.iter().enumerate() {
+        emulator
+            .write_memory(BASE + i as u64 * 4, &instruction.to_le_bytes())
+            .unwrap();
+    }
+    emulator.write_pc(0, BASE);
+    emulator.write_register(0, 5, u64::MAX);
+    emulator.stop_when(StopConditions {
+        instructions: Some(INSTRUCTIONS),
+        ..StopConditions::default()
+    });
+    let start = Instant::now();
+    emulator.run().unwrap();
+    let seconds = start.elapsed().as_secs_f64();
+    println!(
+        "{:<16} {:>10.1} MIPS",
+        "arithmetic",
+        INSTRUCTIONS as f64 / seconds / 1e6
+    );
+}
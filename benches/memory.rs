@@ -0,0 +1,60 @@
+// Measures the bulk accesses of loaders and devices, and the loads and stores of a program.
+// Run with `cargo bench --bench memory`.
+use five::emulator::{Emulator, StopConditions};
+use std::time::Instant;
+
+const BASE: u64 = 0x8000_0000;
+const BULK_SIZE: u64 = 64 * 1024 * 1024;
+const INSTRUCTIONS: u64 = 30_000_000;
+
+fn main() {
+    // sd a1, 0(sp); ld a2, 0(sp); sw a1, 9(sp); lw a3, 9(sp); addi t0, t0, -1; bnez t0, -20
+    let program = [
+        0x00b13023u32,
+        0x00013603,
+        0x00b124a3,
+        0x00912683,
+        0xfff28293,
+        0xfe0296e3,
+    ];
+    let mut emulator = Emulator::default();
+    for (i, instruction) in program.iter().enumerate() {
+        emulator
+            .write_memory(BASE + i as u64 * 4, &instruction.to_le_bytes())
+            .unwrap();
+    }
+    emulator.write_pc(0, BASE);
+    emulator.write_register(0, 2, BASE + 0x1100);
+    emulator.write_register(0, 5, u64::MAX);
+    emulator.stop_when(StopConditions {
+        instructions: Some(INSTRUCTIONS),
+        ..StopConditions::default()
+    });
+    let start = Instant::now();
+    emulator.run().unwrap();
+    let seconds = start.elapsed().as_secs_f64();
+    println!(
+        "{:<16} {:>10.1} MIPS",
+        "loads and stores",
+        INSTRUCTIONS as f64 / seconds / 1e6
+    );
+
+    let mut emulator = Emulator::default();
+    let bytes = (0..BULK_SIZE).map(|i| i as u8 | 1).collect::<Vec<_>>();
+    let start = Instant::now();
+    emulator.write_memory(BASE, &bytes).unwrap();
+    report("write_memory", BULK_SIZE, start);
+    let start = Instant::now();
+    let read = emulator.read_memory(BASE, BULK_SIZE).unwrap();
+    report("read_memory", BULK_SIZE, start);
+    assert_eq!(read, bytes);
+}
+
+fn report(name: &str, bytes: u64, start: Instant) {
+    let seconds = start.elapsed().as_secs_f64();
+    println!(
+        "{:<16} {:>10.1} MiB/s",
+        name,
+        bytes as f64 / seconds / (1024.0 * 1024.0)
+    );
+}
@@ -1,19 +1,185 @@
+mod monitor;
+
 use clap::Clap;
-use five::emulator::Emulator;
+use five::emulator::{
+    gdb,
+    lockstep::{self, Verdict},
+    replay, snapshot, Config, Costs, Emulator, EmulatorError, Misaligned, RunOutcome,
+    StopConditions,
+};
 use std::fs::File;
-use std::io::Result;
+use std::io::{BufReader, BufWriter, Read, Result};
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::time::Duration;
 
 #[derive(Clap)]
 struct Opts {
-    input: String,
+    /// A flat binary or an ELF executable to run, or the firmware to start the kernel with
+    input: Option<String>,
+    /// The arguments of a Linux, proxy kernel or semihosting program
+    args: Vec<String>,
+    /// Run the input as a static Linux executable in user mode
+    #[clap(long, conflicts_with = "kernel")]
+    linux: bool,
+    /// Run the input as a newlib executable under an emulated proxy kernel in user mode
+    #[clap(long, conflicts_with_all = &["kernel", "linux"])]
+    pk: bool,
+    /// Service the semihosting calls of the program
+    #[clap(long)]
+    semihosting: bool,
+    /// A Linux Image to boot with the firmware given as the input
+    #[clap(long)]
+    kernel: Option<String>,
+    /// An initial ramdisk for the kernel
+    #[clap(long, requires = "kernel")]
+    initrd: Option<String>,
+    /// The kernel command line
+    #[clap(long, requires = "kernel")]
+    append: Option<String>,
+    /// The number of harts
+    #[clap(long, default_value = "1")]
+    harts: usize,
+    /// The number of instructions each hart runs before switching to the next
+    #[clap(long, default_value = "100")]
+    quantum: u64,
+    /// The size of the memory in MiB
+    #[clap(long, default_value = "1024", parse(try_from_str = mebibytes))]
+    memory: u64,
+    /// The address the memory starts at
+    #[clap(long, default_value = "0x80000000", parse(try_from_str = number))]
+    memory_base: u64,
+    /// What a misaligned load or store does: carry on as if aligned, trap as misaligned for the
+    /// firmware to emulate, or fault
+    #[clap(
+        long,
+        default_value = "emulate",
+        possible_values = &["emulate", "trap", "fault"],
+        parse(try_from_str = misaligned)
+    )]
+    misaligned: Misaligned,
+    /// The cycles the kinds of instructions take, which mcycle counts, as kind=cycles pairs
+    /// separated by commas, e.g. load=2,divide=20. The kinds are alu, load, store, multiply,
+    /// divide, jump and system, each of which takes a cycle otherwise
+    #[clap(long, parse(try_from_str = costs))]
+    cycles: Option<Costs>,
+    /// Log the retired instructions and the traps to stderr like `spike -l --log-commits`
+    #[clap(long)]
+    log_commits: bool,
+    /// Run in lockstep with a commit log of Spike, stopping at the first divergence
+    #[clap(long, conflicts_with = "gdb")]
+    lockstep: Option<String>,
+    /// Wait for GDB on a TCP address (host:port) or a UNIX socket path before running
+    #[clap(long)]
+    gdb: Option<String>,
+    /// Stop after the harts have executed this many instructions
+    #[clap(long)]
+    max_instructions: Option<u64>,
+    /// Stop after running for this many seconds
+    #[clap(long)]
+    timeout: Option<f64>,
+    /// Stop before executing the instruction at an address or a symbol
+    #[clap(long, number_of_values = 1)]
+    stop_at: Vec<String>,
+    /// Stop after a store to an address or a symbol
+    #[clap(long, number_of_values = 1)]
+    watch: Vec<String>,
+    /// Save a snapshot of the machine to a file when the run stops
+    #[clap(long)]
+    save: Option<String>,
+    /// Resume the machine from a snapshot instead of loading a program
+    #[clap(long, conflicts_with_all = &["kernel", "linux", "pk", "semihosting"])]
+    restore: Option<String>,
+    /// Record the inputs the program takes from the host to a file, to replay the run
+    #[clap(long, conflicts_with_all = &["kernel", "linux", "pk", "semihosting"])]
+    record: Option<String>,
+    /// Inspect and run the machine from a monitor console, which Ctrl-C returns to
+    #[clap(long, conflicts_with_all = &["gdb", "lockstep", "record", "save"])]
+    monitor: bool,
+    /// Replay a recorded run instead of loading a program
+    #[clap(long, conflicts_with_all = &["kernel", "linux", "pk", "semihosting", "restore"])]
+    replay: Option<String>,
 }
 
 fn main() -> Result<()> {
-    let input = Opts::parse().input;
+    let opts = Opts::parse();
+    // a snapshot or a recording is restored on a machine configured as the one it has been made
+    // with, and the quantum is the one it has been made with
+    let mut emulator = match (&opts.restore, &opts.replay) {
+        (Some(path), _) => snapshot::restore(config(&opts), BufReader::new(File::open(path)?))?,
+        (_, Some(path)) => replay::restore(config(&opts), BufReader::new(File::open(path)?))?,
+        _ => Emulator::new(config(&opts)),
+    };
+    if opts.log_commits {
+        emulator.trace(std::io::stderr());
+    }
+    if opts.restore.is_some() || opts.replay.is_some() {
+        std::process::exit(start(&mut emulator, &opts));
+    }
+    if let Some(kernel) = &opts.kernel {
+        let firmware = match &opts.input {
+            Some(firmware) => File::open(firmware)?,
+            None => {
+                eprintln!("--kernel requires a firmware binary (e.g. OpenSBI fw_jump.bin)");
+                std::process::exit(2);
+            }
+        };
+        let initrd = opts.initrd.as_ref().map(File::open).transpose()?;
+        emulator.load_kernel(
+            firmware,
+            File::open(kernel)?,
+            initrd,
+            opts.append.as_deref().unwrap_or(""),
+        )?;
+        std::process::exit(start(&mut emulator, &opts));
+    }
+    let input = match opts.input.clone() {
+        Some(input) => input,
+        None => {
+            eprintln!("either a binary or --kernel is required");
+            std::process::exit(2);
+        }
+    };
     let file = File::open(&input)?;
-    let mut emulator = Emulator::default();
+    if opts.semihosting {
+        let mut command_line = vec![input.clone()];
+        command_line.extend(opts.args.iter().cloned());
+        emulator.enable_semihosting(&command_line.join(" "));
+    }
+    if opts.linux {
+        let mut args = vec![input];
+        args.extend(opts.args.iter().cloned());
+        let envs = std::env::vars()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>();
+        emulator.load_linux_executable(file, &args, &envs)?;
+        std::process::exit(start(&mut emulator, &opts));
+    }
+    if opts.pk {
+        let mut args = vec![input];
+        args.extend(opts.args.iter().cloned());
+        emulator.load_pk_executable(file, &args)?;
+        std::process::exit(start(&mut emulator, &opts));
+    }
+    if is_elf(&input)? {
+        // a bare-metal program reports its exit status through HTIF
+        emulator.load_elf(file)?;
+        std::process::exit(start(&mut emulator, &opts));
+    }
     emulator.load(file)?;
-    let result = emulator.run();
+    if opts.monitor {
+        std::process::exit(start(&mut emulator, &opts));
+    }
+    // a flat binary stops at the end of the program with the result in gp
+    let result = match run(&mut emulator, &opts) {
+        Ok(Some(RunOutcome::Exited(status))) => status,
+        Ok(None)
+        | Ok(Some(RunOutcome::Breakpoint { .. }))
+        | Err(EmulatorError::IllegalInstruction { .. })
+        | Err(EmulatorError::UnmappedAccess { .. }) => emulator.read_register(0, 3),
+        result => std::process::exit(status(result)),
+    };
     if result == 1 {
         println!("PASS: {}", input);
     } else {
@@ -21,3 +187,228 @@ fn main() -> Result<()> {
     }
     Ok(())
 }
+
+fn config(opts: &Opts) -> Config {
+    Config {
+        harts: opts.harts,
+        quantum: opts.quantum,
+        memory_base: opts.memory_base,
+        memory_size: opts.memory,
+        misaligned: opts.misaligned,
+        costs: opts.cycles.unwrap_or_default(),
+    }
+}
+
+// Runs the program, or hands it over to the monitor when asked to, and returns the exit status.
+fn start(emulator: &mut Emulator, opts: &Opts) -> i32 {
+    if !opts.monitor {
+        return status(run(emulator, opts));
+    }
+    match conditions(emulator, opts) {
+        Ok(conditions) => monitor::serve(emulator, config(opts), conditions),
+        Err(error) => status(Err(error.into())),
+    }
+}
+
+// Runs the program until it stops, recording it and saving a snapshot of the machine then when
+// asked to. There is no outcome when the reference of lockstep ends before the program.
+fn run(
+    emulator: &mut Emulator,
+    opts: &Opts,
+) -> std::result::Result<Option<RunOutcome>, EmulatorError> {
+    let conditions = conditions(emulator, opts)?;
+    emulator.stop_when(conditions);
+    if opts.record.is_some() {
+        replay::record(emulator)?;
+    }
+    // a run which has stopped with an error is recorded as well
+    let result = execute(emulator, opts);
+    if let Some(path) = &opts.record {
+        replay::save(emulator, BufWriter::new(File::create(path)?))?;
+    }
+    let outcome = result?;
+    if let Some(path) = &opts.save {
+        snapshot::save(emulator, BufWriter::new(File::create(path)?))?;
+    }
+    Ok(outcome)
+}
+
+// Runs the program, in lockstep with a reference commit log or under the control of GDB when
+// asked to.
+fn execute(
+    emulator: &mut Emulator,
+    opts: &Opts,
+) -> std::result::Result<Option<RunOutcome>, EmulatorError> {
+    if let Some(reference) = &opts.lockstep {
+        let reference = BufReader::new(File::open(reference)?);
+        match lockstep::run(emulator, reference)? {
+            Verdict::Matched {
+                instructions,
+                outcome,
+            } => {
+                eprintln!("{} instructions matched the reference", instructions);
+                return Ok(outcome);
+            }
+            Verdict::Mismatched(mismatch) => {
+                eprint!("{}", mismatch);
+                std::process::exit(1);
+            }
+        }
+    }
+    let address = match &opts.gdb {
+        Some(address) => address,
+        None => return emulator.run().map(Some),
+    };
+    eprintln!("waiting for gdb on {}", address);
+    let outcome = if address.contains(':') {
+        let (stream, _) = TcpListener::bind(address)?.accept()?;
+        gdb::serve(emulator, stream)?
+    } else {
+        serve_unix(emulator, address)?
+    };
+    match outcome {
+        Some(outcome) => Ok(Some(outcome)),
+        // the debugger has killed the program
+        None => std::process::exit(0),
+    }
+}
+
+fn conditions(emulator: &Emulator, opts: &Opts) -> Result<StopConditions> {
+    let addresses = |locations: &[String]| {
+        locations
+            .iter()
+            .map(|location| address(emulator, location))
+            .collect::<Result<Vec<_>>>()
+    };
+    Ok(StopConditions {
+        instructions: opts.max_instructions,
+        timeout: opts.timeout.map(Duration::from_secs_f64),
+        breakpoints: addresses(&opts.stop_at)?,
+        watchpoints: addresses(&opts.watch)?,
+        interrupt: None,
+    })
+}
+
+// The exit status of the program when it has exited, otherwise 1 after telling why it has
+// stopped.
+fn status(result: std::result::Result<Option<RunOutcome>, EmulatorError>) -> i32 {
+    match result {
+        Ok(Some(RunOutcome::Exited(status))) => status as i32,
+        Ok(None) => 0,
+        Ok(Some(RunOutcome::BudgetExhausted)) => {
+            eprintln!("the program has run out of instructions");
+            1
+        }
+        Ok(Some(RunOutcome::TimedOut)) => {
+            eprintln!("the program has run out of time");
+            1
+        }
+        Ok(Some(RunOutcome::Interrupted)) => {
+            eprintln!("the program has been interrupted");
+            1
+        }
+        Ok(Some(RunOutcome::Reached { hartid, pc })) => {
+            eprintln!("hart {} reached {:#x}", hartid, pc);
+            0
+        }
+        Ok(Some(RunOutcome::Written {
+            hartid,
+            pc,
+            address,
+        })) => {
+            eprintln!("hart {} stored to {:#x} at {:#x}", hartid, address, pc);
+            0
+        }
+        Ok(Some(RunOutcome::Breakpoint { hartid, pc })) => {
+            eprintln!("hart {} stopped at the breakpoint at {:#x}", hartid, pc);
+            1
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            1
+        }
+    }
+}
+
+#[cfg(unix)]
+fn serve_unix(
+    emulator: &mut Emulator,
+    path: &str,
+) -> std::result::Result<Option<RunOutcome>, EmulatorError> {
+    let listener = UnixListener::bind(path)?;
+    let (stream, _) = listener.accept()?;
+    std::fs::remove_file(path)?;
+    gdb::serve(emulator, stream)
+}
+
+#[cfg(not(unix))]
+fn serve_unix(_: &mut Emulator, _: &str) -> std::result::Result<Option<RunOutcome>, EmulatorError> {
+    Err(EmulatorError::Io(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "UNIX sockets are not supported on this platform",
+    )))
+}
+
+// the address a location on the command line stands for, which is a number or a symbol
+fn address(emulator: &Emulator, location: &str) -> Result<u64> {
+    number(location)
+        .ok()
+        .or_else(|| emulator.symbol(location))
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} is neither an address nor a symbol", location),
+            )
+        })
+}
+
+fn number(text: &str) -> std::result::Result<u64, String> {
+    let number = match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    };
+    number.ok_or_else(|| format!("{} is not a number", text))
+}
+
+// the bytes of a size in MiB
+fn mebibytes(text: &str) -> std::result::Result<u64, String> {
+    number(text)?
+        .checked_mul(1 << 20)
+        .ok_or_else(|| format!("{} MiB is too large a memory", text))
+}
+
+fn misaligned(text: &str) -> std::result::Result<Misaligned, String> {
+    match text {
+        "emulate" => Ok(Misaligned::Emulate),
+        "trap" => Ok(Misaligned::Trap),
+        "fault" => Ok(Misaligned::Fault),
+        _ => Err(format!("{} is not a policy for misaligned accesses", text)),
+    }
+}
+
+fn costs(text: &str) -> std::result::Result<Costs, String> {
+    let mut costs = Costs::default();
+    for pair in text.split(',') {
+        let (kind, cycles) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("{} is not a kind=cycles pair", pair))?;
+        let cost = match kind {
+            "alu" => &mut costs.alu,
+            "load" => &mut costs.load,
+            "store" => &mut costs.store,
+            "multiply" => &mut costs.multiply,
+            "divide" => &mut costs.divide,
+            "jump" => &mut costs.jump,
+            "system" => &mut costs.system,
+            _ => return Err(format!("{} is not a kind of instructions", kind)),
+        };
+        *cost = number(cycles)?;
+    }
+    Ok(costs)
+}
+
+fn is_elf(path: &str) -> Result<bool> {
+    let mut magic = [0; 4];
+    let read = File::open(path)?.read(&mut magic)?;
+    Ok(read == magic.len() && &magic == b"\x7fELF")
+}
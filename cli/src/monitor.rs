@@ -0,0 +1,339 @@
+use crate::{address, number, status};
+use five::emulator::{
+    disassemble, snapshot, Config, Emulator, RunOutcome, StopConditions, ABI_NAMES,
+};
+use std::fs::File;
+use std::io::{stdin, stdout, BufRead, BufReader, BufWriter, Write};
+#[cfg(unix)]
+use std::sync::OnceLock;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+const PROMPT: &str = "(five) ";
+const HELP: &str = "\
+regs                   the integer registers and the pc of the hart
+csr <name|address>     a CSR of the hart
+x/<n><size><format> <location>
+                       examine n units of memory, the size of which is b, h, w or g and
+                       the format x, d or i
+disas [location] [n]   disassemble n instructions from the location, the pc by default
+break <location>       stop before executing the instruction at the location
+watch <location>       stop after a store to the location
+delete <location>      remove the breakpoint or the watchpoint at the location
+info break             the breakpoints and the watchpoints
+info tlb               the translations of the addresses
+step [n]               execute n instructions across the harts
+continue               run until the program stops, or Ctrl-C is pressed
+snapshot save <file>   save the machine to a file
+snapshot load <file>   restore the machine from a file
+devices                what is mapped in the address space
+hart <id>              select the hart the registers are read from
+quit                   leave the emulator
+A location is an address, a symbol, a register or pc.";
+
+struct Monitor<'a> {
+    emulator: &'a mut Emulator,
+    // the machine a snapshot is loaded on
+    config: Config,
+    conditions: StopConditions,
+    // the hart the registers are read from
+    hart: usize,
+}
+
+// Reads commands to inspect and run the machine from stdin until the program exits or the user
+// quits, and returns the exit status. Ctrl-C stops the run and returns to the prompt.
+pub fn serve(emulator: &mut Emulator, config: Config, mut conditions: StopConditions) -> i32 {
+    let interrupt = Arc::new(AtomicBool::new(false));
+    catch_interrupts(interrupt.clone());
+    conditions.interrupt = Some(interrupt);
+    let mut monitor = Monitor {
+        emulator,
+        config,
+        conditions,
+        hart: 0,
+    };
+    monitor.show_location();
+    let stdin = stdin();
+    loop {
+        print!("{}", PROMPT);
+        let _ = stdout().flush();
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => return 0,
+            Ok(_) => {}
+        }
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        if words.is_empty() {
+            continue;
+        }
+        match monitor.execute(&words) {
+            Ok(Some(status)) => return status,
+            Ok(None) => {}
+            Err(message) => println!("{}", message),
+        }
+    }
+}
+
+impl Monitor<'_> {
+    // Executes the command, and returns the exit status when the monitor is to be left.
+    fn execute(&mut self, words: &[&str]) -> Result<Option<i32>, String> {
+        let (command, arguments) = (words[0], &words[1..]);
+        match (command, arguments) {
+            ("help", _) => println!("{}", HELP),
+            ("quit", _) | ("q", _) => return Ok(Some(0)),
+            ("regs", []) => self.show_registers(),
+            ("csr", [csr]) => self.show_csr(csr)?,
+            ("disas", _) => {
+                let address = match arguments.first() {
+                    Some(location) => self.location(location)?,
+                    None => self.emulator.read_pc(self.hart),
+                };
+                let count = arguments.get(1).map_or(Ok(10), |count| number(count))?;
+                self.examine(address, count, 4, 'i')?;
+            }
+            ("break", [location]) => {
+                let address = self.location(location)?;
+                self.conditions.breakpoints.push(address);
+            }
+            ("watch", [location]) => {
+                let address = self.location(location)?;
+                self.conditions.watchpoints.push(address);
+            }
+            ("delete", [location]) => {
+                let address = self.location(location)?;
+                self.conditions.breakpoints.retain(|&a| a != address);
+                self.conditions.watchpoints.retain(|&a| a != address);
+            }
+            ("info", ["break"]) => {
+                for address in &self.conditions.breakpoints {
+                    println!("breakpoint at {:#x}", address);
+                }
+                for address in &self.conditions.watchpoints {
+                    println!("watchpoint at {:#x}", address);
+                }
+            }
+            ("info", ["tlb"]) => println!("there is no TLB, as the addresses are not translated"),
+            ("step", _) | ("s", _) => {
+                let count = arguments.first().map_or(Ok(1), |count| number(count))?;
+                return Ok(self.run(Some(count)));
+            }
+            ("continue", []) | ("c", []) => return Ok(self.run(None)),
+            ("snapshot", ["save", path]) => {
+                let file = File::create(path).map_err(|e| e.to_string())?;
+                snapshot::save(self.emulator, BufWriter::new(file)).map_err(|e| e.to_string())?;
+            }
+            ("snapshot", ["load", path]) => {
+                let file = File::open(path).map_err(|e| e.to_string())?;
+                *self.emulator = snapshot::restore(self.config, BufReader::new(file))
+                    .map_err(|e| e.to_string())?;
+                self.hart = 0;
+                self.show_location();
+            }
+            ("devices", []) => {
+                for device in self.emulator.devices() {
+                    println!(
+                        "{:#018x}-{:#018x} {}",
+                        device.base,
+                        device.base + device.size,
+                        device.name
+                    );
+                }
+            }
+            ("hart", [hartid]) => {
+                let hartid = number(hartid)? as usize;
+                if hartid >= self.emulator.harts() {
+                    return Err(format!("there is no hart {}", hartid));
+                }
+                self.hart = hartid;
+                self.show_location();
+            }
+            _ if command.starts_with("x") => match arguments {
+                [location] => {
+                    let (count, size, format) = unit(command)?;
+                    let address = self.location(location)?;
+                    self.examine(address, count, size, format)?;
+                }
+                _ => return Err("x needs a location".to_string()),
+            },
+            _ => return Err(format!("unknown command {}, try help", words.join(" "))),
+        }
+        Ok(None)
+    }
+
+    // Runs the machine for the instructions, or until it stops, and returns the exit status when
+    // the program has exited.
+    fn run(&mut self, instructions: Option<u64>) -> Option<i32> {
+        let mut conditions = self.conditions.clone();
+        conditions.instructions = instructions;
+        if let Some(interrupt) = &conditions.interrupt {
+            interrupt.store(false, Ordering::Relaxed);
+        }
+        self.emulator.stop_when(conditions);
+        match self.emulator.run() {
+            Ok(RunOutcome::Exited(code)) => {
+                println!("the program has exited with {}", code);
+                return Some(code as i32);
+            }
+            Ok(RunOutcome::BudgetExhausted) if instructions.is_some() => {}
+            Ok(RunOutcome::Interrupted) => println!("interrupted"),
+            result => {
+                status(result.map(Some));
+            }
+        }
+        self.show_location();
+        None
+    }
+
+    fn show_location(&self) {
+        let pc = self.emulator.read_pc(self.hart);
+        println!("hart {} at {}", self.hart, self.instruction(pc));
+    }
+
+    fn show_registers(&self) {
+        for (register, name) in ABI_NAMES.iter().enumerate() {
+            print!(
+                "{:<4} {:#018x}{}",
+                name,
+                self.emulator.read_register(self.hart, register),
+                if register % 4 == 3 { "\n" } else { "  " }
+            );
+        }
+        println!("pc   {:#018x}", self.emulator.read_pc(self.hart));
+    }
+
+    fn show_csr(&mut self, csr: &str) -> Result<(), String> {
+        let address = match self.emulator.csr_address(csr) {
+            Some(address) => address,
+            None => number(csr)?,
+        };
+        match self.emulator.read_csr(self.hart, address) {
+            Some(value) => {
+                println!("{} {:#018x}", csr, value);
+                Ok(())
+            }
+            None => Err(format!("the hart has no CSR {}", csr)),
+        }
+    }
+
+    // prints the units of memory from the address, in the format x, d or i
+    fn examine(&self, address: u64, count: u64, size: u64, format: char) -> Result<(), String> {
+        if format == 'i' {
+            for i in 0..count {
+                println!("{}", self.instruction(address + i * 4));
+            }
+            return Ok(());
+        }
+        let bytes = self
+            .emulator
+            .read_memory(address, count * size)
+            .map_err(|e| e.to_string())?;
+        for (line, units) in bytes.chunks(16).enumerate() {
+            print!("{:#018x}:", address + line as u64 * 16);
+            for unit in units.chunks(size as usize) {
+                let value = unit
+                    .iter()
+                    .rev()
+                    .fold(0u64, |value, &byte| value << 8 | byte as u64);
+                match format {
+                    'd' => print!(" {}", value),
+                    _ => print!(" {:#0width$x}", value, width = size as usize * 2 + 2),
+                }
+            }
+            println!();
+        }
+        Ok(())
+    }
+
+    // the address and the disassembly of the instruction at the address
+    fn instruction(&self, address: u64) -> String {
+        match self.emulator.read_memory(address, 4) {
+            Ok(bytes) => {
+                let instruction = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                format!(
+                    "{:#018x}: {:08x} {}",
+                    address,
+                    instruction,
+                    disassemble(instruction, address)
+                )
+            }
+            Err(_) => format!("{:#018x}: out of memory", address),
+        }
+    }
+
+    // the address a location stands for, which is pc, a register, an address or a symbol
+    fn location(&self, location: &str) -> Result<u64, String> {
+        if location == "pc" {
+            return Ok(self.emulator.read_pc(self.hart));
+        }
+        let register = ABI_NAMES
+            .iter()
+            .position(|&name| name == location)
+            .or_else(|| {
+                location
+                    .strip_prefix('x')
+                    .and_then(|number| number.parse().ok())
+                    .filter(|&register| register < 32)
+            });
+        match register {
+            Some(register) => Ok(self.emulator.read_register(self.hart, register)),
+            None => address(self.emulator, location).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+// the count, the size and the format of the units of an x command such as x/16xw
+fn unit(command: &str) -> Result<(u64, u64, char), String> {
+    let spec = match command.strip_prefix("x") {
+        Some("") => "",
+        Some(spec) => spec
+            .strip_prefix('/')
+            .ok_or_else(|| format!("unknown command {}", command))?,
+        None => return Err(format!("unknown command {}", command)),
+    };
+    let digits = spec.chars().take_while(char::is_ascii_digit).count();
+    let count = if digits == 0 {
+        1
+    } else {
+        number(&spec[..digits])?
+    };
+    let (mut size, mut format) = (4, 'x');
+    for letter in spec[digits..].chars() {
+        match letter {
+            'b' => size = 1,
+            'h' => size = 2,
+            'w' => size = 4,
+            'g' => size = 8,
+            'x' | 'd' | 'i' => format = letter,
+            _ => return Err(format!("unknown unit {} of {}", letter, command)),
+        }
+    }
+    Ok((count, size, format))
+}
+
+// raises the flag when Ctrl-C is pressed
+#[cfg(unix)]
+fn catch_interrupts(interrupt: Arc<AtomicBool>) {
+    const SIGINT: i32 = 2;
+    static INTERRUPT: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+    extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+    }
+
+    extern "C" fn raise(_: i32) {
+        if let Some(interrupt) = INTERRUPT.get() {
+            interrupt.store(true, Ordering::Relaxed);
+        }
+    }
+
+    if INTERRUPT.set(interrupt).is_ok() {
+        unsafe {
+            signal(SIGINT, raise);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn catch_interrupts(_: Arc<AtomicBool>) {}
@@ -1,31 +1,1240 @@
 mod bus;
+mod clint;
 mod cpu;
+mod elf;
+mod fdt;
+pub mod gdb;
+mod htif;
+pub mod lockstep;
 mod memory;
+mod outcome;
+pub mod replay;
+pub mod snapshot;
+mod syscall;
 
-use crate::emulator::{bus::Size, cpu::Cpu, memory::MEMORY_BASE_ADDRESS};
+pub use bus::Misaligned;
+pub use cpu::{cost::Costs, csr::counters::Event, disassembler::disassemble, x::ABI_NAMES, Step};
+pub use outcome::{EmulatorError, RunOutcome};
+
+use crate::emulator::{
+    bus::SystemBus,
+    clint::{CLINT_BASE_ADDRESS, CLINT_SIZE},
+    cpu::{
+        csr,
+        mode::PrivilegeMode,
+        trace::{CommitLog, Tracer},
+        trap::Exception,
+        x::SP,
+        Cpu,
+    },
+    elf::Elf,
+    fdt::Chosen,
+    htif::Htif,
+    memory::{Memory, MEMORY_BASE_ADDRESS, MEMORY_SIZE},
+    replay::History,
+    syscall::{
+        linux::{self, Linux},
+        newlib::Newlib,
+        semihosting::Semihosting,
+        Kernel, STACK_SIZE,
+    },
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufReader, Read, Result};
+use std::io::{BufReader, Error, ErrorKind, Read, Result, Write};
+use std::rc::Rc;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::{Duration, Instant};
+
+const PAGE_SIZE: u64 = 4096;
+
+// the fields of the header at the beginning of a RISC-V Linux Image
+const IMAGE_HEADER_SIZE: usize = 64;
+const IMAGE_TEXT_OFFSET: usize = 8;
+const IMAGE_MAGIC2: usize = 56;
+const IMAGE_MAGIC2_VALUE: &[u8] = b"RSC\x05";
+const DEFAULT_TEXT_OFFSET: u64 = 0x20_0000;
+
+const DEFAULT_QUANTUM: u64 = 100;
+
+#[derive(Clone, Copy)]
+pub struct Config {
+    // the number of harts sharing the bus
+    pub harts: usize,
+    // the number of instructions a hart executes before the next hart takes its turn
+    pub quantum: u64,
+    // where the memory starts in the address space, and its size in bytes
+    pub memory_base: u64,
+    pub memory_size: u64,
+    // what a misaligned load or store does
+    pub misaligned: Misaligned,
+    // the cycles the instructions take, which mcycle counts
+    pub costs: Costs,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            harts: 1,
+            quantum: DEFAULT_QUANTUM,
+            memory_base: MEMORY_BASE_ADDRESS,
+            memory_size: MEMORY_SIZE,
+            misaligned: Misaligned::default(),
+            costs: Costs::default(),
+        }
+    }
+}
+
+// What stops a run before the program exits, besides an error.
+#[derive(Default, Clone)]
+pub struct StopConditions {
+    // the number of instructions the harts may execute across them
+    pub instructions: Option<u64>,
+    // how long the run may take on the host
+    pub timeout: Option<Duration>,
+    // the addresses of the instructions to stop at, except the one a hart resumes from
+    pub breakpoints: Vec<u64>,
+    // the addresses a store to which stops the run
+    pub watchpoints: Vec<u64>,
+    // the flag the host raises to stop the run, which is lowered when the run stops
+    pub interrupt: Option<Arc<AtomicBool>>,
+}
+
+// a range of the addresses and what is mapped there
+#[derive(Debug, Clone, PartialEq)]
+pub struct Device {
+    pub name: &'static str,
+    pub base: u64,
+    pub size: u64,
+}
+
+// the hart whose turn it is to execute and the instructions it has executed in its turn
+#[derive(Default, Clone, Copy)]
+struct Turn {
+    hartid: usize,
+    executed: u64,
+}
 
-#[allow(dead_code)]
-#[derive(Default)]
 pub struct Emulator {
-    cpu: Cpu,
+    harts: Vec<Cpu>,
+    bus: SystemBus,
+    quantum: u64,
+    turn: Turn,
+    // the kernel emulated for a user program
+    kernel: Option<Kernel>,
+    htif: Option<Htif>,
+    semihosting: Option<Semihosting>,
+    exit_code: Option<u64>,
+    stop: StopConditions,
+    // the symbols of the ELF executable loaded
+    symbols: HashMap<String, u64>,
+    // the inputs from the host recorded or replayed
+    history: Option<History>,
+}
+
+impl Default for Emulator {
+    fn default() -> Self {
+        Self::new(Config::default())
+    }
 }
 
 impl Emulator {
+    pub fn new(config: Config) -> Self {
+        let harts = config.harts.max(1);
+        Self {
+            harts: (0..harts)
+                .map(|hartid| {
+                    let mut hart = Cpu::new(hartid);
+                    hart.write_pc(config.memory_base);
+                    hart.set_costs(config.costs);
+                    hart
+                })
+                .collect(),
+            bus: SystemBus {
+                misaligned: config.misaligned,
+                ..SystemBus::new(harts, Memory::new(config.memory_base, config.memory_size))
+            },
+            quantum: config.quantum.max(1),
+            turn: Turn::default(),
+            kernel: None,
+            htif: None,
+            semihosting: None,
+            exit_code: None,
+            stop: StopConditions::default(),
+            symbols: HashMap::new(),
+            history: None,
+        }
+    }
+
     pub fn load(&mut self, file: File) -> Result<()> {
-        let buffer = BufReader::new(file);
-        for (address, byte) in buffer.bytes().enumerate() {
-            self.cpu.bus.memory.store(
-                address as u64 + MEMORY_BASE_ADDRESS,
-                byte? as u64,
-                Size::Byte,
-            );
+        self.write_memory(self.bus.memory.base(), &read(file)?)
+    }
+
+    // Loads the firmware (e.g. OpenSBI fw_jump) at the beginning of memory, a Linux Image, an
+    // optional initrd and a device tree describing both, then starts the harts at the firmware
+    // in M-mode the way the boot protocol expects: a0 holds the hart ID and a1 the address of
+    // the device tree. five does not emulate SBI, so the firmware has to service the calls of
+    // the kernel. The machine is not one a stock kernel boots to a shell on: the harts have no
+    // MMU and no A, C, F or D extensions, and there is no UART or PLIC for a console.
+    pub fn load_kernel(
+        &mut self,
+        firmware: File,
+        image: File,
+        initrd: Option<File>,
+        bootargs: &str,
+    ) -> Result<()> {
+        self.load(firmware)?;
+        let image = read(image)?;
+        let memory = (self.bus.memory.base(), self.bus.memory.size());
+        let kernel_address = memory.0 + text_offset(&image);
+        let kernel_end = kernel_address + image.len() as u64;
+        self.write_memory(kernel_address, &image)?;
+
+        let memory_end = self.bus.memory.end();
+        let initrd = initrd.map(read).transpose()?;
+        // the size of the device tree does not depend on the initrd addresses, so a draft with
+        // placeholders decides the layout at the top of memory
+        let harts = self.harts.len();
+        let dtb_size = fdt::generate(
+            harts,
+            memory,
+            &Chosen {
+                bootargs,
+                initrd: initrd.as_ref().map(|_| (0, 0)),
+            },
+        )
+        .len() as u64;
+        let dtb_address = align_down(memory_end - dtb_size, PAGE_SIZE);
+        let initrd_range = initrd.as_ref().map(|bytes| {
+            let start = align_down(dtb_address.saturating_sub(bytes.len() as u64), PAGE_SIZE);
+            (start, start + bytes.len() as u64)
+        });
+        if initrd_range.map_or(dtb_address, |(start, _)| start) < kernel_end {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "the kernel, initrd and device tree do not fit in memory",
+            ));
+        }
+        if let (Some(bytes), Some((start, _))) = (&initrd, initrd_range) {
+            self.write_memory(start, bytes)?;
+        }
+        let dtb = fdt::generate(
+            harts,
+            memory,
+            &Chosen {
+                bootargs,
+                initrd: initrd_range,
+            },
+        );
+        self.write_memory(dtb_address, &dtb)?;
+
+        for (hartid, hart) in self.harts.iter_mut().enumerate() {
+            hart.boot(PrivilegeMode::Machine, memory.0, hartid as u64, dtb_address);
+        }
+        Ok(())
+    }
+
+    // Loads a bare-metal ELF executable and starts every hart at its entry in M-mode. When the
+    // program has the `tohost` symbol, it reaches the host through HTIF, whose system calls
+    // follow the newlib convention.
+    pub fn load_elf(&mut self, file: File) -> Result<()> {
+        let elf = Elf::parse(&read(file)?)?;
+        self.symbols = elf.symbols.clone();
+        for segment in &elf.segments {
+            self.store_segment(segment.address, segment.size, &segment.data)?;
+        }
+        if let Some(tohost) = elf.symbol("tohost") {
+            let brk = align_up(elf.end(), PAGE_SIZE);
+            let newlib = Newlib::new(brk, self.bus.memory.end());
+            self.htif = Some(Htif::new(tohost, elf.symbol("fromhost"), newlib));
+        }
+        for (hartid, hart) in self.harts.iter_mut().enumerate() {
+            hart.boot(PrivilegeMode::Machine, elf.entry, hartid as u64, 0);
+        }
+        Ok(())
+    }
+
+    // Loads a static Linux executable into a memory of its own and sets up the stack with the
+    // arguments and the environment, so that the program starts in U-mode and its system calls
+    // are serviced on the host.
+    pub fn load_linux_executable(
+        &mut self,
+        file: File,
+        args: &[String],
+        envs: &[String],
+    ) -> Result<()> {
+        let elf = Elf::parse(&read(file)?)?;
+        self.symbols = elf.symbols.clone();
+        let stack_top = self.load_user_program(&elf, args, envs)?;
+        self.kernel = Some(Kernel::Linux(Linux::new(&elf, stack_top)));
+        Ok(())
+    }
+
+    // Loads an executable linked against newlib to run in U-mode as under riscv-pk, which
+    // services its system calls on the host.
+    pub fn load_pk_executable(&mut self, file: File, args: &[String]) -> Result<()> {
+        let elf = Elf::parse(&read(file)?)?;
+        self.symbols = elf.symbols.clone();
+        let stack_top = self.load_user_program(&elf, args, &[])?;
+        let brk = align_up(elf.end(), PAGE_SIZE);
+        let newlib = Newlib::new(brk, stack_top - STACK_SIZE);
+        self.kernel = Some(Kernel::ProxyKernel(newlib));
+        Ok(())
+    }
+
+    // Lets the program request the semihosting operations, with the command line it asks for.
+    pub fn enable_semihosting(&mut self, command_line: &str) {
+        self.semihosting = Some(Semihosting::new(command_line));
+    }
+
+    // Logs the instructions the harts retire and the traps they take to the output in the format
+    // of `spike -l --log-commits`.
+    pub fn trace(&mut self, output: impl Write + 'static) {
+        let tracer: Rc<RefCell<dyn Tracer>> = Rc::new(RefCell::new(CommitLog::new(output)));
+        for hart in self.harts.iter_mut() {
+            hart.trace(Some(tracer.clone()));
+        }
+    }
+
+    // Lets runs stop under the conditions as well as when the program exits.
+    pub fn stop_when(&mut self, conditions: StopConditions) {
+        self.bus.watchpoints = conditions.watchpoints.clone();
+        self.stop = conditions;
+    }
+
+    // the address of the symbol in the ELF executable loaded
+    pub fn symbol(&self, name: &str) -> Option<u64> {
+        self.symbols.get(name).cloned()
+    }
+
+    // Runs the harts in turn, each for the quantum, until the program exits, a hart stops or one
+    // of the stop conditions is met.
+    pub fn run(&mut self) -> std::result::Result<RunOutcome, EmulatorError> {
+        // the clock is read only for a timeout, as there is none on wasm32
+        let deadline = self.stop.timeout.map(|timeout| (Instant::now(), timeout));
+        let mut remaining = self.stop.instructions.unwrap_or(u64::MAX);
+        let mut resuming = true;
+        self.bus.hit = None;
+        loop {
+            let hartid = self.turn();
+            if let Some(status) = self.exit_code {
+                return Ok(RunOutcome::Exited(status));
+            }
+            if self.turn.executed == 0 {
+                if deadline.is_some_and(|(start, timeout)| start.elapsed() >= timeout) {
+                    return Ok(RunOutcome::TimedOut);
+                }
+                if let Some(interrupt) = &self.stop.interrupt {
+                    if interrupt.swap(false, Ordering::Relaxed) {
+                        return Ok(RunOutcome::Interrupted);
+                    }
+                }
+            }
+            if remaining == 0 {
+                return Ok(RunOutcome::BudgetExhausted);
+            }
+            let pc = self.harts[hartid].read_pc();
+            if !std::mem::take(&mut resuming) && self.stop.breakpoints.contains(&pc) {
+                return Ok(RunOutcome::Reached { hartid, pc });
+            }
+            // the hart runs for the rest of its turn, but an instruction at a time when a store
+            // is to be reported with the pc of the instruction
+            let budget = if self.bus.watchpoints.is_empty() {
+                (self.quantum - self.turn.executed).min(remaining)
+            } else {
+                1
+            };
+            let (executed, result) =
+                self.harts[hartid].run(&mut self.bus, budget, &self.stop.breakpoints);
+            remaining -= executed;
+            self.turn.executed += executed;
+            if let Some(address) = self.bus.hit.take() {
+                return Ok(RunOutcome::Written {
+                    hartid,
+                    pc,
+                    address,
+                });
+            }
+            if let Err(exception) = result {
+                // an exception ends the turn of the hart
+                self.turn.executed = self.quantum;
+                if let Some(exception) = self.service(hartid, exception) {
+                    if self.trap(hartid, exception)? == Step::Breakpoint {
+                        // the hart has run from the pc of its slice up to the ebreak, where it
+                        // stays
+                        let pc = self.harts[hartid].read_pc();
+                        return Ok(RunOutcome::Breakpoint { hartid, pc });
+                    }
+                }
+            }
         }
+    }
+
+    // Executes an instruction of the hart, servicing or trapping on the exception it raises as
+    // `run` does. The time of the CLINT advances with every step.
+    pub fn step(&mut self, hartid: usize) -> std::result::Result<Step, EmulatorError> {
+        if let Some(status) = self.exit_code {
+            return Ok(Step::Exited(status));
+        }
+        let step = match self.harts[hartid].step(&mut self.bus) {
+            Ok(step) => step,
+            Err(exception) => match self.service(hartid, exception) {
+                Some(exception) => self.trap(hartid, exception)?,
+                None => Step::Retired,
+            },
+        };
+        self.poll_htif();
+        self.bus.clint.tick(1);
+        Ok(match self.exit_code {
+            Some(status) => Step::Exited(status),
+            None => step,
+        })
+    }
+
+    pub fn harts(&self) -> usize {
+        self.harts.len()
+    }
+
+    pub fn read_register(&self, hartid: usize, register: usize) -> u64 {
+        self.harts[hartid].read_register(register)
+    }
+
+    // Writes the integer register of the hart. Writes to x0 are ignored.
+    pub fn write_register(&mut self, hartid: usize, register: usize, value: u64) {
+        self.harts[hartid].registers().writeu(register, value);
+    }
+
+    pub fn read_pc(&self, hartid: usize) -> u64 {
+        self.harts[hartid].read_pc()
+    }
+
+    pub fn write_pc(&mut self, hartid: usize, address: u64) {
+        self.harts[hartid].write_pc(address);
+    }
+
+    // Reads the CSR of the hart, or returns None when the hart does not implement it.
+    pub fn read_csr(&mut self, hartid: usize, address: u64) -> Option<u64> {
+        self.harts[hartid].read_csr(address)
+    }
+
+    // the address of the CSR with the name, if the harts implement it
+    pub fn csr_address(&self, name: &str) -> Option<u64> {
+        self.harts[0]
+            .csr_addresses()
+            .into_iter()
+            .find(|&address| csr::name(address).as_deref() == Some(name))
+    }
+
+    // Writes the CSR of the hart, and returns whether the hart implements it.
+    pub fn write_csr(&mut self, hartid: usize, address: u64, value: u64) -> bool {
+        self.harts[hartid].write_csr(address, value)
+    }
+
+    pub fn read_memory(&self, address: u64, size: u64) -> Result<Vec<u8>> {
+        self.check_memory(address, size)?;
+        let mut bytes = vec![0; size as usize];
+        self.bus.load_bytes(address, &mut bytes);
+        Ok(bytes)
+    }
+
+    pub fn write_memory(&mut self, address: u64, bytes: &[u8]) -> Result<()> {
+        self.check_memory(address, bytes.len() as u64)?;
+        self.bus.store_bytes(address, bytes);
         Ok(())
     }
 
-    pub fn run(&mut self) -> u64 {
-        self.cpu.run()
+    // what is mapped in the address space
+    pub fn devices(&self) -> Vec<Device> {
+        let mut devices = vec![
+            Device {
+                name: "memory",
+                base: self.bus.memory.base(),
+                size: self.bus.memory.size(),
+            },
+            Device {
+                name: "clint",
+                base: CLINT_BASE_ADDRESS,
+                size: CLINT_SIZE,
+            },
+        ];
+        if let Some(htif) = &self.htif {
+            devices.push(Device {
+                name: "tohost",
+                base: htif.tohost(),
+                size: 8,
+            });
+            if let Some(fromhost) = htif.fromhost() {
+                devices.push(Device {
+                    name: "fromhost",
+                    base: fromhost,
+                    size: 8,
+                });
+            }
+        }
+        devices
+    }
+
+    fn check_memory(&self, address: u64, size: u64) -> Result<()> {
+        match address.checked_add(size) {
+            Some(end) if address >= self.bus.memory.base() && end <= self.bus.memory.end() => {
+                Ok(())
+            }
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{:#x} is out of memory", address),
+            )),
+        }
+    }
+
+    // Services the environment call or the semihosting call the hart has made on the host, and
+    // returns the exception when there is nothing to service it.
+    fn service(&mut self, hartid: usize, exception: Exception) -> Option<Exception> {
+        let hart = &mut self.harts[hartid];
+        match (exception, &mut self.kernel, &mut self.semihosting) {
+            (Exception::EnvironmentCallFromUMode, Some(kernel), _) => {
+                self.exit_code = kernel.ecall(hart.registers(), &mut self.bus);
+            }
+            (Exception::Breakpoint, _, Some(semihosting))
+                if Semihosting::is_call(&self.bus, hart.read_pc()) =>
+            {
+                self.exit_code = semihosting.call(hart.registers(), &mut self.bus);
+            }
+            _ => return Some(exception),
+        }
+        hart.skip_instruction();
+        None
+    }
+
+    // Takes the exception as a trap. Nothing handles the exception of a user program, whose
+    // kernel is emulated, or one whose trap handler is out of memory, so the hart stops at the
+    // instruction instead.
+    fn trap(
+        &mut self,
+        hartid: usize,
+        exception: Exception,
+    ) -> std::result::Result<Step, EmulatorError> {
+        let hart = &mut self.harts[hartid];
+        if self.kernel.is_none() && self.bus.memory.contains(hart.handler(exception)) {
+            hart.trap(exception, &self.bus);
+            return Ok(Step::Trapped(exception.cause()));
+        }
+        let pc = hart.read_pc();
+        let tval = hart.tval(exception, &self.bus);
+        Err(match exception {
+            Exception::Breakpoint => return Ok(Step::Breakpoint),
+            Exception::IllegalInstruction => EmulatorError::IllegalInstruction {
+                hartid,
+                pc,
+                instruction: tval as u32,
+            },
+            Exception::InstructionAccessFault
+            | Exception::LoadAccessFault
+            | Exception::StoreAccessFault => EmulatorError::UnmappedAccess {
+                hartid,
+                pc,
+                address: tval,
+            },
+            _ => EmulatorError::Unhandled {
+                hartid,
+                pc,
+                cause: exception.cause(),
+            },
+        })
+    }
+
+    fn poll_htif(&mut self) {
+        if let (None, Some(htif)) = (self.exit_code, &mut self.htif) {
+            self.exit_code = match &mut self.history {
+                Some(history) => history.poll(htif, &mut self.bus),
+                None => htif.poll(&mut self.bus),
+            };
+        }
+    }
+
+    // Ends the turn of the hart once it has executed its quantum or raised an exception, and
+    // returns the hart whose turn it is. The harts take their turns in order, as if each ran
+    // on its own for the quantum, and the time of the CLINT advances by the quantum after every
+    // hart has had its turn.
+    fn turn(&mut self) -> usize {
+        if self.turn.executed >= self.quantum {
+            self.poll_htif();
+            self.turn = Turn {
+                hartid: (self.turn.hartid + 1) % self.harts.len(),
+                executed: 0,
+            };
+            if self.turn.hartid == 0 {
+                self.bus.clint.tick(self.quantum);
+            }
+        }
+        self.turn.hartid
+    }
+
+    // Gives the program an address space of its own as large as the memory configured, with
+    // the stack at the top, and returns the top of the stack. The first page is left out, so
+    // that a null pointer faults. The program is single-threaded and its addresses start at
+    // zero, so a machine of more harts or of another memory base cannot run it.
+    fn load_user_program(&mut self, elf: &Elf, args: &[String], envs: &[String]) -> Result<u64> {
+        let size = self.bus.memory.size();
+        if self.harts.len() > 1
+            || self.bus.memory.base() != MEMORY_BASE_ADDRESS
+            || size < STACK_SIZE + PAGE_SIZE
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "a user program runs on a hart with the default memory base, and a memory \
+                 larger than its stack",
+            ));
+        }
+        self.bus.memory = Memory::new(PAGE_SIZE, size - PAGE_SIZE);
+        for segment in &elf.segments {
+            self.store_segment(segment.address, segment.size, &segment.data)?;
+        }
+        let stack_top = size;
+        let sp = linux::initial_stack(&mut self.bus, elf, stack_top, args, envs)?;
+        let hart = &mut self.harts[0];
+        hart.boot(PrivilegeMode::User, elf.entry, 0, 0);
+        hart.registers().writeu(SP, sp);
+        Ok(stack_top)
+    }
+
+    // Stores the data of a segment, the rest of which is left zero-filled.
+    fn store_segment(&mut self, address: u64, size: u64, data: &[u8]) -> Result<()> {
+        if address < self.bus.memory.base() || address + size > self.bus.memory.end() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("the segment at {:#x} is out of memory", address),
+            ));
+        }
+        self.write_memory(address, data)
+    }
+}
+
+fn read(file: File) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    BufReader::new(file).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn text_offset(image: &[u8]) -> u64 {
+    if image.len() < IMAGE_HEADER_SIZE
+        || &image[IMAGE_MAGIC2..IMAGE_MAGIC2 + IMAGE_MAGIC2_VALUE.len()] != IMAGE_MAGIC2_VALUE
+    {
+        return DEFAULT_TEXT_OFFSET;
+    }
+    let mut offset = [0; 8];
+    offset.copy_from_slice(&image[IMAGE_TEXT_OFFSET..IMAGE_TEXT_OFFSET + 8]);
+    u64::from_le_bytes(offset)
+}
+
+fn align_down(value: u64, align: u64) -> u64 {
+    value & !(align - 1)
+}
+
+fn align_up(value: u64, align: u64) -> u64 {
+    align_down(value + align - 1, align)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::emulator::elf::tests::executable;
+    use std::sync::atomic::AtomicUsize;
+
+    fn emulator(config: Config, program: &[u32]) -> Emulator {
+        let mut emulator = Emulator::new(config);
+        for (i, instruction) in program.iter().enumerate() {
+            emulator
+                .bus
+                .store32(MEMORY_BASE_ADDRESS + i as u64 * 4, *instruction);
+        }
+        emulator
+    }
+
+    #[test]
+    fn run_ipi_ok() {
+        // hart 0 raises a software interrupt on hart 1 through the CLINT, then waits for the
+        // interrupt handler of hart 1 to set a flag
+        let program = [
+            0xf1402573, 0x02051463, 0x020002b7, 0x00100313, 0x0062a223, 0x00000397, 0x05438393,
+            0x0003ae03, 0xfe0e0ee3, 0x00100193, 0x00000000, 0x00000297, 0x02028293, 0x30529073,
+            0x00800293, 0x30429073, 0x30046073, 0x00000013, 0xffdff06f, 0x020002b7, 0x0002a223,
+            0x00000397, 0x01438393, 0x00100e13, 0x01c3a023, 0x00000000, 0x00000000,
+        ];
+        let mut emulator = emulator(
+            Config {
+                harts: 2,
+                quantum: 3,
+                ..Config::default()
+            },
+            &program,
+        );
+        // hart 0 sets gp and stops at an illegal instruction, which has no trap handler
+        match emulator.run() {
+            Err(EmulatorError::IllegalInstruction {
+                hartid: 0,
+                pc,
+                instruction: 0,
+            }) => assert_eq!(pc, MEMORY_BASE_ADDRESS + 0x28),
+            result => panic!("{:?}", result),
+        }
+        assert_eq!(emulator.read_register(0, 3), 1);
+        assert!(!emulator.bus.clint.software_interrupt(1));
+    }
+
+    pub fn file(name: &str, bytes: &[u8]) -> File {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, bytes).unwrap();
+        let file = File::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        file
+    }
+
+    // an emulator which has loaded an executable of the program at the base of the memory
+    pub fn loaded(program: &[u32], symbols: &[(&str, u64)]) -> Emulator {
+        static EXECUTABLES: AtomicUsize = AtomicUsize::new(0);
+        let name = format!(
+            "five-{}-{}",
+            std::process::id(),
+            EXECUTABLES.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut emulator = Emulator::default();
+        emulator
+            .load_elf(file(
+                &name,
+                &executable(MEMORY_BASE_ADDRESS, program, symbols),
+            ))
+            .unwrap();
+        emulator
+    }
+
+    // An emulator which has loaded the program of run_elf_ok, which takes the trap of an ecall
+    // from M-mode and reports mcause as the exit status through tohost.
+    pub fn reports_mcause() -> Emulator {
+        let program = [
+            0x00000297, 0x01028293, 0x30529073, 0x00000073, 0x34202373, 0x00131313, 0x00136313,
+            0x00000397, 0x0063ba23, 0x00000013, 0xffdff06f, 0x00000013, 0x00000000, 0x00000000,
+        ];
+        loaded(&program, &[("tohost", 0x800000a8)])
+    }
+
+    #[test]
+    fn load_kernel_ok() {
+        let config = || Config {
+            harts: 2,
+            memory_size: 16 << 20,
+            ..Config::default()
+        };
+        let firmware = [0x13, 0, 0, 0];
+        // an Image whose header asks for the kernel at 4 MiB into memory
+        let mut image = vec![0; IMAGE_HEADER_SIZE];
+        image[IMAGE_TEXT_OFFSET..IMAGE_TEXT_OFFSET + 8]
+            .copy_from_slice(&0x40_0000u64.to_le_bytes());
+        image[IMAGE_MAGIC2..IMAGE_MAGIC2 + 4].copy_from_slice(IMAGE_MAGIC2_VALUE);
+        image.extend_from_slice(b"kernel");
+        let initrd = vec![0xa5; 5000];
+        let mut emulator = Emulator::new(config());
+        emulator
+            .load_kernel(
+                file("five-load-kernel-ok-firmware", &firmware),
+                file("five-load-kernel-ok-image", &image),
+                Some(file("five-load-kernel-ok-initrd", &initrd)),
+                "console=hvc0",
+            )
+            .unwrap();
+        assert_eq!(
+            emulator.read_memory(MEMORY_BASE_ADDRESS, 4).unwrap(),
+            firmware
+        );
+        assert_eq!(
+            emulator
+                .read_memory(MEMORY_BASE_ADDRESS + 0x40_0000, image.len() as u64)
+                .unwrap(),
+            image
+        );
+        // the harts start at the firmware with their hart ID and the device tree
+        let dtb = emulator.read_register(0, 11);
+        for hartid in 0..2 {
+            assert_eq!(emulator.read_pc(hartid), MEMORY_BASE_ADDRESS);
+            assert_eq!(emulator.read_register(hartid, 10), hartid as u64);
+            assert_eq!(emulator.read_register(hartid, 11), dtb);
+        }
+        // the device tree is at the top of memory, and the initrd under it, each on a page
+        let end = MEMORY_BASE_ADDRESS + (16 << 20);
+        let header = emulator.read_memory(dtb, 8).unwrap();
+        let size = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as u64;
+        assert_eq!(dtb % PAGE_SIZE, 0);
+        assert!(dtb + size <= end && end - dtb < size + PAGE_SIZE);
+        let start = dtb - 2 * PAGE_SIZE;
+        assert_eq!(emulator.read_memory(start, 5000).unwrap(), initrd);
+        let blob = emulator.read_memory(dtb, size).unwrap();
+        let initrd_start = start.to_be_bytes();
+        assert!(blob.windows(8).any(|w| w == initrd_start));
+        // an Image without the header is placed at 2 MiB into memory
+        let mut emulator = Emulator::new(config());
+        emulator
+            .load_kernel(
+                file("five-load-kernel-ok-firmware", &firmware),
+                file("five-load-kernel-ok-image", b"kernel"),
+                None,
+                "",
+            )
+            .unwrap();
+        assert_eq!(
+            emulator
+                .read_memory(MEMORY_BASE_ADDRESS + DEFAULT_TEXT_OFFSET, 6)
+                .unwrap(),
+            b"kernel"
+        );
+        // nor does an initrd larger than the memory fit
+        let mut emulator = Emulator::new(config());
+        let error = emulator
+            .load_kernel(
+                file("five-load-kernel-ok-firmware", &firmware),
+                file("five-load-kernel-ok-image", &image),
+                Some(file("five-load-kernel-ok-initrd", &vec![0; 16 << 20])),
+                "",
+            )
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn run_elf_ok() {
+        let mut emulator = reports_mcause();
+        assert_eq!(emulator.symbol("tohost"), Some(0x800000a8));
+        assert_eq!(emulator.run().unwrap(), RunOutcome::Exited(11));
+    }
+
+    #[test]
+    fn trace_ok() {
+        struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+        impl Write for Buffer {
+            fn write(&mut self, buf: &[u8]) -> Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
+
+            fn flush(&mut self) -> Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut emulator = reports_mcause();
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        emulator.trace(Buffer(buffer.clone()));
+        emulator.run().unwrap();
+        let log = String::from_utf8(buffer.borrow().clone()).unwrap();
+        let lines = log.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[..8],
+            [
+                "core   0: 0x0000000080000078 (0x00000297) auipc   t0, 0x0",
+                "core   0: 3 0x0000000080000078 (0x00000297) x5  0x0000000080000078",
+                "core   0: 0x000000008000007c (0x01028293) addi    t0, t0, 16",
+                "core   0: 3 0x000000008000007c (0x01028293) x5  0x0000000080000088",
+                "core   0: 0x0000000080000080 (0x30529073) csrw    mtvec, t0",
+                "core   0: 3 0x0000000080000080 (0x30529073) c773_mtvec 0x0000000080000088",
+                "core   0: exception trap_machine_ecall, epc 0x0000000080000084",
+                "core   0:           tval 0x0000000000000000",
+            ]
+        );
+        assert!(lines.contains(
+            &"core   0: 3 0x0000000080000098 (0x0063ba23) mem 0x00000000800000a8 0x0000000000000017"
+        ));
+    }
+
+    #[test]
+    fn step_ok() {
+        // addi a0, a0, 1; ecall
+        let program = [0x00150513, 0x00000073];
+        let mut emulator = emulator(Config::default(), &program);
+        emulator.write_pc(0, MEMORY_BASE_ADDRESS);
+        emulator.write_register(0, 10, 41);
+        assert_eq!(emulator.step(0).unwrap(), Step::Retired);
+        assert_eq!(emulator.read_register(0, 10), 42);
+        assert!(emulator.write_csr(0, 0x305, MEMORY_BASE_ADDRESS + 0x100));
+        assert!(!emulator.write_csr(0, 0xfff, 0));
+        assert_eq!(emulator.step(0).unwrap(), Step::Trapped(11));
+        assert_eq!(emulator.read_pc(0), MEMORY_BASE_ADDRESS + 0x100);
+        assert_eq!(emulator.read_csr(0, 0x341), Some(MEMORY_BASE_ADDRESS + 4));
+        // a nop at the trap handler
+        let nop = [0x13, 0x00, 0x00, 0x00];
+        emulator
+            .write_memory(MEMORY_BASE_ADDRESS + 0x100, &nop)
+            .unwrap();
+        assert_eq!(
+            emulator
+                .read_memory(MEMORY_BASE_ADDRESS + 0x100, 4)
+                .unwrap(),
+            nop
+        );
+        assert!(emulator.read_memory(0, 4).is_err());
+        assert_eq!(emulator.step(0).unwrap(), Step::Retired);
+        // the zero which follows is illegal, and takes the trap to the handler
+        assert_eq!(emulator.step(0).unwrap(), Step::Trapped(2));
+        assert_eq!(emulator.read_csr(0, 0x343), Some(0));
+    }
+
+    #[test]
+    fn counters_ok() {
+        // addi a0, a0, 1; mul a0, a0, a0; rdcycle a1; rdinstret a2; rdtime a3; auipc t0, 0;
+        // addi t0, t0, 16; csrw mepc, t0; mret; rdcycle a4; rdtime a5
+        let program = [
+            0x00150513, 0x02a50533, 0xc00025f3, 0xc0202673, 0xc01026f3, 0x00000297, 0x01028293,
+            0x34129073, 0x30200073, 0xc0002773, 0xc01027f3,
+        ];
+        let costs = Costs {
+            multiply: 3,
+            system: 2,
+            ..Costs::default()
+        };
+        let mut emulator = emulator(
+            Config {
+                costs,
+                ..Config::default()
+            },
+            &program,
+        );
+        emulator.write_pc(0, MEMORY_BASE_ADDRESS);
+        assert!(emulator.write_csr(0, 0x305, MEMORY_BASE_ADDRESS + 0x100));
+        // U-mode may read cycle, but not time
+        assert!(emulator.write_csr(0, 0x306, 0b101));
+        assert!(emulator.write_csr(0, 0x106, 0b011));
+        for _ in 0..10 {
+            assert_eq!(emulator.step(0).unwrap(), Step::Retired);
+        }
+        assert_eq!(emulator.read_register(0, 11), 4);
+        assert_eq!(emulator.read_register(0, 12), 3);
+        // the time of the CLINT, which advances as the hart steps
+        assert_eq!(emulator.read_register(0, 13), 4);
+        assert_eq!(emulator.read_register(0, 14), 16);
+        assert_eq!(emulator.step(0).unwrap(), Step::Trapped(2));
+        assert_eq!(emulator.read_csr(0, 0xb00), Some(18));
+        assert_eq!(emulator.read_csr(0, 0xb02), Some(10));
+    }
+
+    #[test]
+    fn hpm_ok() {
+        // addi a0, zero, 3; addi a0, a0, -1; bnez a0, -4; auipc t0, 0; lw a1, 0(t0)
+        let program = [0x00300513, 0xfff50513, 0xfe051ee3, 0x00000297, 0x0002a583];
+        let mut emulator = emulator(Config::default(), &program);
+        emulator.write_pc(0, MEMORY_BASE_ADDRESS);
+        assert!(emulator.write_csr(0, 0x305, MEMORY_BASE_ADDRESS + 0x100));
+        // enable the local counter overflow interrupt in M-mode
+        assert!(emulator.write_csr(0, 0x304, 1 << 13));
+        assert!(emulator.write_csr(0, 0x300, 1 << 3));
+        let events = [
+            Event::BranchTaken as u64,
+            Event::BranchNotTaken as u64,
+            Event::Load as u64,
+            // inhibited in M-mode
+            Event::Integer as u64 | 1 << 62,
+            Event::Integer as u64,
+            Event::Trap as u64,
+        ];
+        for (i, event) in events.iter().enumerate() {
+            assert!(emulator.write_csr(0, 0x323 + i as u64, *event));
+        }
+        // mhpmcounter7 overflows as the last instruction retires
+        assert!(emulator.write_csr(0, 0xb07, u64::MAX - 8));
+        for _ in 0..9 {
+            assert_eq!(emulator.step(0).unwrap(), Step::Retired);
+        }
+        assert_eq!(emulator.read_csr(0, 0x344), Some(1 << 13));
+        assert_eq!(emulator.step(0).unwrap(), Step::Trapped(1 << 63 | 13));
+        let counters = (0xb03..0xb09)
+            .map(|address| emulator.read_csr(0, address).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(counters, [2, 1, 1, 0, 0, 1]);
+        assert_eq!(
+            emulator.read_csr(0, 0x327),
+            Some(1 << 63 | Event::Integer as u64)
+        );
+        // scountovf
+        assert_eq!(emulator.read_csr(0, 0xda0), Some(1 << 7));
+    }
+
+    #[test]
+    fn self_modifying_ok() {
+        // auipc t0, 0; lw t1, 24(t0); addi a0, a0, 1; sw t1, 8(t0); fence.i; j -12;
+        // addi a0, a0, 10
+        let program = [
+            0x00000297, 0x0182a303, 0x00150513, 0x0062a423, 0x0000100f, 0xff5ff06f, 0x00a50513,
+        ];
+        let mut emulator = emulator(Config::default(), &program);
+        emulator.write_pc(0, MEMORY_BASE_ADDRESS);
+        emulator.stop_when(StopConditions {
+            instructions: Some(7),
+            ..StopConditions::default()
+        });
+        assert_eq!(emulator.run().unwrap(), RunOutcome::BudgetExhausted);
+        // the block of the addi has been decoded again after the store over it
+        assert_eq!(emulator.read_register(0, 10), 11);
+        // so it is after a write of the debugger
+        let addi = 0x06450513u32.to_le_bytes();
+        emulator
+            .write_memory(MEMORY_BASE_ADDRESS + 8, &addi)
+            .unwrap();
+        emulator.write_pc(0, MEMORY_BASE_ADDRESS + 8);
+        emulator.step(0).unwrap();
+        assert_eq!(emulator.read_register(0, 10), 111);
+    }
+
+    #[test]
+    fn cross_modifying_ok() {
+        // hart 0 calls a function, then waits for hart 1 to store another instruction over it
+        // and set a flag, executes fence.i and calls the function again
+        let program = [
+            0xf14022f3, 0x02029463, 0x00000317, 0x018000ef, 0x04032383, 0xfe038ee3, 0x0000100f,
+            0x008000ef, 0x00000000, 0x00150513, 0x00008067, 0x00000e17, 0x020e2e83, 0xffde2c23,
+            0x0110000f, 0x00100f13, 0x01ee2e23, 0x0000006f, 0x00000000, 0x06450513,
+        ];
+        let mut emulator = emulator(
+            Config {
+                harts: 2,
+                quantum: 3,
+                ..Config::default()
+            },
+            &program,
+        );
+        match emulator.run() {
+            Err(EmulatorError::IllegalInstruction { hartid: 0, pc, .. }) => {
+                assert_eq!(pc, MEMORY_BASE_ADDRESS + 0x20)
+            }
+            result => panic!("{:?}", result),
+        }
+        // the function has been decoded before the store, and again after it
+        assert_eq!(emulator.read_register(0, 10), 101);
+    }
+
+    #[test]
+    fn run_error_ok() {
+        // csrr a0, 0x800; lw a0, 0(zero); ebreak; nop; j -4; nop; nop; j -20
+        let program = [
+            0x80002573, 0x00002503, 0x00100073, 0x00000013, 0xffdff06f, 0x00000013, 0x00000013,
+            0xfedff06f,
+        ];
+        let mut emulator = emulator(Config::default(), &program);
+        emulator.write_pc(0, MEMORY_BASE_ADDRESS);
+        match emulator.run() {
+            Err(EmulatorError::IllegalInstruction {
+                hartid: 0,
+                pc,
+                instruction: 0x80002573,
+            }) => assert_eq!(pc, MEMORY_BASE_ADDRESS),
+            result => panic!("{:?}", result),
+        }
+        // the hart stays at the instruction
+        assert_eq!(emulator.read_pc(0), MEMORY_BASE_ADDRESS);
+        emulator.write_pc(0, MEMORY_BASE_ADDRESS + 4);
+        match emulator.run() {
+            Err(EmulatorError::UnmappedAccess {
+                hartid: 0,
+                address: 0,
+                ..
+            }) => {}
+            result => panic!("{:?}", result),
+        }
+        emulator.write_pc(0, MEMORY_BASE_ADDRESS + 8);
+        assert_eq!(
+            emulator.run().unwrap(),
+            RunOutcome::Breakpoint {
+                hartid: 0,
+                pc: MEMORY_BASE_ADDRESS + 8
+            }
+        );
+        // the ebreak is reached after other instructions in the slice
+        emulator.write_pc(0, MEMORY_BASE_ADDRESS + 20);
+        assert_eq!(
+            emulator.run().unwrap(),
+            RunOutcome::Breakpoint {
+                hartid: 0,
+                pc: MEMORY_BASE_ADDRESS + 8
+            }
+        );
+        assert_eq!(emulator.read_pc(0), MEMORY_BASE_ADDRESS + 8);
+        emulator.write_pc(0, MEMORY_BASE_ADDRESS + 12);
+        emulator.stop_when(StopConditions {
+            instructions: Some(10),
+            ..StopConditions::default()
+        });
+        assert_eq!(emulator.run().unwrap(), RunOutcome::BudgetExhausted);
+        // the handler of the trap is in memory
+        assert!(emulator.write_csr(0, 0x305, MEMORY_BASE_ADDRESS + 12));
+        emulator.write_pc(0, MEMORY_BASE_ADDRESS + 4);
+        assert_eq!(emulator.step(0).unwrap(), Step::Trapped(5));
+        assert_eq!(emulator.read_csr(0, 0x343), Some(0));
+    }
+
+    #[test]
+    fn misaligned_ok() {
+        // auipc t0, 0; sw t0, 0x209(t0); lw a0, 0x209(t0); jr 2(t0)
+        let program = [0x00000297, 0x2052a4a3, 0x2092a503, 0x00228067];
+        let mut emulator = emulator(Config::default(), &program);
+        emulator.write_pc(0, MEMORY_BASE_ADDRESS);
+        assert!(emulator.write_csr(0, 0x305, MEMORY_BASE_ADDRESS + 0x100));
+        for _ in 0..3 {
+            assert_eq!(emulator.step(0).unwrap(), Step::Retired);
+        }
+        assert_eq!(emulator.read_register(0, 10), MEMORY_BASE_ADDRESS);
+        // the jump raises the exception, as there are no compressed instructions
+        assert_eq!(emulator.step(0).unwrap(), Step::Trapped(0));
+        assert_eq!(emulator.read_csr(0, 0x341), Some(MEMORY_BASE_ADDRESS + 12));
+        assert_eq!(emulator.read_csr(0, 0x343), Some(MEMORY_BASE_ADDRESS + 2));
+        for (misaligned, cause) in [(Misaligned::Trap, 6), (Misaligned::Fault, 7)].iter() {
+            let config = Config {
+                misaligned: *misaligned,
+                ..Config::default()
+            };
+            let mut emulator = self::emulator(config, &program);
+            emulator.write_pc(0, MEMORY_BASE_ADDRESS);
+            assert!(emulator.write_csr(0, 0x305, MEMORY_BASE_ADDRESS + 0x100));
+            assert_eq!(emulator.step(0).unwrap(), Step::Retired);
+            assert_eq!(emulator.step(0).unwrap(), Step::Trapped(*cause));
+            assert_eq!(
+                emulator.read_csr(0, 0x343),
+                Some(MEMORY_BASE_ADDRESS + 0x209)
+            );
+        }
+    }
+
+    #[test]
+    fn run_stop_ok() {
+        // auipc t0, 0; addi a0, a0, 1; sw a0, 64(t0); j -8
+        let program = [0x00000297, 0x00150513, 0x04a2a023, 0xff9ff06f];
+        let mut emulator = emulator(Config::default(), &program);
+        emulator.write_pc(0, MEMORY_BASE_ADDRESS);
+        emulator.stop_when(StopConditions {
+            breakpoints: vec![MEMORY_BASE_ADDRESS + 8],
+            ..StopConditions::default()
+        });
+        let reached = RunOutcome::Reached {
+            hartid: 0,
+            pc: MEMORY_BASE_ADDRESS + 8,
+        };
+        assert_eq!(emulator.run().unwrap(), reached);
+        assert_eq!(emulator.read_register(0, 10), 1);
+        // the hart resumes past the breakpoint
+        assert_eq!(emulator.run().unwrap(), reached);
+        assert_eq!(emulator.read_register(0, 10), 2);
+
+        emulator.stop_when(StopConditions {
+            watchpoints: vec![MEMORY_BASE_ADDRESS + 66],
+            ..StopConditions::default()
+        });
+        assert_eq!(
+            emulator.run().unwrap(),
+            RunOutcome::Written {
+                hartid: 0,
+                pc: MEMORY_BASE_ADDRESS + 8,
+                address: MEMORY_BASE_ADDRESS + 66,
+            }
+        );
+        assert_eq!(
+            emulator.read_memory(MEMORY_BASE_ADDRESS + 64, 4).unwrap(),
+            [2, 0, 0, 0]
+        );
+
+        emulator.stop_when(StopConditions {
+            timeout: Some(Duration::from_millis(10)),
+            ..StopConditions::default()
+        });
+        assert_eq!(emulator.run().unwrap(), RunOutcome::TimedOut);
+
+        let interrupt = Arc::new(AtomicBool::new(true));
+        emulator.stop_when(StopConditions {
+            interrupt: Some(interrupt.clone()),
+            ..StopConditions::default()
+        });
+        assert_eq!(emulator.run().unwrap(), RunOutcome::Interrupted);
+        assert!(!interrupt.load(Ordering::Relaxed));
+        assert_eq!(emulator.csr_address("mstatus"), Some(0x300));
+        assert_eq!(emulator.devices()[0].base, MEMORY_BASE_ADDRESS);
+    }
+
+    #[test]
+    fn run_semihosting_ok() {
+        // exits with the status 7 through SYS_EXIT_EXTENDED
+        let program = [
+            0x00000597, 0x02058593, 0x02000513, 0x01f01013, 0x00100073, 0x40705013, 0x00000013,
+            0x00000013, 0x00020026, 0x00000000, 0x00000007, 0x00000000,
+        ];
+        let mut emulator = emulator(Config::default(), &program);
+        emulator.enable_semihosting("");
+        assert_eq!(emulator.run().unwrap(), RunOutcome::Exited(7));
+    }
+
+    #[test]
+    fn run_pk_executable_ok() {
+        // writes to a descriptor which has not been opened and exits with the errno
+        let program = [
+            0x06300513, 0x00000597, 0x02058593, 0x00300613, 0x04000893, 0x00000073, 0x40a00533,
+            0x05d00893, 0x00000073, 0x000a6b70,
+        ];
+        let elf = executable(0x10000, &program, &[]);
+        let mut emulator = Emulator::default();
+        emulator
+            .load_pk_executable(file("five-run-pk-executable-ok", &elf), &["pk".to_string()])
+            .unwrap();
+        assert_eq!(emulator.run().unwrap(), RunOutcome::Exited(9));
+    }
+
+    #[test]
+    fn run_linux_executable_ok() {
+        // exits with argc + 40 after moving the program break by 16 bytes
+        let program = [
+            0x00013483, 0x00000513, 0x0d600893, 0x00000073, 0x01050513, 0x00050413, 0x00000073,
+            0x408502b3, 0x02800513, 0x00950533, 0x00550533, 0x05d00893, 0x00000073,
+        ];
+        let elf = executable(0x10000, &program, &[]);
+        let mut emulator = Emulator::default();
+        emulator
+            .load_linux_executable(
+                file("five-run-linux-executable-ok", &elf),
+                &["program".to_string(), "argument".to_string()],
+                &[],
+            )
+            .unwrap();
+        assert_eq!(emulator.run().unwrap(), RunOutcome::Exited(42));
+    }
+
+    #[test]
+    fn load_user_program_ok() {
+        // ld a0, 0(zero)
+        let elf = executable(0x10000, &[0x00003503], &[]);
+        let args = ["program".to_string()];
+        let mut emulator = Emulator::new(Config {
+            memory_size: 64 << 20,
+            ..Config::default()
+        });
+        emulator
+            .load_linux_executable(file("five-load-user-program-ok", &elf), &args, &[])
+            .unwrap();
+        // the stack is at the top of the memory configured
+        let sp = emulator.read_register(0, 2);
+        assert!(sp < 64 << 20 && sp > (64 << 20) - PAGE_SIZE);
+        // and a null pointer faults
+        match emulator.run() {
+            Err(EmulatorError::UnmappedAccess {
+                hartid: 0,
+                pc: 0x10078,
+                address: 0,
+            }) => {}
+            result => panic!("{:?}", result),
+        }
+
+        // nor does a user program run on another hart
+        let mut emulator = Emulator::new(Config {
+            harts: 2,
+            ..Config::default()
+        });
+        let error = emulator
+            .load_pk_executable(file("five-load-user-program-ok-harts", &elf), &args)
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
     }
 }
@@ -1,5 +1,10 @@
-use crate::emulator::memory::Memory;
+use crate::emulator::{
+    clint::{Clint, CLINT_BASE_ADDRESS, CLINT_SIZE},
+    cpu::trace::{Access, Log},
+    memory::Memory,
+};
 
+#[derive(Clone, Copy)]
 pub enum Size {
     Byte = 1,
     Halfword = 2,
@@ -7,13 +12,53 @@ pub enum Size {
     Doubleword = 8,
 }
 
-#[derive(Default)]
+// What a load or a store does at an address which is not a multiple of its size.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Misaligned {
+    // the access is carried out as if it was aligned
+    #[default]
+    Emulate,
+    // the access raises an address-misaligned exception, for the firmware to emulate it
+    Trap,
+    // the access raises an access fault
+    Fault,
+}
+
 pub struct SystemBus {
+    pub clint: Clint,
     pub memory: Memory,
+    pub misaligned: Misaligned,
+    pub log: Log<Access>,
+    // the addresses a store to which is reported
+    pub watchpoints: Vec<u64>,
+    // the address of the watchpoint a store has hit since it was reported
+    pub hit: Option<u64>,
 }
 
 impl SystemBus {
+    pub fn new(harts: usize, memory: Memory) -> Self {
+        Self {
+            clint: Clint::new(harts),
+            memory,
+            misaligned: Misaligned::default(),
+            log: Log::default(),
+            watchpoints: Vec::new(),
+            hit: None,
+        }
+    }
+
+    // whether an access of the size at the address reaches memory or a device
+    pub fn mapped(&self, address: u64, size: Size) -> bool {
+        let end = address.wrapping_add(size as u64 - 1);
+        (is_clint(address) && is_clint(end))
+            || (self.memory.contains(address) && self.memory.contains(end))
+    }
+
     pub fn load(&self, address: u64, size: Size) -> u64 {
+        self.log.record(Access::Load { address });
+        if is_clint(address) {
+            return self.clint.load(address - CLINT_BASE_ADDRESS, size);
+        }
         self.memory.load(address, size)
     }
 
@@ -34,9 +79,58 @@ impl SystemBus {
     }
 
     pub fn store(&mut self, address: u64, value: u64, size: Size) {
+        self.log.record(Access::Store {
+            address,
+            value,
+            size: size as u64,
+        });
+        if !self.watchpoints.is_empty() {
+            let end = address.wrapping_add(size as u64);
+            if let Some(watchpoint) = self
+                .watchpoints
+                .iter()
+                .find(|&&watchpoint| watchpoint >= address && watchpoint < end)
+            {
+                self.hit = Some(*watchpoint);
+            }
+        }
+        if is_clint(address) {
+            return self.clint.store(address - CLINT_BASE_ADDRESS, value, size);
+        }
         self.memory.store(address, value, size);
     }
 
+    // Reads the bytes from the address, which are to be in memory, in one go rather than a load
+    // at a time, for a loader or a device.
+    pub fn load_bytes(&self, address: u64, bytes: &mut [u8]) {
+        self.memory.load_bytes(address, bytes);
+    }
+
+    // Writes the bytes to the address, which are to be in memory, in one go rather than a store
+    // at a time, for a loader or a device. The bytes are logged as stores of up to eight bytes.
+    pub fn store_bytes(&mut self, address: u64, bytes: &[u8]) {
+        if self.log.recording() {
+            for (i, chunk) in bytes.chunks(8).enumerate() {
+                let mut value = [0; 8];
+                value[..chunk.len()].copy_from_slice(chunk);
+                self.log.record(Access::Store {
+                    address: address + i as u64 * 8,
+                    value: u64::from_le_bytes(value),
+                    size: chunk.len() as u64,
+                });
+            }
+        }
+        let end = address.wrapping_add(bytes.len() as u64);
+        if let Some(watchpoint) = self
+            .watchpoints
+            .iter()
+            .find(|&&watchpoint| watchpoint >= address && watchpoint < end)
+        {
+            self.hit = Some(*watchpoint);
+        }
+        self.memory.store_bytes(address, bytes);
+    }
+
     pub fn store8(&mut self, address: u64, value: u8) {
         self.store(address, value as u64, Size::Byte)
     }
@@ -53,3 +147,7 @@ impl SystemBus {
         self.store(address, value, Size::Doubleword)
     }
 }
+
+fn is_clint(address: u64) -> bool {
+    (CLINT_BASE_ADDRESS..CLINT_BASE_ADDRESS + CLINT_SIZE).contains(&address)
+}
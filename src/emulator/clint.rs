@@ -0,0 +1,112 @@
+use crate::emulator::{
+    bus::Size,
+    snapshot::{Reader, Snapshot, Writer},
+};
+use std::io::{Error, ErrorKind, Result};
+
+pub const CLINT_BASE_ADDRESS: u64 = 0x0200_0000;
+pub const CLINT_SIZE: u64 = 0x1_0000;
+
+// offsets of the registers from the base address
+const MSIP: u64 = 0x0000; // 4 bytes per hart
+const MTIMECMP: u64 = 0x4000; // 8 bytes per hart
+const MTIME: u64 = 0xbff8;
+
+// Core Local Interruptor providing the software interrupts used as IPIs and the timer.
+pub struct Clint {
+    msip: Vec<u32>,
+    mtimecmp: Vec<u64>,
+    mtime: u64,
+}
+
+impl Clint {
+    pub fn new(harts: usize) -> Self {
+        Self {
+            msip: vec![0; harts],
+            mtimecmp: vec![u64::MAX; harts],
+            mtime: 0,
+        }
+    }
+
+    pub fn load(&self, offset: u64, size: Size) -> u64 {
+        let (register, shift) = match offset {
+            MSIP..=0x3fff if ((offset - MSIP) / 4) < self.msip.len() as u64 => (
+                self.msip[((offset - MSIP) / 4) as usize] as u64,
+                (offset - MSIP) % 4,
+            ),
+            MTIMECMP..=0xbff7 if ((offset - MTIMECMP) / 8) < self.mtimecmp.len() as u64 => (
+                self.mtimecmp[((offset - MTIMECMP) / 8) as usize],
+                (offset - MTIMECMP) % 8,
+            ),
+            MTIME..=0xbfff => (self.mtime, offset - MTIME),
+            _ => (0, 0),
+        };
+        (register >> (shift * 8)) & mask(size)
+    }
+
+    pub fn store(&mut self, offset: u64, value: u64, size: Size) {
+        let mask = mask(size);
+        let merge = |register: u64, shift: u64| {
+            register & !(mask << (shift * 8)) | (value & mask) << (shift * 8)
+        };
+        match offset {
+            MSIP..=0x3fff if ((offset - MSIP) / 4) < self.msip.len() as u64 => {
+                let hart = ((offset - MSIP) / 4) as usize;
+                // only the lowest bit of msip is writable
+                self.msip[hart] = merge(self.msip[hart] as u64, (offset - MSIP) % 4) as u32 & 1;
+            }
+            MTIMECMP..=0xbff7 if ((offset - MTIMECMP) / 8) < self.mtimecmp.len() as u64 => {
+                let hart = ((offset - MTIMECMP) / 8) as usize;
+                self.mtimecmp[hart] = merge(self.mtimecmp[hart], (offset - MTIMECMP) % 8);
+            }
+            MTIME..=0xbfff => self.mtime = merge(self.mtime, offset - MTIME),
+            _ => {}
+        }
+    }
+
+    pub fn mtime(&self) -> u64 {
+        self.mtime
+    }
+
+    pub fn tick(&mut self, ticks: u64) {
+        self.mtime = self.mtime.wrapping_add(ticks);
+    }
+
+    pub fn software_interrupt(&self, hartid: usize) -> bool {
+        self.msip[hartid] & 1 != 0
+    }
+
+    pub fn timer_interrupt(&self, hartid: usize) -> bool {
+        self.mtime >= self.mtimecmp[hartid]
+    }
+}
+
+fn mask(size: Size) -> u64 {
+    match size {
+        Size::Doubleword => u64::MAX,
+        s => (1 << (s as u64 * 8)) - 1,
+    }
+}
+
+impl Snapshot for Clint {
+    fn save(&mut self, snapshot: &mut Writer) {
+        for (msip, mtimecmp) in self.msip.iter().zip(&self.mtimecmp) {
+            snapshot.u32(*msip);
+            snapshot.u64(*mtimecmp);
+        }
+        snapshot.u64(self.mtime);
+    }
+
+    // restores the registers of as many harts as the CLINT has been made for
+    fn restore(&mut self, snapshot: &mut Reader) -> Result<()> {
+        for (msip, mtimecmp) in self.msip.iter_mut().zip(self.mtimecmp.iter_mut()) {
+            *msip = snapshot.u32()?;
+            *mtimecmp = snapshot.u64()?;
+        }
+        self.mtime = snapshot.u64()?;
+        if self.msip.iter().any(|&msip| msip > 1) {
+            return Err(Error::new(ErrorKind::InvalidData, "msip is not a bit"));
+        }
+        Ok(())
+    }
+}
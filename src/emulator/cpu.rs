@@ -1,109 +1,414 @@
-mod csr;
+mod block;
+pub mod cost;
+pub mod csr;
 mod decoder;
+pub mod disassembler;
 mod executor;
+pub mod mode;
 mod pc;
-mod x;
-
-use crate::emulator::{
-    bus::SystemBus,
-    cpu::{
-        csr::ControlAndStatusRegister,
-        decoder::{
-            privileged::PrivilegedDecoder, rv32i::Rv32iDecoder, rv32m::Rv32mDecoder,
-            rv64i::Rv64iDecoder, rv64m::Rv64mDecoder, zicsr::ZicsrDecoder,
-            zifencei::ZifenceiDecoder, Decoder,
-        },
-        executor::{
-            privileged::PrivilegedExecutor, rv32i::Rv32iExecutor, rv32m::Rv32mExecutor,
-            rv64i::Rv64iExecutor, rv64m::Rv64mExecutor, zicsr::ZicsrExecutor,
-            zifencei::ZifenceiExecutor, Executor,
+pub mod trace;
+pub mod trap;
+pub mod x;
+
+use crate::{
+    emulator::{
+        bus::SystemBus,
+        cpu::{
+            block::BlockCache,
+            cost::Costs,
+            csr::{
+                counters::Event,
+                machine_level::{MCOUNTEREN, MIP_MSIP, MIP_MTIP},
+                supervisor_level::SCOUNTEREN,
+                ControlAndStatusRegister,
+            },
+            decoder::{decode, Decoded},
+            executor::{
+                privileged::PrivilegedExecutor, rv32i::Rv32iExecutor, rv32m::Rv32mExecutor,
+                rv64i::Rv64iExecutor, rv64m::Rv64mExecutor, zicsr::ZicsrExecutor,
+                zifencei::ZifenceiExecutor, Executor,
+            },
+            mode::PrivilegeMode,
+            pc::ProgramCounter,
+            trace::{Commit, Tracer},
+            trap::{Exception, Interrupt},
+            x::{IntegerRegister, A0, A1},
         },
-        pc::ProgramCounter,
-        x::{IntegerRegister, GP},
+        snapshot::{Reader, Snapshot, Writer},
     },
+    isa::instruction::Instruction,
 };
+use std::cell::RefCell;
+use std::io::{self, Error, ErrorKind};
+use std::rc::Rc;
+
+// what happens to a hart when it is stepped
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    // the hart has retired an instruction
+    Retired,
+    // the hart has taken a trap with the cause
+    Trapped(u64),
+    // the hart has stopped at a breakpoint which has no trap handler
+    Breakpoint,
+    // the program has exited with the status
+    Exited(u64),
+}
 
 #[derive(Default)]
 pub struct Cpu {
+    hartid: usize,
     x: IntegerRegister,
     pc: ProgramCounter,
     csr: ControlAndStatusRegister,
-    pub bus: SystemBus,
+    mode: PrivilegeMode,
+    // the cycles the instructions take, which mcycle counts
+    costs: Costs,
+    blocks: BlockCache,
+    tracer: Option<Rc<RefCell<dyn Tracer>>>,
 }
 
 impl Cpu {
-    pub fn run(&mut self) -> u64 {
-        while self.pc.read() < self.bus.memory.size() {
+    pub fn new(hartid: usize) -> Self {
+        Self {
+            hartid,
+            csr: ControlAndStatusRegister::new(hartid as u64),
+            ..Self::default()
+        }
+    }
+
+    pub fn set_costs(&mut self, costs: Costs) {
+        self.costs = costs;
+    }
+
+    // Starts the hart in the mode at the address. Below M-mode, the counters are enabled to the
+    // mode as the firmware or the kernel the emulator stands in for would enable them.
+    pub fn boot(&mut self, mode: PrivilegeMode, address: u64, a0: u64, a1: u64) {
+        if mode < PrivilegeMode::Machine {
+            self.csr.write(MCOUNTEREN, 0xffff_ffff);
+        }
+        if mode < PrivilegeMode::Supervisor {
+            self.csr.write(SCOUNTEREN, 0xffff_ffff);
+        }
+        self.mode = mode;
+        self.pc.jump(address);
+        self.x.writeu(A0, a0);
+        self.x.writeu(A1, a1);
+    }
+
+    pub fn read_pc(&self) -> u64 {
+        self.pc.read()
+    }
+
+    pub fn write_pc(&mut self, address: u64) {
+        self.pc.jump(address);
+    }
+
+    pub fn read_csr(&mut self, address: u64) -> Option<u64> {
+        if self.csr.contains(address) {
+            Some(self.csr.read(address))
+        } else {
+            None
+        }
+    }
+
+    pub fn write_csr(&mut self, address: u64, value: u64) -> bool {
+        if self.csr.contains(address) {
+            self.csr.write(address, value);
+        }
+        self.csr.contains(address)
+    }
+
+    pub fn csr_addresses(&self) -> Vec<u64> {
+        self.csr.addresses()
+    }
+
+    // Reports each instruction the hart retires and each trap it takes to the tracer, and
+    // returns the tracer it replaces.
+    pub fn trace(
+        &mut self,
+        tracer: Option<Rc<RefCell<dyn Tracer>>>,
+    ) -> Option<Rc<RefCell<dyn Tracer>>> {
+        std::mem::replace(&mut self.tracer, tracer)
+    }
+
+    pub fn read_register(&self, register: usize) -> u64 {
+        self.x.readu(register)
+    }
+
+    pub fn registers(&mut self) -> &mut IntegerRegister {
+        &mut self.x
+    }
+
+    // Executes an instruction or takes a pending interrupt. An exception the instruction raises
+    // is not taken as a trap but returned with the pc left at the instruction, so that the caller
+    // can service an environment call or a breakpoint, report it to a debugger or let the hart
+    // take the trap.
+    pub fn step(&mut self, bus: &mut SystemBus) -> Result<Step, Exception> {
+        self.run(bus, 1, &[]).1
+    }
+
+    // Executes instructions a basic block at a time, until the hart has executed the budget,
+    // reaches one of the breakpoints after the first instruction, takes an interrupt, raises an
+    // exception or hits a watchpoint, and returns how many instructions it has executed or tried
+    // to, the interrupt counting as one, and what the last one has done as `step` does.
+    // Interrupts are taken when the hart starts to run and after the instructions which may
+    // have enabled them, so that they wait for the next run of the hart otherwise.
+    pub fn run(
+        &mut self,
+        bus: &mut SystemBus,
+        budget: u64,
+        breakpoints: &[u64],
+    ) -> (u64, Result<Step, Exception>) {
+        let mut executed = 0;
+        let mut previous = None;
+        let mut interruptible = true;
+        while executed < budget {
+            if std::mem::take(&mut interruptible) {
+                if let Some(cause) = self.take_interrupt(bus) {
+                    return (executed + 1, Ok(Step::Trapped(cause)));
+                }
+            }
             // read an address from the pc
             let address = self.pc.read();
-            // fetch an instruction
-            let instruction = self.bus.load32(address);
-            // decode and execute the instruction
-            if let Some(decoded) = PrivilegedDecoder::decode(instruction) {
-                PrivilegedExecutor::execute(
-                    decoded,
-                    &mut self.pc,
-                    &mut self.x,
-                    &mut self.csr,
-                    &mut self.bus,
-                )
-            } else if let Some(decoded) = ZifenceiDecoder::decode(instruction) {
-                ZifenceiExecutor::execute(
-                    decoded,
-                    &mut self.pc,
-                    &mut self.x,
-                    &mut self.csr,
-                    &mut self.bus,
-                )
-            } else if let Some(decoded) = ZicsrDecoder::decode(instruction) {
-                ZicsrExecutor::execute(
-                    decoded,
-                    &mut self.pc,
-                    &mut self.x,
-                    &mut self.csr,
-                    &mut self.bus,
-                )
-            } else if let Some(decoded) = Rv32iDecoder::decode(instruction) {
-                Rv32iExecutor::execute(
-                    decoded,
-                    &mut self.pc,
-                    &mut self.x,
-                    &mut self.csr,
-                    &mut self.bus,
-                )
-            } else if let Some(decoded) = Rv64iDecoder::decode(instruction) {
-                Rv64iExecutor::execute(
-                    decoded,
-                    &mut self.pc,
-                    &mut self.x,
-                    &mut self.csr,
-                    &mut self.bus,
-                )
-            } else if let Some(decoded) = Rv32mDecoder::decode(instruction) {
-                Rv32mExecutor::execute(
-                    decoded,
-                    &mut self.pc,
-                    &mut self.x,
-                    &mut self.csr,
-                    &mut self.bus,
-                )
-            } else if let Some(decoded) = Rv64mDecoder::decode(instruction) {
-                Rv64mExecutor::execute(
-                    decoded,
-                    &mut self.pc,
-                    &mut self.x,
-                    &mut self.csr,
-                    &mut self.bus,
-                )
-            } else {
-                // end the loop when unable to decode the instruction
-                break;
+            if !bus.memory.contains(address) {
+                return (executed + 1, Err(Exception::InstructionAccessFault));
+            }
+            // fetch the block of instructions from the address, decoding it if it has not
+            // been
+            let block = match self.blocks.lookup(bus, previous, address) {
+                Some(block) => block,
+                None => return (executed + 1, Err(Exception::IllegalInstruction)),
             };
-            // increment the pc when the pc has not been updated
-            if self.pc.read() == address {
-                self.pc.increment();
+            previous = Some(block);
+            let mut position = 0;
+            while let Some((instruction, decoded)) = self.blocks.instruction(block, position) {
+                let address = self.pc.read();
+                if executed == budget || (executed > 0 && breakpoints.contains(&address)) {
+                    return (executed, Ok(Step::Retired));
+                }
+                if let Err(exception) = self.execute_traced(instruction, decoded, bus) {
+                    return (executed + 1, Err(exception));
+                }
+                executed += 1;
+                position += 1;
+                interruptible = decoded.writes_csrs();
+                if decoded.fences_code() {
+                    self.blocks.flush();
+                    previous = None;
+                    break;
+                }
+                // stop at a watchpoint, and leave a block which a store has made stale
+                if bus.hit.is_some() {
+                    return (executed, Ok(Step::Retired));
+                }
+                if self.blocks.stale(bus) {
+                    break;
+                }
+            }
+        }
+        (executed, Ok(Step::Retired))
+    }
+
+    // Executes the instruction at the pc, and increments the pc when the instruction has not
+    // jumped.
+    fn execute_traced(
+        &mut self,
+        instruction: u32,
+        decoded: Decoded,
+        bus: &mut SystemBus,
+    ) -> Result<(), Exception> {
+        let address = self.pc.read();
+        let mode = self.mode;
+        self.pc.take_jumped();
+        self.csr.counters.begin();
+        if self.tracer.is_some() {
+            self.x.log.start();
+            self.csr.log.start();
+            bus.log.start();
+        }
+        let result = self.execute(decoded, bus);
+        let commit = self.tracer.is_some().then(|| Commit {
+            hartid: self.hartid,
+            mode,
+            pc: address,
+            instruction,
+            registers: self.x.log.stop(),
+            csrs: self.csr.log.stop(),
+            accesses: bus.log.stop(),
+        });
+        result?;
+        let jumped = self.pc.take_jumped();
+        if !jumped {
+            self.pc.increment();
+        }
+        self.csr.counters.retire(self.costs.of(decoded.kind()));
+        if self.csr.counters.monitoring() {
+            self.csr.count(decoded.events(jumped), mode);
+        }
+        if let (Some(tracer), Some(commit)) = (&self.tracer, commit) {
+            tracer.borrow_mut().commit(&commit);
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, decoded: Decoded, bus: &mut SystemBus) -> Result<(), Exception> {
+        let (pc, x, csr, mode) = (&mut self.pc, &mut self.x, &mut self.csr, &mut self.mode);
+        match decoded {
+            Decoded::Privileged(instruction) => {
+                PrivilegedExecutor::execute(instruction, pc, x, csr, mode, bus)
+            }
+            Decoded::Zifencei(instruction) => {
+                ZifenceiExecutor::execute(instruction, pc, x, csr, mode, bus)
+            }
+            Decoded::Zicsr(instruction) => {
+                ZicsrExecutor::execute(instruction, pc, x, csr, mode, bus)
+            }
+            Decoded::Rv32i(instruction) => {
+                Rv32iExecutor::execute(instruction, pc, x, csr, mode, bus)
+            }
+            Decoded::Rv64i(instruction) => {
+                Rv64iExecutor::execute(instruction, pc, x, csr, mode, bus)
+            }
+            Decoded::Rv32m(instruction) => {
+                Rv32mExecutor::execute(instruction, pc, x, csr, mode, bus)
+            }
+            Decoded::Rv64m(instruction) => {
+                Rv64mExecutor::execute(instruction, pc, x, csr, mode, bus)
+            }
+        }
+    }
+
+    // Completes the environment call or the breakpoint the caller has serviced.
+    pub fn skip_instruction(&mut self) {
+        self.pc.increment();
+        self.csr.counters.retire(self.costs.system);
+        self.csr.count(Event::Integer.bit(), self.mode);
+    }
+
+    // Takes the exception caused by the instruction at the pc.
+    pub fn trap(&mut self, exception: Exception, bus: &SystemBus) {
+        let tval = self.tval(exception, bus);
+        self.trace_trap(exception.cause(), tval);
+        self.csr.count(Event::Trap.bit(), self.mode);
+        trap::take(
+            exception.cause(),
+            tval,
+            &mut self.pc,
+            &mut self.csr,
+            &mut self.mode,
+        );
+    }
+
+    // the address of the trap handler the hart would enter for the exception
+    pub fn handler(&mut self, exception: Exception) -> u64 {
+        trap::handler(exception.cause(), &mut self.csr, self.mode)
+    }
+
+    // The value the exception caused by the instruction at the pc writes to the trap value
+    // register: the address which has faulted, the target a jump is misaligned to, the
+    // instruction which is illegal or the pc of the breakpoint.
+    pub fn tval(&self, exception: Exception, bus: &SystemBus) -> u64 {
+        let pc = self.pc.read();
+        match exception {
+            Exception::InstructionAddressMisaligned => match decode(bus.load32(pc)) {
+                Some(Decoded::Rv32i(Instruction::TypeI { rs1, imm, .. })) => {
+                    self.x.readu(rs1).wrapping_add(imm) & !1
+                }
+                Some(Decoded::Rv32i(Instruction::TypeB { imm, .. }))
+                | Some(Decoded::Rv32i(Instruction::TypeJ { imm, .. })) => pc.wrapping_add(imm),
+                _ => 0,
+            },
+            Exception::InstructionAccessFault | Exception::Breakpoint => pc,
+            Exception::IllegalInstruction => bus.load32(pc) as u64,
+            Exception::LoadAddressMisaligned | Exception::LoadAccessFault => {
+                let instruction = bus.load32(pc);
+                self.x
+                    .readu(((instruction >> 15) & 0x1f) as usize)
+                    .wrapping_add((instruction as i32 >> 20) as u64)
+            }
+            Exception::StoreAddressMisaligned | Exception::StoreAccessFault => {
+                let instruction = bus.load32(pc);
+                let imm = ((instruction as i32 >> 25) << 5) | ((instruction >> 7) & 0x1f) as i32;
+                self.x
+                    .readu(((instruction >> 15) & 0x1f) as usize)
+                    .wrapping_add(imm as u64)
+            }
+            _ => 0,
+        }
+    }
+
+    fn trace_trap(&self, cause: u64, tval: u64) {
+        if let Some(tracer) = &self.tracer {
+            tracer
+                .borrow_mut()
+                .trap(self.hartid, cause, self.pc.read(), tval);
+        }
+    }
+
+    // Takes the interrupt which is pending and enabled, if there is one, and returns its cause.
+    fn take_interrupt(&mut self, bus: &SystemBus) -> Option<u64> {
+        self.update_pending_interrupts(bus);
+        let interrupt = Interrupt::pending(self.mode, &mut self.csr)?;
+        self.trace_trap(interrupt.cause(), 0);
+        self.csr.count(Event::Trap.bit(), self.mode);
+        trap::take(
+            interrupt.cause(),
+            0,
+            &mut self.pc,
+            &mut self.csr,
+            &mut self.mode,
+        );
+        Some(interrupt.cause())
+    }
+
+    // reflects the interrupt lines of the CLINT in mip
+    fn update_pending_interrupts(&mut self, bus: &SystemBus) {
+        let mut pending = 0;
+        if bus.clint.software_interrupt(self.hartid) {
+            pending |= MIP_MSIP;
+        }
+        if bus.clint.timer_interrupt(self.hartid) {
+            pending |= MIP_MTIP;
+        }
+        self.csr.drive(MIP_MSIP | MIP_MTIP, pending);
+    }
+}
+
+impl Snapshot for Cpu {
+    fn save(&mut self, snapshot: &mut Writer) {
+        snapshot.u8(self.mode as u8);
+        snapshot.u64(self.pc.read());
+        self.costs.save(snapshot);
+        for register in 0..32 {
+            snapshot.u64(self.x.readu(register));
+        }
+        let addresses = self.csr.addresses();
+        snapshot.u64(addresses.len() as u64);
+        for address in addresses {
+            snapshot.u64(address);
+            snapshot.u64(self.csr.read(address));
+        }
+    }
+
+    fn restore(&mut self, snapshot: &mut Reader) -> io::Result<()> {
+        self.blocks.flush();
+        self.mode = PrivilegeMode::from(snapshot.u8()? as u64);
+        self.pc.jump(snapshot.u64()?);
+        self.costs.restore(snapshot)?;
+        for register in 0..32 {
+            self.x.writeu(register, snapshot.u64()?);
+        }
+        for _ in 0..snapshot.u64()? {
+            let address = snapshot.u64()?;
+            let value = snapshot.u64()?;
+            if !self.write_csr(address, value) {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("the CSR {:#x} is unknown", address),
+                ));
             }
         }
-        self.x.readu(GP)
+        Ok(())
     }
 }
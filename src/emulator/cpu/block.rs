@@ -0,0 +1,175 @@
+use crate::emulator::{
+    bus::{Size, SystemBus},
+    cpu::decoder::{decode, Decoded},
+    memory::PAGE_SIZE,
+};
+use std::collections::HashMap;
+
+const MAX_INSTRUCTIONS: usize = 64;
+// the blocks a hart keeps before it decodes its code again from scratch
+const MAX_BLOCKS: usize = 0x4000;
+
+// A basic block: the instructions from an address to the first one which ends the block, within
+// a page of the memory.
+struct Block {
+    // the instructions and their encodings
+    instructions: Vec<(u32, Decoded)>,
+    // the address and the index of the block which has last followed this one, by which the hart
+    // goes from block to block without looking them up
+    next: Option<(u64, usize)>,
+}
+
+// The basic blocks a hart has decoded, indexed by the physical address of their first
+// instruction. The instructions of a block are not compiled: the hart still matches on each
+// one every time it executes it, and saves only their fetch and decode. The blocks of a page are
+// thrown away once a store has hit it, and all of them when the hart executes FENCE.I or
+// SFENCE.VMA.
+#[derive(Default)]
+pub struct BlockCache {
+    blocks: Vec<Block>,
+    index: HashMap<u64, usize>,
+    // the code writes of the memory when the blocks were decoded
+    code_writes: Option<u64>,
+}
+
+impl BlockCache {
+    // Returns the block at the address, which the hart goes to from the previous block, and
+    // decodes it if it has not been. None when there is no instruction to execute at the
+    // address.
+    pub fn lookup(
+        &mut self,
+        bus: &mut SystemBus,
+        previous: Option<usize>,
+        address: u64,
+    ) -> Option<usize> {
+        let mut previous = previous;
+        if self.stale(bus) {
+            self.invalidate(bus);
+            previous = None;
+        }
+        if let Some(previous) = previous {
+            if let Some((next, index)) = self.blocks[previous].next {
+                // the block followed may have been thrown away since
+                if next == address && !self.blocks[index].instructions.is_empty() {
+                    return Some(index);
+                }
+            }
+        }
+        let index = match self.index.get(&address) {
+            Some(&index) => index,
+            None => {
+                let block = decode_block(bus, address)?;
+                if self.blocks.len() >= MAX_BLOCKS {
+                    self.flush();
+                    self.code_writes = Some(bus.memory.code_writes());
+                    previous = None;
+                }
+                self.blocks.push(block);
+                self.index.insert(address, self.blocks.len() - 1);
+                self.blocks.len() - 1
+            }
+        };
+        if let Some(previous) = previous {
+            self.blocks[previous].next = Some((address, index));
+        }
+        Some(index)
+    }
+
+    // the instruction of the block at the position, if the block has one there
+    pub fn instruction(&self, block: usize, position: usize) -> Option<(u32, Decoded)> {
+        self.blocks[block].instructions.get(position).copied()
+    }
+
+    // whether a store has hit a page the blocks have been decoded from
+    pub fn stale(&self, bus: &SystemBus) -> bool {
+        self.code_writes != Some(bus.memory.code_writes())
+    }
+
+    // Throws away the blocks of the pages the stores have hit since the blocks were checked, or
+    // all of them when the memory has not kept as many pages.
+    fn invalidate(&mut self, bus: &SystemBus) {
+        let pages = self
+            .code_writes
+            .and_then(|code_writes| bus.memory.code_pages(code_writes));
+        match pages {
+            Some(pages) => {
+                let base = bus.memory.base();
+                let blocks = &mut self.blocks;
+                self.index.retain(|address, index| {
+                    let hit = pages.contains(&((address - base) / PAGE_SIZE));
+                    if hit {
+                        blocks[*index].instructions.clear();
+                    }
+                    !hit
+                });
+            }
+            None => self.flush(),
+        }
+        self.code_writes = Some(bus.memory.code_writes());
+    }
+
+    pub fn flush(&mut self) {
+        self.blocks.clear();
+        self.index.clear();
+        self.code_writes = None;
+    }
+}
+
+// Decodes the instructions from the address up to the one which ends the block, the first one
+// which cannot be decoded or the end of the page.
+fn decode_block(bus: &mut SystemBus, address: u64) -> Option<Block> {
+    let base = bus.memory.base();
+    let end = base + ((address - base) / PAGE_SIZE + 1) * PAGE_SIZE;
+    let mut instructions = Vec::new();
+    let mut pc = address;
+    while pc + 4 <= end && bus.memory.contains(pc + 3) && instructions.len() < MAX_INSTRUCTIONS {
+        let instruction = bus.memory.load(pc, Size::Word) as u32;
+        let decoded = match decode(instruction) {
+            Some(decoded) => decoded,
+            None => break,
+        };
+        instructions.push((instruction, decoded));
+        if decoded.ends_block() {
+            break;
+        }
+        pc += 4;
+    }
+    if instructions.is_empty() {
+        return None;
+    }
+    bus.memory.decoded(address);
+    Some(Block {
+        instructions,
+        next: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::memory::{Memory, MEMORY_BASE_ADDRESS};
+
+    #[test]
+    fn invalidate_ok() {
+        let mut bus = SystemBus::new(1, Memory::new(MEMORY_BASE_ADDRESS, 0x10_0000));
+        // nop; j 0 on two pages
+        let (first, second) = (MEMORY_BASE_ADDRESS, MEMORY_BASE_ADDRESS + PAGE_SIZE);
+        for address in [first, second].iter() {
+            bus.store_bytes(*address, &[0x13, 0, 0, 0, 0x6f, 0, 0, 0]);
+        }
+        let mut blocks = BlockCache::default();
+        let a = blocks.lookup(&mut bus, None, first).unwrap();
+        let b = blocks.lookup(&mut bus, Some(a), second).unwrap();
+        assert_eq!(blocks.lookup(&mut bus, Some(a), second), Some(b));
+
+        // a store to the second page throws away only its block
+        bus.store32(second, 0x00100513);
+        assert!(blocks.stale(&bus));
+        assert_eq!(blocks.lookup(&mut bus, None, first), Some(a));
+        let c = blocks.lookup(&mut bus, Some(a), second).unwrap();
+        assert_ne!(c, b);
+        assert_eq!(blocks.instruction(c, 0).unwrap().0, 0x00100513);
+        assert!(blocks.instruction(b, 0).is_none());
+        assert!(!blocks.stale(&bus));
+    }
+}
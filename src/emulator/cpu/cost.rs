@@ -0,0 +1,95 @@
+use crate::emulator::snapshot::{Reader, Snapshot, Writer};
+use std::io::Result;
+
+// what an instruction does, by which it takes its cycles
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    // an instruction which computes on the integer registers alone
+    Alu,
+    Load,
+    Store,
+    Multiply,
+    // a division or a remainder
+    Divide,
+    // a jump or a branch
+    Jump,
+    // an instruction which accesses the CSRs, fences or changes the mode
+    System,
+}
+
+// The cycles an instruction takes by its kind, which mcycle counts. Every instruction takes a
+// cycle by default, so that mcycle counts as minstret does, as on Spike.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Costs {
+    pub alu: u64,
+    pub load: u64,
+    pub store: u64,
+    pub multiply: u64,
+    pub divide: u64,
+    pub jump: u64,
+    pub system: u64,
+}
+
+impl Default for Costs {
+    fn default() -> Self {
+        Self {
+            alu: 1,
+            load: 1,
+            store: 1,
+            multiply: 1,
+            divide: 1,
+            jump: 1,
+            system: 1,
+        }
+    }
+}
+
+impl Costs {
+    // the cycles an instruction of the kind takes
+    pub fn of(&self, kind: Kind) -> u64 {
+        match kind {
+            Kind::Alu => self.alu,
+            Kind::Load => self.load,
+            Kind::Store => self.store,
+            Kind::Multiply => self.multiply,
+            Kind::Divide => self.divide,
+            Kind::Jump => self.jump,
+            Kind::System => self.system,
+        }
+    }
+}
+
+impl Snapshot for Costs {
+    fn save(&mut self, snapshot: &mut Writer) {
+        for cost in [
+            self.alu,
+            self.load,
+            self.store,
+            self.multiply,
+            self.divide,
+            self.jump,
+            self.system,
+        ]
+        .iter()
+        {
+            snapshot.u64(*cost);
+        }
+    }
+
+    fn restore(&mut self, snapshot: &mut Reader) -> Result<()> {
+        for cost in [
+            &mut self.alu,
+            &mut self.load,
+            &mut self.store,
+            &mut self.multiply,
+            &mut self.divide,
+            &mut self.jump,
+            &mut self.system,
+        ]
+        .iter_mut()
+        {
+            **cost = snapshot.u64()?;
+        }
+        Ok(())
+    }
+}
@@ -1,62 +1,351 @@
-mod machine_level;
-mod supervisor_level;
-mod user_level;
+pub mod counters;
+pub mod machine_level;
+pub mod supervisor_level;
+pub mod user_level;
 
-use crate::emulator::cpu::csr::{
-    machine_level::MachineLevelCsr, supervisor_level::SupervisorLevelCsr, user_level::UserLevelCsr,
+use crate::emulator::cpu::{
+    csr::{
+        counters::Counters,
+        machine_level::{MachineLevelCsr, MCOUNTEREN, MIDELEG, MIE, MIP, MIP_LCOFIP, MSTATUS},
+        supervisor_level::{
+            SupervisorLevelCsr, SCOUNTEREN, SIE, SIP, SIP_WRITABLE, SSTATUS, SSTATUS_MASK,
+        },
+        user_level::{UserLevelCsr, CYCLE, CYCLEH, HPMCOUNTER31, HPMCOUNTER31H},
+    },
+    mode::PrivilegeMode,
+    trace::Log,
 };
 
+// The CSRs of a hart. sstatus is a view of the fields of mstatus S-mode may access, and sie and
+// sip of the bits of mie and mip of the interrupts delegated to S-mode.
 #[derive(Default)]
 pub struct ControlAndStatusRegister {
     ucsr: UserLevelCsr,
     scsr: SupervisorLevelCsr,
     mcsr: MachineLevelCsr,
+    pub counters: Counters,
+    pub log: Log<(u64, u64)>,
 }
 
 impl ControlAndStatusRegister {
-    pub fn csrrw(&mut self, address: u64, value: u64) -> u64 {
-        if self.ucsr.contains(address) {
-            return self.ucsr.csrrw(address, value);
+    pub fn new(hartid: u64) -> Self {
+        Self {
+            mcsr: MachineLevelCsr::new(hartid),
+            ..Self::default()
+        }
+    }
+
+    pub fn read(&mut self, address: u64) -> u64 {
+        self.csrrs(address, 0)
+    }
+
+    pub fn write(&mut self, address: u64, value: u64) {
+        self.csrrw(address, value);
+    }
+
+    pub fn contains(&self, address: u64) -> bool {
+        matches!(address, SSTATUS | SIE | SIP)
+            || self.ucsr.contains(address)
+            || self.scsr.contains(address)
+            || self.mcsr.contains(address)
+            || self.counters.contains(address)
+    }
+
+    // Whether the hart in the mode may access the CSR at the address: the CSR is not to be of a
+    // level above the mode, which its bits 9:8 encode, a read-only CSR is not to be written, and
+    // the counters are enabled below M-mode by mcounteren, and below S-mode by scounteren as well.
+    pub fn permits(&mut self, address: u64, mode: PrivilegeMode, write: bool) -> bool {
+        if (address >> 8) & 0b11 > mode as u64 {
+            return false;
+        }
+        if write && address >> 10 == 0b11 {
+            return false;
         }
-        if self.scsr.contains(address) {
-            return self.scsr.csrrw(address, value);
+        let counter = (CYCLE..=HPMCOUNTER31).contains(&address)
+            || (CYCLEH..=HPMCOUNTER31H).contains(&address);
+        if !counter {
+            return true;
         }
-        if self.mcsr.contains(address) {
-            return self.mcsr.csrrw(address, value);
+        let bit = 1 << (address & 0x1f);
+        match mode {
+            PrivilegeMode::Machine => true,
+            PrivilegeMode::Supervisor => self.read(MCOUNTEREN) & bit != 0,
+            PrivilegeMode::User => self.read(MCOUNTEREN) & self.read(SCOUNTEREN) & bit != 0,
         }
-        panic!("todo: address not found.");
+    }
+
+    // Counts the events which have occurred in the mode on the performance-monitoring counters,
+    // and makes the local counter overflow interrupt pending when one of them overflows.
+    pub fn count(&mut self, events: u64, mode: PrivilegeMode) {
+        if self.counters.count(events, mode) {
+            self.mcsr.drive(MIP_LCOFIP, MIP_LCOFIP);
+        }
+    }
+
+    // sets the bits of mip in the mask to the interrupts the devices raise
+    pub fn drive(&mut self, mask: u64, pending: u64) {
+        self.mcsr.drive(mask, pending);
+    }
+
+    // the addresses of the CSRs in ascending order
+    pub fn addresses(&self) -> Vec<u64> {
+        let mut addresses = vec![SSTATUS, SIE, SIP];
+        addresses.extend(self.ucsr.addresses());
+        addresses.extend(self.scsr.addresses());
+        addresses.extend(self.mcsr.addresses());
+        addresses.extend(self.counters.addresses());
+        addresses.sort_unstable();
+        addresses
+    }
+
+    pub fn csrrw(&mut self, address: u64, value: u64) -> u64 {
+        let old = self.load(address);
+        self.store(address, value);
+        self.record(address);
+        old
     }
 
     pub fn csrrs(&mut self, address: u64, value: u64) -> u64 {
-        if self.ucsr.contains(address) {
-            return self.ucsr.csrrs(address, value);
+        let old = self.load(address);
+        if value != 0 {
+            self.store(address, old | value);
+            self.record(address);
         }
-        if self.scsr.contains(address) {
-            return self.scsr.csrrs(address, value);
+        old
+    }
+
+    pub fn csrrc(&mut self, address: u64, value: u64) -> u64 {
+        let old = self.load(address);
+        if value != 0 {
+            self.store(address, old & !value);
+            self.record(address);
         }
-        if self.mcsr.contains(address) {
-            return self.mcsr.csrrs(address, value);
+        old
+    }
+
+    // reads the CSR at the address, or the bits of the machine-level CSR it is a view of
+    fn load(&mut self, address: u64) -> u64 {
+        match address {
+            SSTATUS => self.mcsr.read(MSTATUS) & SSTATUS_MASK,
+            SIE => self.mcsr.read(MIE) & self.mcsr.read(MIDELEG),
+            SIP => self.mcsr.read(MIP) & self.mcsr.read(MIDELEG),
+            _ => self.level(address).read(address),
         }
-        panic!("todo: address not found.");
     }
 
-    pub fn csrrc(&mut self, address: u64, value: u64) -> u64 {
-        if self.ucsr.contains(address) {
-            return self.ucsr.csrrc(address, value);
+    // writes the CSR at the address, or the bits of the machine-level CSR it is a view of
+    fn store(&mut self, address: u64, value: u64) {
+        let (address, mask) = match address {
+            SSTATUS => (MSTATUS, SSTATUS_MASK),
+            SIE => (MIE, self.mcsr.read(MIDELEG)),
+            SIP => (MIP, self.mcsr.read(MIDELEG) & SIP_WRITABLE),
+            _ => return self.level(address).write(address, value),
+        };
+        let old = self.mcsr.read(address);
+        self.mcsr.write(address, old & !mask | value & mask);
+    }
+
+    // The CSRs the address is in, by the privilege level its bits 9:8 encode, but the counters,
+    // which are in all of them.
+    fn level(&mut self, address: u64) -> &mut dyn Csr {
+        if self.counters.contains(address) {
+            return &mut self.counters;
         }
-        if self.scsr.contains(address) {
-            return self.scsr.csrrc(address, value);
+        match (address >> 8) & 0b11 {
+            0b00 => &mut self.ucsr,
+            0b01 => &mut self.scsr,
+            _ => &mut self.mcsr,
         }
-        if self.mcsr.contains(address) {
-            return self.mcsr.csrrc(address, value);
+    }
+
+    // records the value the CSR has been written while the hart is traced
+    fn record(&mut self, address: u64) {
+        if self.log.recording() {
+            let value = self.load(address);
+            self.log.record((address, value));
         }
-        panic!("todo: address not found.");
     }
 }
 
+// the name of the CSR at the address, of which bits 4:0 number the performance-monitoring
+// counters and events
+pub fn name(address: u64) -> Option<String> {
+    let name = match address {
+        user_level::USTATUS => "ustatus",
+        user_level::UIE => "uie",
+        user_level::UTVEC => "utvec",
+        user_level::USCRATCH => "uscratch",
+        user_level::UEPC => "uepc",
+        user_level::UCAUSE => "ucause",
+        user_level::UTVAL => "utval",
+        user_level::UIP => "uip",
+        user_level::FFLAGS => "fflags",
+        user_level::FRM => "frm",
+        user_level::FCSR => "fcsr",
+        user_level::CYCLE => "cycle",
+        user_level::TIME => "time",
+        user_level::INSTRET => "instret",
+        user_level::HPMCOUNTER3..=user_level::HPMCOUNTER31 => {
+            return Some(format!("hpmcounter{}", address & 0x1f))
+        }
+        user_level::CYCLEH => "cycleh",
+        user_level::TIMEH => "timeh",
+        user_level::INSTRETH => "instreth",
+        user_level::HPMCOUNTER3H..=user_level::HPMCOUNTER31H => {
+            return Some(format!("hpmcounter{}h", address & 0x1f))
+        }
+        supervisor_level::SSTATUS => "sstatus",
+        supervisor_level::SEDELEG => "sedeleg",
+        supervisor_level::SIDELEG => "sideleg",
+        supervisor_level::SIE => "sie",
+        supervisor_level::STVEC => "stvec",
+        supervisor_level::SCOUNTEREN => "scounteren",
+        supervisor_level::SSCRATCH => "sscratch",
+        supervisor_level::SEPC => "sepc",
+        supervisor_level::SCAUSE => "scause",
+        supervisor_level::STVAL => "stval",
+        supervisor_level::SIP => "sip",
+        supervisor_level::SATP => "satp",
+        supervisor_level::SCOUNTOVF => "scountovf",
+        machine_level::MVENDORID => "mvendorid",
+        machine_level::MARCHID => "marchid",
+        machine_level::MIMPID => "mimpid",
+        machine_level::MHARTID => "mhartid",
+        machine_level::MSTATUS => "mstatus",
+        machine_level::MISA => "misa",
+        machine_level::MEDELEG => "medeleg",
+        machine_level::MIDELEG => "mideleg",
+        machine_level::MIE => "mie",
+        machine_level::MTVEC => "mtvec",
+        machine_level::MCOUNTEREN => "mcounteren",
+        machine_level::MSCRATCH => "mscratch",
+        machine_level::MEPC => "mepc",
+        machine_level::MCAUSE => "mcause",
+        machine_level::MTVAL => "mtval",
+        machine_level::MIP => "mip",
+        machine_level::PMPCFG0 => "pmpcfg0",
+        machine_level::PMPCFG1 => "pmpcfg1",
+        machine_level::PMPCFG2 => "pmpcfg2",
+        machine_level::PMPCFG3 => "pmpcfg3",
+        machine_level::PMPADDR0 => "pmpaddr0",
+        machine_level::PMPADDR1 => "pmpaddr1",
+        machine_level::PMPADDR2 => "pmpaddr2",
+        machine_level::PMPADDR3 => "pmpaddr3",
+        machine_level::PMPADDR4 => "pmpaddr4",
+        machine_level::PMPADDR5 => "pmpaddr5",
+        machine_level::PMPADDR6 => "pmpaddr6",
+        machine_level::PMPADDR7 => "pmpaddr7",
+        machine_level::PMPADDR8 => "pmpaddr8",
+        machine_level::PMPADDR9 => "pmpaddr9",
+        machine_level::PMPADDR10 => "pmpaddr10",
+        machine_level::PMPADDR11 => "pmpaddr11",
+        machine_level::PMPADDR12 => "pmpaddr12",
+        machine_level::PMPADDR13 => "pmpaddr13",
+        machine_level::PMPADDR14 => "pmpaddr14",
+        machine_level::PMPADDR15 => "pmpaddr15",
+        machine_level::MCYCLE => "mcycle",
+        machine_level::MINSTRET => "minstret",
+        machine_level::MHPMCOUNTER3..=machine_level::MHPMCOUNTER31 => {
+            return Some(format!("mhpmcounter{}", address & 0x1f))
+        }
+        machine_level::MCYCLEH => "mcycleh",
+        machine_level::MINSTRETH => "minstreth",
+        machine_level::MHPMCOUNTER3H..=machine_level::MHPMCOUNTER31H => {
+            return Some(format!("mhpmcounter{}h", address & 0x1f))
+        }
+        machine_level::MCOUNTINHIBIT => "mcountinhibit",
+        machine_level::MHPMEVENT3..=machine_level::MHPMEVENT31 => {
+            return Some(format!("mhpmevent{}", address & 0x1f))
+        }
+        machine_level::MHPMEVENT3H..=machine_level::MHPMEVENT31H => {
+            return Some(format!("mhpmevent{}h", address & 0x1f))
+        }
+        machine_level::TSELECT => "tselect",
+        machine_level::TDATA1 => "tdata1",
+        machine_level::TDATA2 => "tdata2",
+        machine_level::TDATA3 => "tdata3",
+        machine_level::DCSR => "dcsr",
+        machine_level::DPC => "dpc",
+        machine_level::DSCRATCH0 => "dscratch0",
+        machine_level::DSCRATCH1 => "dscratch1",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+// the CSRs of a level, whose fields which are WARL keep their legal values alone as they are
+// written
 pub trait Csr {
     fn contains(&self, address: u64) -> bool;
-    fn csrrw(&mut self, address: u64, value: u64) -> u64;
-    fn csrrs(&mut self, address: u64, value: u64) -> u64;
-    fn csrrc(&mut self, address: u64, value: u64) -> u64;
+    fn addresses(&self) -> Vec<u64>;
+    fn read(&self, address: u64) -> u64;
+    fn write(&mut self, address: u64, value: u64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::cpu::csr::{
+        machine_level::{
+            MHPMCOUNTER3H, MHPMEVENT31H, MHPMEVENT3H, MISA, MSTATUS_MPP, MSTATUS_SIE, MSTATUS_SUM,
+            MSTATUS_UXL, MTVEC,
+        },
+        supervisor_level::SATP,
+        user_level::{FCSR, FFLAGS, FRM},
+    };
+
+    #[test]
+    fn views_ok() {
+        let mut csr = ControlAndStatusRegister::new(1);
+        // sstatus shows the fields of mstatus S-mode may access
+        csr.write(MSTATUS, MSTATUS_MPP | MSTATUS_SIE);
+        assert_eq!(
+            csr.read(SSTATUS),
+            MSTATUS_SIE | 2 << MSTATUS_UXL.trailing_zeros()
+        );
+        csr.write(SSTATUS, MSTATUS_SUM | MSTATUS_MPP);
+        assert_eq!(
+            csr.read(MSTATUS) & (MSTATUS_SUM | MSTATUS_MPP | MSTATUS_SIE),
+            MSTATUS_SUM | MSTATUS_MPP
+        );
+        // the bits of sie and sip of the interrupts which are not delegated are zero
+        csr.write(SIE, 0x2222);
+        assert_eq!(csr.read(MIE), 0);
+        csr.write(MIDELEG, 0x2222);
+        csr.write(SIE, 0x2aaa);
+        assert_eq!(csr.read(MIE), 0x2222);
+        // the interrupts of the devices are not written
+        csr.write(MIP, 0x2aaa);
+        assert_eq!(csr.read(SIP), 0x2222);
+        csr.write(SIP, 0);
+        assert_eq!(csr.read(MIP), 0x0220);
+        // fflags and frm are the fields of fcsr
+        csr.write(FCSR, 0x1ff);
+        assert_eq!((csr.read(FFLAGS), csr.read(FRM)), (0x1f, 0b111));
+        csr.write(FRM, 0b010);
+        assert_eq!(csr.read(FCSR), 0x5f);
+        // the WARL fields keep their legal values
+        csr.write(MSTATUS, 0b10 << MSTATUS_MPP.trailing_zeros());
+        assert_eq!(csr.read(MSTATUS) & MSTATUS_MPP, 0);
+        csr.write(MTVEC, 0x8000_0003);
+        assert_eq!(csr.read(MTVEC), 0x8000_0001);
+        let misa = csr.read(MISA);
+        csr.write(MISA, 0);
+        assert_eq!(csr.read(MISA), misa);
+        // satp is left alone by a write selecting Sv39, as only Bare is implemented
+        csr.write(SATP, 0x1234);
+        csr.write(SATP, 8 << 60 | 0x8_0000);
+        assert_eq!(csr.read(SATP), 0x1234);
+        csr.write(SATP, 0);
+        assert_eq!(csr.read(SATP), 0);
+    }
+
+    #[test]
+    fn name_ok() {
+        assert_eq!(name(CYCLEH).as_deref(), Some("cycleh"));
+        assert_eq!(name(HPMCOUNTER31).as_deref(), Some("hpmcounter31"));
+        assert_eq!(name(MHPMCOUNTER3H + 1).as_deref(), Some("mhpmcounter4h"));
+        assert_eq!(name(MHPMEVENT3H + 27).as_deref(), Some("mhpmevent30h"));
+        assert_eq!(name(MHPMEVENT31H + 1), None);
+    }
 }
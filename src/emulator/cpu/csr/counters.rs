@@ -0,0 +1,330 @@
+use crate::emulator::cpu::{
+    csr::{
+        machine_level::{
+            MCOUNTINHIBIT, MCYCLE, MCYCLEH, MHPMCOUNTER3, MHPMCOUNTER31, MHPMCOUNTER31H,
+            MHPMCOUNTER3H, MHPMEVENT3, MHPMEVENT31, MHPMEVENT31H, MHPMEVENT3H, MINSTRET, MINSTRETH,
+        },
+        supervisor_level::SCOUNTOVF,
+        user_level::{
+            CYCLE, CYCLEH, HPMCOUNTER3, HPMCOUNTER31, HPMCOUNTER31H, HPMCOUNTER3H, INSTRET,
+            INSTRETH, TIME, TIMEH,
+        },
+        Csr,
+    },
+    mode::PrivilegeMode,
+};
+
+// Machine Counter-Inhibit Register fields
+pub const MCOUNTINHIBIT_CY: u64 = 1 << 0; // Inhibits mcycle.
+pub const MCOUNTINHIBIT_IR: u64 = 1 << 2; // Inhibits minstret.
+
+// the bits of mcountinhibit which may be set, as time cannot be inhibited
+const MCOUNTINHIBIT_MASK: u64 = 0xffff_fffd;
+
+// Machine Performance-Monitoring Event Selector fields
+pub const MHPMEVENT_OF: u64 = 1 << 63; // Overflow.
+pub const MHPMEVENT_MINH: u64 = 1 << 62; // Inhibits counting in M-mode.
+pub const MHPMEVENT_SINH: u64 = 1 << 61; // Inhibits counting in S-mode.
+pub const MHPMEVENT_UINH: u64 = 1 << 60; // Inhibits counting in U-mode.
+pub const MHPMEVENT_EVENT: u64 = 0xff_ffff_ffff_ffff; // Event selector.
+
+// the bits of mhpmevent which may be set, as there is no hypervisor whose modes to inhibit
+const MHPMEVENT_MASK: u64 =
+    MHPMEVENT_OF | MHPMEVENT_MINH | MHPMEVENT_SINH | MHPMEVENT_UINH | MHPMEVENT_EVENT;
+
+// the number of the performance-monitoring counters, mhpmcounter3 to mhpmcounter31
+const HPM_COUNTERS: usize = 29;
+
+// The events the performance-monitoring counters count, by the code mhpmevent selects them by.
+// The events an instruction causes are a mask of the bits at their codes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    Load = 1,
+    Store = 2,
+    BranchTaken = 3,
+    BranchNotTaken = 4,
+    // never counted, as five translates no addresses and has no TLB to miss
+    TlbMiss = 5,
+    // an exception or an interrupt the hart takes
+    Trap = 6,
+    // the instructions retired of RV32I and RV64I
+    Integer = 7,
+    // the instructions retired of RV32M and RV64M
+    Multiply = 8,
+    Zicsr = 9,
+    Zifencei = 10,
+    // the instructions retired of the privileged architecture
+    Privileged = 11,
+}
+
+impl Event {
+    pub fn bit(self) -> u64 {
+        1 << self as u64
+    }
+}
+
+// a register of a performance-monitoring counter
+enum Hpm {
+    Counter,
+    CounterHigh,
+    Event,
+    EventHigh,
+}
+
+// the register of the performance-monitoring counter at the address, and the index of the counter
+fn hpm(address: u64) -> Option<(Hpm, usize)> {
+    let register = match address {
+        MHPMCOUNTER3..=MHPMCOUNTER31 | HPMCOUNTER3..=HPMCOUNTER31 => Hpm::Counter,
+        MHPMCOUNTER3H..=MHPMCOUNTER31H | HPMCOUNTER3H..=HPMCOUNTER31H => Hpm::CounterHigh,
+        MHPMEVENT3..=MHPMEVENT31 => Hpm::Event,
+        MHPMEVENT3H..=MHPMEVENT31H => Hpm::EventHigh,
+        _ => return None,
+    };
+    Some((register, (address & 0x1f) as usize - 3))
+}
+
+// The counters of the cycles, the time, the instructions retired and the events the
+// performance-monitoring counters select, and the halves of them which RV32 reads. The
+// user-level counters are read-only shadows of the machine-level ones, and time follows mtime of
+// the CLINT. A performance-monitoring counter which overflows sets the OF bit of its mhpmevent,
+// which scountovf shadows, and raises a local counter overflow interrupt unless the bit is set.
+#[derive(Default)]
+pub struct Counters {
+    cycle: u64,
+    instret: u64,
+    time: u64,
+    inhibit: u64,
+    hpm: [u64; HPM_COUNTERS],
+    events: [u64; HPM_COUNTERS],
+    // the mask of the performance-monitoring counters which select an event
+    selected: u32,
+    // whether the instruction being executed has written mcycle, minstret or the
+    // performance-monitoring counters, whose writes take the place of the counting of the
+    // instruction
+    cycle_written: bool,
+    instret_written: bool,
+    hpm_written: u32,
+}
+
+impl Counters {
+    // Starts the counting of an instruction.
+    pub fn begin(&mut self) {
+        self.cycle_written = false;
+        self.instret_written = false;
+        self.hpm_written = 0;
+    }
+
+    // Counts the instruction which has retired in the cycles it has taken, unless the counters
+    // are inhibited or the instruction has written them.
+    pub fn retire(&mut self, cycles: u64) {
+        if self.inhibit & MCOUNTINHIBIT_CY == 0 && !self.cycle_written {
+            self.cycle = self.cycle.wrapping_add(cycles);
+        }
+        if self.inhibit & MCOUNTINHIBIT_IR == 0 && !self.instret_written {
+            self.instret = self.instret.wrapping_add(1);
+        }
+    }
+
+    // whether a performance-monitoring counter selects an event, and so counts
+    pub fn monitoring(&self) -> bool {
+        self.selected != 0
+    }
+
+    // Counts the events which have occurred in the mode on the performance-monitoring counters
+    // which select them, and returns whether a counter has overflowed and raises an interrupt.
+    pub fn count(&mut self, events: u64, mode: PrivilegeMode) -> bool {
+        let inhibit = match mode {
+            PrivilegeMode::User => MHPMEVENT_UINH,
+            PrivilegeMode::Supervisor => MHPMEVENT_SINH,
+            PrivilegeMode::Machine => MHPMEVENT_MINH,
+        };
+        let mut overflow = false;
+        for i in 0..HPM_COUNTERS {
+            let event = self.events[i];
+            let code = event & MHPMEVENT_EVENT;
+            if code >= 64
+                || events >> code & 1 == 0
+                || event & inhibit != 0
+                || (self.inhibit >> (i + 3)) & 1 != 0
+                || (self.hpm_written >> i) & 1 != 0
+            {
+                continue;
+            }
+            self.hpm[i] = self.hpm[i].wrapping_add(1);
+            if self.hpm[i] == 0 && event & MHPMEVENT_OF == 0 {
+                self.events[i] |= MHPMEVENT_OF;
+                overflow = true;
+            }
+        }
+        overflow
+    }
+
+    pub fn set_time(&mut self, time: u64) {
+        self.time = time;
+    }
+}
+
+impl Csr for Counters {
+    fn contains(&self, address: u64) -> bool {
+        ADDRESSES.contains(&address) || hpm(address).is_some()
+    }
+
+    fn addresses(&self) -> Vec<u64> {
+        let mut addresses = ADDRESSES.to_vec();
+        for i in 0..HPM_COUNTERS as u64 {
+            addresses.extend(
+                [
+                    MHPMCOUNTER3 + i,
+                    MHPMCOUNTER3H + i,
+                    MHPMEVENT3 + i,
+                    MHPMEVENT3H + i,
+                    HPMCOUNTER3 + i,
+                    HPMCOUNTER3H + i,
+                ]
+                .iter(),
+            );
+        }
+        addresses
+    }
+
+    fn read(&self, address: u64) -> u64 {
+        if let Some((register, i)) = hpm(address) {
+            return match register {
+                Hpm::Counter => self.hpm[i],
+                Hpm::CounterHigh => self.hpm[i] >> 32,
+                Hpm::Event => self.events[i],
+                Hpm::EventHigh => self.events[i] >> 32,
+            };
+        }
+        match address {
+            MCYCLE | CYCLE => self.cycle,
+            MINSTRET | INSTRET => self.instret,
+            TIME => self.time,
+            MCYCLEH | CYCLEH => self.cycle >> 32,
+            MINSTRETH | INSTRETH => self.instret >> 32,
+            TIMEH => self.time >> 32,
+            MCOUNTINHIBIT => self.inhibit,
+            SCOUNTOVF => self
+                .events
+                .iter()
+                .enumerate()
+                .filter(|(_, &event)| event & MHPMEVENT_OF != 0)
+                .fold(0, |overflows, (i, _)| overflows | 1 << (i + 3)),
+            _ => unreachable!(),
+        }
+    }
+
+    // writes the counter at the address, leaving the read-only shadows alone
+    fn write(&mut self, address: u64, value: u64) {
+        match hpm(address) {
+            Some(_) if address >> 10 == 0b11 => return,
+            Some((register, i)) => {
+                match register {
+                    Hpm::Counter => self.hpm[i] = value,
+                    Hpm::CounterHigh => self.hpm[i] = self.hpm[i] & 0xffff_ffff | value << 32,
+                    Hpm::Event => self.events[i] = value & MHPMEVENT_MASK,
+                    Hpm::EventHigh => {
+                        self.events[i] =
+                            (self.events[i] & 0xffff_ffff | value << 32) & MHPMEVENT_MASK
+                    }
+                }
+                if let Hpm::Counter | Hpm::CounterHigh = register {
+                    self.hpm_written |= 1 << i;
+                } else if self.events[i] & MHPMEVENT_EVENT != 0 {
+                    self.selected |= 1 << i;
+                } else {
+                    self.selected &= !(1 << i);
+                }
+                return;
+            }
+            None => {}
+        }
+        match address {
+            MCYCLE => self.cycle = value,
+            MINSTRET => self.instret = value,
+            MCYCLEH => self.cycle = self.cycle & 0xffff_ffff | value << 32,
+            MINSTRETH => self.instret = self.instret & 0xffff_ffff | value << 32,
+            MCOUNTINHIBIT => self.inhibit = value & MCOUNTINHIBIT_MASK,
+            _ => return,
+        }
+        self.cycle_written |= matches!(address, MCYCLE | MCYCLEH);
+        self.instret_written |= matches!(address, MINSTRET | MINSTRETH);
+    }
+}
+
+const ADDRESSES: [u64; 12] = [
+    MCOUNTINHIBIT,
+    MCYCLE,
+    MINSTRET,
+    MCYCLEH,
+    MINSTRETH,
+    CYCLE,
+    TIME,
+    INSTRET,
+    CYCLEH,
+    TIMEH,
+    INSTRETH,
+    SCOUNTOVF,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retire_ok() {
+        let mut counters = Counters::default();
+        counters.begin();
+        counters.retire(3);
+        assert_eq!(counters.read(CYCLE), 3);
+        assert_eq!(counters.read(INSTRET), 1);
+        // the write takes the place of the counting of the instruction which has made it
+        counters.begin();
+        counters.write(MINSTRET, 0x1_0000_0010);
+        counters.retire(1);
+        assert_eq!(counters.read(MINSTRET), 0x1_0000_0010);
+        assert_eq!(counters.read(INSTRETH), 1);
+        counters.begin();
+        counters.write(MCOUNTINHIBIT, MCOUNTINHIBIT_CY | 0b10);
+        counters.retire(1);
+        assert_eq!(counters.read(MCYCLE), 4);
+        assert_eq!(counters.read(MINSTRET), 0x1_0000_0011);
+        assert_eq!(counters.read(MCOUNTINHIBIT), MCOUNTINHIBIT_CY);
+        // the shadows are read-only
+        counters.write(CYCLE, 0);
+        assert_eq!(counters.read(CYCLE), 4);
+        counters.set_time(0x2_0000_0001);
+        assert_eq!(counters.read(TIMEH), 2);
+    }
+    #[test]
+    fn count_ok() {
+        let mut counters = Counters::default();
+        assert!(!counters.monitoring());
+        counters.write(MHPMEVENT3, Event::Load as u64 | MHPMEVENT_SINH | 1 << 59);
+        counters.write(MHPMEVENT3 + 1, Event::Store as u64);
+        assert!(counters.monitoring());
+        // there is no hypervisor whose modes to inhibit
+        assert_eq!(
+            counters.read(MHPMEVENT3),
+            Event::Load as u64 | MHPMEVENT_SINH
+        );
+        counters.begin();
+        assert!(!counters.count(Event::Load.bit(), PrivilegeMode::User));
+        assert!(!counters.count(Event::Load.bit(), PrivilegeMode::Supervisor));
+        assert_eq!(counters.read(HPMCOUNTER3), 1);
+        counters.write(MHPMCOUNTER3 + 1, u64::MAX);
+        // the write takes the place of the counting
+        assert!(!counters.count(Event::Store.bit(), PrivilegeMode::Machine));
+        counters.begin();
+        assert!(counters.count(Event::Store.bit(), PrivilegeMode::Machine));
+        assert_eq!(counters.read(MHPMEVENT3H + 1), MHPMEVENT_OF >> 32);
+        assert_eq!(counters.read(SCOUNTOVF), 1 << 4);
+        // the overflow is raised once until the OF bit is cleared
+        counters.write(MHPMCOUNTER3 + 1, u64::MAX);
+        counters.begin();
+        assert!(!counters.count(Event::Store.bit(), PrivilegeMode::Machine));
+        counters.write(MCOUNTINHIBIT, 1 << 4);
+        counters.count(Event::Store.bit(), PrivilegeMode::Machine);
+        assert_eq!(counters.read(MHPMCOUNTER3 + 1), 0);
+    }
+}
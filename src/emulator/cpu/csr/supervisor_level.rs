@@ -1,53 +1,75 @@
-use crate::emulator::cpu::csr::Csr;
-use std::collections::HashMap;
+use crate::emulator::cpu::csr::{
+    machine_level::{
+        MIP_LCOFIP, MIP_SSIP, MSTATUS_MXR, MSTATUS_SIE, MSTATUS_SPIE, MSTATUS_SPP, MSTATUS_SUM,
+        MSTATUS_UXL,
+    },
+    Csr,
+};
 
+// The supervisor-level CSRs, but sstatus, sie and sip, which are views of mstatus, mie and mip.
+#[derive(Default)]
 pub struct SupervisorLevelCsr {
-    csr: HashMap<u64, u64>,
+    sedeleg: u64,
+    sideleg: u64,
+    stvec: u64,
+    scounteren: u64,
+    sscratch: u64,
+    sepc: u64,
+    scause: u64,
+    stval: u64,
+    satp: u64,
 }
 
 impl Csr for SupervisorLevelCsr {
     fn contains(&self, address: u64) -> bool {
-        self.csr.contains_key(&address)
+        ADDRESSES.binary_search(&address).is_ok()
     }
 
-    fn csrrw(&mut self, address: u64, value: u64) -> u64 {
-        let t = self.csr[&address];
-        *self.csr.get_mut(&address).unwrap() = value;
-        t
+    fn addresses(&self) -> Vec<u64> {
+        ADDRESSES.to_vec()
     }
 
-    fn csrrs(&mut self, address: u64, value: u64) -> u64 {
-        let t = self.csr[&address];
-        *self.csr.get_mut(&address).unwrap() = self.csr[&address] | value;
-        t
+    fn read(&self, address: u64) -> u64 {
+        match address {
+            SEDELEG => self.sedeleg,
+            SIDELEG => self.sideleg,
+            STVEC => self.stvec,
+            SCOUNTEREN => self.scounteren,
+            SSCRATCH => self.sscratch,
+            SEPC => self.sepc,
+            SCAUSE => self.scause,
+            STVAL => self.stval,
+            SATP => self.satp,
+            _ => unreachable!(),
+        }
     }
 
-    fn csrrc(&mut self, address: u64, value: u64) -> u64 {
-        let t = self.csr[&address];
-        *self.csr.get_mut(&address).unwrap() = self.csr[&address] & !value;
-        t
-    }
-}
-
-impl Default for SupervisorLevelCsr {
-    fn default() -> Self {
-        Self {
-            csr: [
-                // Supervisor Trap Setup (SRW)
-                SSTATUS, SEDELEG, SIDELEG, SIE, STVEC, SCOUNTEREN,
-                // Supervisor Trap Handling (SRW)
-                SSCRATCH, SEPC, SCAUSE, STVAL, SIP,
-                // Supervisor Protection and Translation (SRW)
-                SATP,
-            ]
-            .iter()
-            .cloned()
-            .map(|a| (a, 0))
-            .collect::<HashMap<_, _>>(),
+    fn write(&mut self, address: u64, value: u64) {
+        match address {
+            SEDELEG => self.sedeleg = value,
+            SIDELEG => self.sideleg = value,
+            // the reserved modes are not kept
+            STVEC => self.stvec = value & !0b10,
+            SCOUNTEREN => self.scounteren = value & 0xffff_ffff,
+            SSCRATCH => self.sscratch = value,
+            // as there are no compressed instructions, the pc is aligned to four bytes
+            SEPC => self.sepc = value & !0b11,
+            SCAUSE => self.scause = value,
+            STVAL => self.stval = value,
+            // a write of a mode the harts do not implement has no effect, so that only Bare is
+            // selected, as no addresses are translated
+            SATP if value >> SATP_MODE_SHIFT == SATP_MODE_BARE => self.satp = value,
+            SATP => {}
+            _ => unreachable!(),
         }
     }
 }
 
+// the addresses of the CSRs in ascending order
+const ADDRESSES: [u64; 9] = [
+    SEDELEG, SIDELEG, STVEC, SCOUNTEREN, SSCRATCH, SEPC, SCAUSE, STVAL, SATP,
+];
+
 // Supervisor Trap Setup (SRW)
 pub const SSTATUS: u64 = 0x100; // Supervisor status register.
 pub const SEDELEG: u64 = 0x102; // Supervisor exception delegation register.
@@ -56,6 +78,10 @@ pub const SIE: u64 = 0x104; // Supervisor interrupt-enable register.
 pub const STVEC: u64 = 0x105; // Supervisor trap handler base address.
 pub const SCOUNTEREN: u64 = 0x106; // Supervisor counter enable.
 
+// the fields of mstatus sstatus shows
+pub const SSTATUS_MASK: u64 =
+    MSTATUS_SIE | MSTATUS_SPIE | MSTATUS_SPP | MSTATUS_SUM | MSTATUS_MXR | MSTATUS_UXL;
+
 // Supervisor Trap Handling (SRW)
 pub const SSCRATCH: u64 = 0x140; // Scratch register for supervisor trap handlers.
 pub const SEPC: u64 = 0x141; // Supervisor exception program counter.
@@ -63,5 +89,15 @@ pub const SCAUSE: u64 = 0x142; // Supervisor trap cause.
 pub const STVAL: u64 = 0x143; // Supervisor bad address or instruction.
 pub const SIP: u64 = 0x144; // Supervisor interrupt pending.
 
+// the bits of sip S-mode may write, of the interrupts delegated to it
+pub const SIP_WRITABLE: u64 = MIP_SSIP | MIP_LCOFIP;
+
 // Supervisor Protection and Translation (SRW)
 pub const SATP: u64 = 0x180; // Supervisor address translation and protection.
+
+// the MODE field of satp, of which only Bare is implemented
+const SATP_MODE_SHIFT: u64 = 60;
+const SATP_MODE_BARE: u64 = 0;
+
+// Supervisor Count Overflow (SRO)
+pub const SCOUNTOVF: u64 = 0xda0; // Supervisor count overflow.
@@ -6,12 +6,176 @@ pub mod rv64m;
 pub mod zicsr;
 pub mod zifencei;
 
-use crate::isa::instruction::Instruction;
+use crate::{
+    emulator::cpu::{
+        cost::Kind,
+        csr::counters::Event,
+        decoder::{
+            privileged::PrivilegedDecoder, rv32i::Rv32iDecoder, rv32m::Rv32mDecoder,
+            rv64i::Rv64iDecoder, rv64m::Rv64mDecoder, zicsr::ZicsrDecoder,
+            zifencei::ZifenceiDecoder,
+        },
+    },
+    isa::instruction::{
+        privileged::PrivilegedOpcodeR, rv32i::Rv32iOpcodeI, rv32m::Rv32mOpcodeR,
+        rv64i::Rv64iOpcodeI, rv64m::Rv64mOpcodeR, Instruction,
+    },
+};
 
 const MASK_3BIT: u32 = 0b111;
 const MASK_5BIT: u32 = 0b11111;
 const MASK_7BIT: u32 = 0b1111111;
 
+// the instruction a decoder returns
+pub type Decoding<D> = Instruction<
+    <D as Decoder>::OpcodeR,
+    <D as Decoder>::OpcodeI,
+    <D as Decoder>::OpcodeS,
+    <D as Decoder>::OpcodeB,
+    <D as Decoder>::OpcodeU,
+    <D as Decoder>::OpcodeJ,
+>;
+
+// an instruction decoded by the decoder of its extension
+#[derive(Clone, Copy)]
+pub enum Decoded {
+    Privileged(Decoding<PrivilegedDecoder>),
+    Zifencei(Decoding<ZifenceiDecoder>),
+    Zicsr(Decoding<ZicsrDecoder>),
+    Rv32i(Decoding<Rv32iDecoder>),
+    Rv64i(Decoding<Rv64iDecoder>),
+    Rv32m(Decoding<Rv32mDecoder>),
+    Rv64m(Decoding<Rv64mDecoder>),
+}
+
+impl Decoded {
+    // Whether the instruction ends a basic block, as it may go anywhere but the next instruction,
+    // or change the mode, the CSRs or the translations the instructions which follow depend on.
+    pub fn ends_block(&self) -> bool {
+        match self {
+            Decoded::Rv32i(Instruction::TypeI { opcode, .. }) => matches!(
+                opcode,
+                Rv32iOpcodeI::Jalr | Rv32iOpcodeI::Ecall | Rv32iOpcodeI::Ebreak
+            ),
+            Decoded::Rv32i(Instruction::TypeB { .. })
+            | Decoded::Rv32i(Instruction::TypeJ { .. }) => true,
+            Decoded::Rv32i(_) | Decoded::Rv64i(_) | Decoded::Rv32m(_) | Decoded::Rv64m(_) => false,
+            Decoded::Privileged(_) | Decoded::Zifencei(_) | Decoded::Zicsr(_) => true,
+        }
+    }
+
+    // what the instruction does, by which it takes its cycles
+    pub fn kind(&self) -> Kind {
+        match self {
+            Decoded::Rv32i(Instruction::TypeI { opcode, .. }) => match opcode {
+                Rv32iOpcodeI::Lb
+                | Rv32iOpcodeI::Lh
+                | Rv32iOpcodeI::Lw
+                | Rv32iOpcodeI::Lbu
+                | Rv32iOpcodeI::Lhu => Kind::Load,
+                Rv32iOpcodeI::Jalr => Kind::Jump,
+                Rv32iOpcodeI::Fence | Rv32iOpcodeI::Ecall | Rv32iOpcodeI::Ebreak => Kind::System,
+                _ => Kind::Alu,
+            },
+            Decoded::Rv64i(Instruction::TypeI {
+                opcode: Rv64iOpcodeI::Lwu,
+                ..
+            })
+            | Decoded::Rv64i(Instruction::TypeI {
+                opcode: Rv64iOpcodeI::Ld,
+                ..
+            }) => Kind::Load,
+            Decoded::Rv32i(Instruction::TypeS { .. })
+            | Decoded::Rv64i(Instruction::TypeS { .. }) => Kind::Store,
+            Decoded::Rv32i(Instruction::TypeB { .. })
+            | Decoded::Rv32i(Instruction::TypeJ { .. }) => Kind::Jump,
+            Decoded::Rv32i(_) | Decoded::Rv64i(_) => Kind::Alu,
+            Decoded::Rv32m(Instruction::TypeR { opcode, .. }) => match opcode {
+                Rv32mOpcodeR::Mul
+                | Rv32mOpcodeR::Mulh
+                | Rv32mOpcodeR::Mulhsu
+                | Rv32mOpcodeR::Mulhu => Kind::Multiply,
+                _ => Kind::Divide,
+            },
+            Decoded::Rv64m(Instruction::TypeR {
+                opcode: Rv64mOpcodeR::Mulw,
+                ..
+            }) => Kind::Multiply,
+            Decoded::Rv32m(_) | Decoded::Rv64m(_) => Kind::Divide,
+            Decoded::Privileged(_) | Decoded::Zifencei(_) | Decoded::Zicsr(_) => Kind::System,
+        }
+    }
+
+    // the mask of the events the instruction causes as it retires, having jumped or not
+    pub fn events(&self, jumped: bool) -> u64 {
+        let class = match self {
+            Decoded::Rv32i(Instruction::TypeB { .. }) if jumped => {
+                Event::Integer.bit() | Event::BranchTaken.bit()
+            }
+            Decoded::Rv32i(Instruction::TypeB { .. }) => {
+                Event::Integer.bit() | Event::BranchNotTaken.bit()
+            }
+            Decoded::Rv32i(_) | Decoded::Rv64i(_) => Event::Integer.bit(),
+            Decoded::Rv32m(_) | Decoded::Rv64m(_) => Event::Multiply.bit(),
+            Decoded::Zicsr(_) => Event::Zicsr.bit(),
+            Decoded::Zifencei(_) => Event::Zifencei.bit(),
+            Decoded::Privileged(_) => Event::Privileged.bit(),
+        };
+        match self.kind() {
+            Kind::Load => class | Event::Load.bit(),
+            Kind::Store => class | Event::Store.bit(),
+            _ => class,
+        }
+    }
+
+    // whether the instruction may write the CSRs or change the mode, which may enable an
+    // interrupt
+    pub fn writes_csrs(&self) -> bool {
+        matches!(self, Decoded::Zicsr(_) | Decoded::Privileged(_))
+    }
+
+    // whether the instruction orders the fetches of the hart after stores, or changes the
+    // translations, which makes the instructions the hart has decoded stale
+    pub fn fences_code(&self) -> bool {
+        matches!(
+            self,
+            Decoded::Zifencei(_)
+                | Decoded::Privileged(Instruction::TypeR {
+                    opcode: PrivilegedOpcodeR::SfenceVma,
+                    ..
+                })
+        )
+    }
+}
+
+// Decodes the instruction in a single pass: the opcode, funct3 and funct7 pick the one extension
+// which owns the encoding, and only the decoder of that extension looks at the instruction.
+pub fn decode(instruction: u32) -> Option<Decoded> {
+    let opcode = instruction & MASK_7BIT;
+    let funct3 = (instruction >> 12) & MASK_3BIT;
+    let funct7 = (instruction >> 25) & MASK_7BIT;
+    match opcode {
+        // OP and OP-32, where funct7 tells M from I
+        0b0110011 if funct7 == 0b0000001 => Rv32mDecoder::decode(instruction).map(Decoded::Rv32m),
+        0b0111011 if funct7 == 0b0000001 => Rv64mDecoder::decode(instruction).map(Decoded::Rv64m),
+        0b0111011 | 0b0011011 => Rv64iDecoder::decode(instruction).map(Decoded::Rv64i),
+        // LOAD and STORE, where ld, lwu and sd are RV64I
+        0b0000011 if funct3 == 0b011 || funct3 == 0b110 => {
+            Rv64iDecoder::decode(instruction).map(Decoded::Rv64i)
+        }
+        0b0100011 if funct3 == 0b011 => Rv64iDecoder::decode(instruction).map(Decoded::Rv64i),
+        // MISC-MEM, where fence.i is Zifencei
+        0b0001111 if funct3 == 0b001 => ZifenceiDecoder::decode(instruction).map(Decoded::Zifencei),
+        // SYSTEM, where ecall and ebreak are RV32I and funct3 tells the CSR instructions apart
+        // from the privileged ones
+        0b1110011 if funct3 != 0b000 => ZicsrDecoder::decode(instruction).map(Decoded::Zicsr),
+        0b1110011 if instruction >> 20 > 0b1 => {
+            PrivilegedDecoder::decode(instruction).map(Decoded::Privileged)
+        }
+        _ => Rv32iDecoder::decode(instruction).map(Decoded::Rv32i),
+    }
+}
+
 pub trait Decoder {
     type OpcodeR;
     type OpcodeI;
@@ -191,3 +355,51 @@ pub trait Decoder {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::discriminant;
+
+    #[test]
+    fn decode_ok() {
+        // each encoding of the major opcodes, with the registers fields which tell the
+        // privileged instructions apart, is known by at most one extension, and is decoded by it
+        for opcode in (0b0000011..=0b1111111).step_by(4) {
+            for funct3 in 0..=MASK_3BIT {
+                for funct7 in 0..=MASK_7BIT {
+                    for rs2 in [0, 1, 2, 5, MASK_5BIT] {
+                        for register in [0, 1] {
+                            let instruction = funct7 << 25
+                                | rs2 << 20
+                                | register << 15
+                                | funct3 << 12
+                                | register << 7
+                                | opcode;
+                            let known = [
+                                PrivilegedDecoder::decode(instruction).map(Decoded::Privileged),
+                                ZifenceiDecoder::decode(instruction).map(Decoded::Zifencei),
+                                ZicsrDecoder::decode(instruction).map(Decoded::Zicsr),
+                                Rv32iDecoder::decode(instruction).map(Decoded::Rv32i),
+                                Rv64iDecoder::decode(instruction).map(Decoded::Rv64i),
+                                Rv32mDecoder::decode(instruction).map(Decoded::Rv32m),
+                                Rv64mDecoder::decode(instruction).map(Decoded::Rv64m),
+                            ]
+                            .iter()
+                            .flatten()
+                            .copied()
+                            .collect::<Vec<_>>();
+                            assert!(known.len() <= 1, "{:#010x} is ambiguous", instruction);
+                            assert_eq!(
+                                decode(instruction).map(|decoded| discriminant(&decoded)),
+                                known.first().map(discriminant),
+                                "{:#010x}",
+                                instruction
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
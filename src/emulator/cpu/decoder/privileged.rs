@@ -40,7 +40,10 @@ impl Decoder for PrivilegedDecoder {
             0b1110011 => Self::decode_r(
                 match funct3 {
                     0b000 => match funct7 {
-                        0b0000000 => Some(PrivilegedOpcodeR::Uret),
+                        0b0000000 => match rs2 {
+                            0b00010 => Some(PrivilegedOpcodeR::Uret),
+                            _ => None,
+                        },
                         0b0001000 => match rs2 {
                             0b00010 => Some(PrivilegedOpcodeR::Sret),
                             0b00101 => Some(PrivilegedOpcodeR::Wfi),
@@ -96,17 +96,17 @@ impl Decoder for Rv32iDecoder {
                         0b0100000 => Some(Rv32iOpcodeR::Sub),
                         _ => None,
                     },
-                    0b001 => Some(Rv32iOpcodeR::Sll),
-                    0b010 => Some(Rv32iOpcodeR::Slt),
-                    0b011 => Some(Rv32iOpcodeR::Sltu),
-                    0b100 => Some(Rv32iOpcodeR::Xor),
+                    0b001 if funct7 == 0b0000000 => Some(Rv32iOpcodeR::Sll),
+                    0b010 if funct7 == 0b0000000 => Some(Rv32iOpcodeR::Slt),
+                    0b011 if funct7 == 0b0000000 => Some(Rv32iOpcodeR::Sltu),
+                    0b100 if funct7 == 0b0000000 => Some(Rv32iOpcodeR::Xor),
                     0b101 => match funct7 {
                         0b0000000 => Some(Rv32iOpcodeR::Srl),
                         0b0100000 => Some(Rv32iOpcodeR::Sra),
                         _ => None,
                     },
-                    0b110 => Some(Rv32iOpcodeR::Or),
-                    0b111 => Some(Rv32iOpcodeR::And),
+                    0b110 if funct7 == 0b0000000 => Some(Rv32iOpcodeR::Or),
+                    0b111 if funct7 == 0b0000000 => Some(Rv32iOpcodeR::And),
                     _ => None,
                 },
                 instruction,
@@ -135,6 +135,7 @@ impl Decoder for Rv32iDecoder {
 }
 
 #[cfg(test)]
+#[allow(clippy::unusual_byte_groupings)]
 mod tests {
     use super::*;
 
@@ -42,7 +42,7 @@ impl Decoder for Rv64iDecoder {
                         0b0100000 => Some(Rv64iOpcodeR::Subw),
                         _ => None,
                     },
-                    0b001 => Some(Rv64iOpcodeR::Sllw),
+                    0b001 if funct7 == 0b0000000 => Some(Rv64iOpcodeR::Sllw),
                     0b101 => match funct7 {
                         0b0000000 => Some(Rv64iOpcodeR::Srlw),
                         0b0100000 => Some(Rv64iOpcodeR::Sraw),
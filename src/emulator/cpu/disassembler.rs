@@ -0,0 +1,267 @@
+use crate::{
+    emulator::cpu::{
+        csr,
+        decoder::{decode, Decoded},
+        x::ABI_NAMES,
+    },
+    isa::instruction::Instruction,
+};
+use std::fmt;
+
+const ZERO: usize = 0;
+const RA: usize = 1;
+
+// the predecessor and successor sets of a fence ordering everything
+const FENCE_ALL: u64 = 0b1111;
+
+#[derive(Clone, Copy)]
+enum Operand {
+    Register(usize),
+    Immediate(i64),
+    // the immediate of lui and auipc, which is shown as the upper 20 bits
+    Upper(u64),
+    // the target of a branch or a jump, resolved against the pc
+    Target(u64),
+    // an offset from a base register
+    Memory(i64, usize),
+    Csr(u64),
+    // the device input, device output, memory reads and memory writes a fence orders
+    Fence(u64),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Operand::Register(register) => f.write_str(ABI_NAMES[register]),
+            Operand::Immediate(value) => write!(f, "{}", value),
+            Operand::Upper(value) => write!(f, "{:#x}", value),
+            Operand::Target(address) => write!(f, "{:#x}", address),
+            Operand::Memory(offset, base) => write!(f, "{}({})", offset, ABI_NAMES[base]),
+            Operand::Csr(address) => match csr::name(address) {
+                Some(name) => f.write_str(&name),
+                None => write!(f, "{:#x}", address),
+            },
+            Operand::Fence(set) => {
+                for (bit, access) in [(8, 'i'), (4, 'o'), (2, 'r'), (1, 'w')] {
+                    if set & bit != 0 {
+                        write!(f, "{}", access)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// Disassembles the instruction at the address into the assembly of GNU, with the ABI names of
+// the registers, the pseudo-instructions and the targets of branches and jumps resolved.
+pub fn disassemble(instruction: u32, address: u64) -> String {
+    let (mnemonic, operands) = match decode(instruction) {
+        Some(Decoded::Privileged(decoded)) => assemble(decoded, address),
+        Some(Decoded::Zifencei(decoded)) => assemble(decoded, address),
+        Some(Decoded::Zicsr(decoded)) => assemble(decoded, address),
+        Some(Decoded::Rv32i(decoded)) => assemble(decoded, address),
+        Some(Decoded::Rv64i(decoded)) => assemble(decoded, address),
+        Some(Decoded::Rv32m(decoded)) => assemble(decoded, address),
+        Some(Decoded::Rv64m(decoded)) => assemble(decoded, address),
+        None => return format!(".word {:#010x}", instruction),
+    };
+    let (mnemonic, operands) = pseudo(&mnemonic, operands);
+    let operands = operands
+        .iter()
+        .map(Operand::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    if operands.is_empty() {
+        mnemonic.to_string()
+    } else {
+        format!("{} {}", mnemonic, operands)
+    }
+}
+
+// lays out the operands of the instruction by its format and, where the format does not tell,
+// by its mnemonic
+fn assemble<R, I, S, B, U, J>(
+    instruction: Instruction<R, I, S, B, U, J>,
+    address: u64,
+) -> (String, Vec<Operand>)
+where
+    R: fmt::Display,
+    I: fmt::Display,
+    S: fmt::Display,
+    B: fmt::Display,
+    U: fmt::Display,
+    J: fmt::Display,
+{
+    use Operand::*;
+    match instruction {
+        Instruction::TypeR {
+            opcode,
+            rs1,
+            rs2,
+            rd,
+        } => {
+            let mnemonic = opcode.to_string();
+            let operands = match mnemonic.as_str() {
+                "uret" | "sret" | "mret" | "wfi" => vec![],
+                "sfence.vma" => vec![Register(rs1), Register(rs2)],
+                _ => vec![Register(rd), Register(rs1), Register(rs2)],
+            };
+            (mnemonic, operands)
+        }
+        Instruction::TypeI {
+            opcode,
+            rs1,
+            rd,
+            imm,
+        } => {
+            let mnemonic = opcode.to_string();
+            let operands = match mnemonic.as_str() {
+                "ecall" | "ebreak" | "fence.i" => vec![],
+                "fence" => vec![Fence((imm >> 4) & 0xf), Fence(imm & 0xf)],
+                "slli" | "srli" | "srai" | "slliw" | "srliw" | "sraiw" => {
+                    vec![Register(rd), Register(rs1), Immediate((imm & 0x3f) as i64)]
+                }
+                "lb" | "lh" | "lw" | "ld" | "lbu" | "lhu" | "lwu" | "jalr" => {
+                    vec![Register(rd), Memory(imm as i64, rs1)]
+                }
+                "csrrw" | "csrrs" | "csrrc" => vec![Register(rd), Csr(imm & 0xfff), Register(rs1)],
+                "csrrwi" | "csrrsi" | "csrrci" => {
+                    vec![Register(rd), Csr(imm & 0xfff), Immediate(rs1 as i64)]
+                }
+                _ => vec![Register(rd), Register(rs1), Immediate(imm as i64)],
+            };
+            (mnemonic, operands)
+        }
+        Instruction::TypeS {
+            opcode,
+            rs1,
+            rs2,
+            imm,
+        } => (
+            opcode.to_string(),
+            vec![Register(rs2), Memory(imm as i64, rs1)],
+        ),
+        Instruction::TypeB {
+            opcode,
+            rs1,
+            rs2,
+            imm,
+        } => (
+            opcode.to_string(),
+            vec![
+                Register(rs1),
+                Register(rs2),
+                Target(address.wrapping_add(imm)),
+            ],
+        ),
+        Instruction::TypeU { opcode, rd, imm } => (
+            opcode.to_string(),
+            vec![Register(rd), Upper((imm >> 12) & 0xfffff)],
+        ),
+        Instruction::TypeJ { opcode, rd, imm } => (
+            opcode.to_string(),
+            vec![Register(rd), Target(address.wrapping_add(imm))],
+        ),
+    }
+}
+
+// replaces the instruction with the pseudo-instruction the assembler would accept for it
+fn pseudo(mnemonic: &str, operands: Vec<Operand>) -> (&str, Vec<Operand>) {
+    use Operand::*;
+    match (mnemonic, operands.as_slice()) {
+        ("addi", [Register(ZERO), Register(ZERO), Immediate(0)]) => ("nop", vec![]),
+        ("addi", [rd, Register(ZERO), Immediate(imm)]) => ("li", vec![*rd, Immediate(*imm)]),
+        ("addi", [rd, rs, Immediate(0)]) => ("mv", vec![*rd, *rs]),
+        ("addiw", [rd, rs, Immediate(0)]) => ("sext.w", vec![*rd, *rs]),
+        ("xori", [rd, rs, Immediate(-1)]) => ("not", vec![*rd, *rs]),
+        ("sub", [rd, Register(ZERO), rs]) => ("neg", vec![*rd, *rs]),
+        ("subw", [rd, Register(ZERO), rs]) => ("negw", vec![*rd, *rs]),
+        ("sltiu", [rd, rs, Immediate(1)]) => ("seqz", vec![*rd, *rs]),
+        ("sltu", [rd, Register(ZERO), rs]) => ("snez", vec![*rd, *rs]),
+        ("slt", [rd, rs, Register(ZERO)]) => ("sltz", vec![*rd, *rs]),
+        ("slt", [rd, Register(ZERO), rs]) => ("sgtz", vec![*rd, *rs]),
+        ("beq", [rs, Register(ZERO), target]) => ("beqz", vec![*rs, *target]),
+        ("bne", [rs, Register(ZERO), target]) => ("bnez", vec![*rs, *target]),
+        ("bge", [Register(ZERO), rs, target]) => ("blez", vec![*rs, *target]),
+        ("bge", [rs, Register(ZERO), target]) => ("bgez", vec![*rs, *target]),
+        ("blt", [rs, Register(ZERO), target]) => ("bltz", vec![*rs, *target]),
+        ("blt", [Register(ZERO), rs, target]) => ("bgtz", vec![*rs, *target]),
+        ("jal", [Register(ZERO), target]) => ("j", vec![*target]),
+        ("jal", [Register(RA), target]) => ("jal", vec![*target]),
+        ("jalr", [Register(ZERO), Memory(0, RA)]) => ("ret", vec![]),
+        ("jalr", [Register(ZERO), Memory(0, rs)]) => ("jr", vec![Register(*rs)]),
+        ("jalr", [Register(RA), Memory(0, rs)]) => ("jalr", vec![Register(*rs)]),
+        ("csrrs", [rd, csr, Register(ZERO)]) => ("csrr", vec![*rd, *csr]),
+        ("csrrw", [Register(ZERO), csr, rs]) => ("csrw", vec![*csr, *rs]),
+        ("csrrs", [Register(ZERO), csr, rs]) => ("csrs", vec![*csr, *rs]),
+        ("csrrc", [Register(ZERO), csr, rs]) => ("csrc", vec![*csr, *rs]),
+        ("csrrwi", [Register(ZERO), csr, imm]) => ("csrwi", vec![*csr, *imm]),
+        ("csrrsi", [Register(ZERO), csr, imm]) => ("csrsi", vec![*csr, *imm]),
+        ("csrrci", [Register(ZERO), csr, imm]) => ("csrci", vec![*csr, *imm]),
+        ("fence", [Fence(FENCE_ALL), Fence(FENCE_ALL)]) => ("fence", vec![]),
+        ("sfence.vma", [Register(ZERO), Register(ZERO)]) => ("sfence.vma", vec![]),
+        _ => (mnemonic, operands),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disassemble_ok() {
+        let address = 0x8000_0000;
+        for (instruction, expected) in [
+            (0x01010513, "addi a0, sp, 16"),
+            (0x00000013, "nop"),
+            (0xffb00513, "li a0, -5"),
+            (0x00050593, "mv a1, a0"),
+            (0x03f51513, "slli a0, a0, 63"),
+            (0x4035d593, "srai a1, a1, 3"),
+            (0x4035d59b, "sraiw a1, a1, 3"),
+            (0x0005851b, "sext.w a0, a1"),
+            (0xfff54513, "not a0, a0"),
+            (0x40b00533, "neg a0, a1"),
+            (0x00b03533, "snez a0, a1"),
+            (0x0015b513, "seqz a0, a1"),
+            (0x00c58533, "add a0, a1, a2"),
+            (0x02c5853b, "mulw a0, a1, a2"),
+            (0x02c59533, "mulh a0, a1, a2"),
+            (0x12345537, "lui a0, 0x12345"),
+            (0xfffff297, "auipc t0, 0xfffff"),
+            (0x00813083, "ld ra, 8(sp)"),
+            (0xfff5c503, "lbu a0, -1(a1)"),
+            (0x00813023, "sd s0, 0(sp)"),
+            (0xfea42e23, "sw a0, -4(s0)"),
+            (0x00050463, "beqz a0, 0x80000008"),
+            (0xfeb54ee3, "blt a0, a1, 0x7ffffffc"),
+            (0x00a05863, "blez a0, 0x80000010"),
+            (0xff9ff06f, "j 0x7ffffff8"),
+            (0x100000ef, "jal 0x80000100"),
+            (0x004005ef, "jal a1, 0x80000004"),
+            (0x00008067, "ret"),
+            (0x00050067, "jr a0"),
+            (0x000500e7, "jalr a0"),
+            (0x008505e7, "jalr a1, 8(a0)"),
+            (0x30002573, "csrr a0, mstatus"),
+            (0x30551073, "csrw mtvec, a0"),
+            (0x34051573, "csrrw a0, mscratch, a0"),
+            (0x30046073, "csrsi mstatus, 8"),
+            (0x7c00d573, "csrrwi a0, 0x7c0, 1"),
+            (0xc0002573, "csrr a0, cycle"),
+            (0x0ff0000f, "fence"),
+            (0x0230000f, "fence r, rw"),
+            (0x0000100f, "fence.i"),
+            (0x00000073, "ecall"),
+            (0x00100073, "ebreak"),
+            (0x30200073, "mret"),
+            (0x10500073, "wfi"),
+            (0x12000073, "sfence.vma"),
+            (0x12b50073, "sfence.vma a0, a1"),
+            (0x00000000, ".word 0x00000000"),
+        ] {
+            assert_eq!(disassemble(instruction, address), expected);
+        }
+    }
+}
@@ -8,8 +8,11 @@ pub mod zifencei;
 
 use crate::{
     emulator::{
-        bus::SystemBus,
-        cpu::{csr::ControlAndStatusRegister, pc::ProgramCounter, x::IntegerRegister},
+        bus::{Misaligned, Size, SystemBus},
+        cpu::{
+            csr::ControlAndStatusRegister, mode::PrivilegeMode, pc::ProgramCounter,
+            trap::Exception, x::IntegerRegister,
+        },
     },
     isa::instruction::Instruction,
 };
@@ -38,6 +41,45 @@ pub trait Executor {
         pc: &mut ProgramCounter,
         x: &mut IntegerRegister,
         csr: &mut ControlAndStatusRegister,
+        mode: &mut PrivilegeMode,
         bus: &mut SystemBus,
-    );
+    ) -> Result<(), Exception>;
+}
+
+// Checks that the load of the size at the address is aligned or may be misaligned, and reaches
+// memory or a device, and raises the exception for the address otherwise.
+fn load_address(bus: &SystemBus, address: u64, size: Size) -> Result<u64, Exception> {
+    match misaligned(bus, address, size) {
+        Some(Misaligned::Trap) => Err(Exception::LoadAddressMisaligned),
+        Some(Misaligned::Fault) => Err(Exception::LoadAccessFault),
+        _ if !bus.mapped(address, size) => Err(Exception::LoadAccessFault),
+        _ => Ok(address),
+    }
+}
+
+// Checks that the store of the size at the address is aligned or may be misaligned, and reaches
+// memory or a device, and raises the exception for the address otherwise.
+fn store_address(bus: &SystemBus, address: u64, size: Size) -> Result<u64, Exception> {
+    match misaligned(bus, address, size) {
+        Some(Misaligned::Trap) => Err(Exception::StoreAddressMisaligned),
+        Some(Misaligned::Fault) => Err(Exception::StoreAccessFault),
+        _ if !bus.mapped(address, size) => Err(Exception::StoreAccessFault),
+        _ => Ok(address),
+    }
+}
+
+// what the bus does with the access of the size at the address if the access is misaligned
+fn misaligned(bus: &SystemBus, address: u64, size: Size) -> Option<Misaligned> {
+    (!address.is_multiple_of(size as u64)).then_some(bus.misaligned)
+}
+
+// Checks that the target of a jump or a taken branch is aligned to an instruction, which is four
+// bytes as there are no compressed instructions, and raises an instruction-address-misaligned
+// exception on the jump otherwise.
+fn jump_target(target: u64) -> Result<u64, Exception> {
+    if target.is_multiple_of(4) {
+        Ok(target)
+    } else {
+        Err(Exception::InstructionAddressMisaligned)
+    }
 }
@@ -2,7 +2,18 @@ use crate::{
     emulator::{
         bus::SystemBus,
         cpu::{
-            csr::ControlAndStatusRegister, executor::Executor, pc::ProgramCounter,
+            csr::{
+                machine_level::{
+                    MEPC, MSTATUS, MSTATUS_MIE, MSTATUS_MPIE, MSTATUS_MPP, MSTATUS_MPRV,
+                    MSTATUS_SIE, MSTATUS_SPIE, MSTATUS_SPP, MSTATUS_TSR,
+                },
+                supervisor_level::SEPC,
+                ControlAndStatusRegister,
+            },
+            executor::Executor,
+            mode::PrivilegeMode,
+            pc::ProgramCounter,
+            trap::Exception,
             x::IntegerRegister,
         },
     },
@@ -34,25 +45,118 @@ impl Executor for PrivilegedExecutor {
             PrivilegedOpcodeU,
             PrivilegedOpcodeJ,
         >,
-        _: &mut ProgramCounter,
+        pc: &mut ProgramCounter,
         _: &mut IntegerRegister,
-        _: &mut ControlAndStatusRegister,
+        csr: &mut ControlAndStatusRegister,
+        mode: &mut PrivilegeMode,
         _: &mut SystemBus,
-    ) {
-        if let Instruction::TypeR {
+    ) -> Result<(), Exception> {
+        let Instruction::TypeR {
             opcode,
             rs1: _,
             rs2: _,
             rd: _,
-        } = instruction
-        {
-            match opcode {
-                PrivilegedOpcodeR::Uret => {}      // not yet supported
-                PrivilegedOpcodeR::Sret => {}      // not yet supported
-                PrivilegedOpcodeR::Mret => {}      // not yet supported
-                PrivilegedOpcodeR::Wfi => {}       // not yet supported
-                PrivilegedOpcodeR::SfenceVma => {} // not yet supported
+        } = instruction;
+        match opcode {
+            PrivilegedOpcodeR::Uret => {} // not yet supported
+            // sret is illegal in U-mode, and in S-mode when mstatus.TSR traps it
+            PrivilegedOpcodeR::Sret
+                if *mode == PrivilegeMode::User
+                    || (*mode == PrivilegeMode::Supervisor
+                        && csr.read(MSTATUS) & MSTATUS_TSR != 0) =>
+            {
+                return Err(Exception::IllegalInstruction)
             }
+            PrivilegedOpcodeR::Mret if *mode != PrivilegeMode::Machine => {
+                return Err(Exception::IllegalInstruction)
+            }
+            PrivilegedOpcodeR::Sret => {
+                let mstatus = csr.read(MSTATUS);
+                let mut status = mstatus & !(MSTATUS_SIE | MSTATUS_SPP) | MSTATUS_SPIE;
+                if mstatus & MSTATUS_SPIE != 0 {
+                    status |= MSTATUS_SIE;
+                }
+                *mode =
+                    PrivilegeMode::from((mstatus & MSTATUS_SPP) >> MSTATUS_SPP.trailing_zeros());
+                if *mode != PrivilegeMode::Machine {
+                    status &= !MSTATUS_MPRV;
+                }
+                csr.write(MSTATUS, status);
+                pc.jump(csr.read(SEPC));
+            }
+            PrivilegedOpcodeR::Mret => {
+                let mstatus = csr.read(MSTATUS);
+                let mut status = mstatus & !(MSTATUS_MIE | MSTATUS_MPP) | MSTATUS_MPIE;
+                if mstatus & MSTATUS_MPIE != 0 {
+                    status |= MSTATUS_MIE;
+                }
+                *mode =
+                    PrivilegeMode::from((mstatus & MSTATUS_MPP) >> MSTATUS_MPP.trailing_zeros());
+                if *mode != PrivilegeMode::Machine {
+                    status &= !MSTATUS_MPRV;
+                }
+                csr.write(MSTATUS, status);
+                pc.jump(csr.read(MEPC));
+            }
+            // implemented as a hint, as the hart takes the interrupts which are pending after it
+            PrivilegedOpcodeR::Wfi => {}
+            PrivilegedOpcodeR::SfenceVma => {} // not yet supported
         }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::memory::{Memory, MEMORY_BASE_ADDRESS, MEMORY_SIZE};
+
+    #[test]
+    fn privilege_ok() {
+        let mut pc = ProgramCounter::default();
+        let mut x = IntegerRegister::default();
+        let mut csr = ControlAndStatusRegister::default();
+        let mut bus = SystemBus::new(1, Memory::new(MEMORY_BASE_ADDRESS, MEMORY_SIZE));
+        let mut execute = |opcode, mode: PrivilegeMode, csr: &mut ControlAndStatusRegister| {
+            let instruction = Instruction::TypeR {
+                opcode,
+                rs1: 0,
+                rs2: 0,
+                rd: 0,
+            };
+            let mut mode = mode;
+            PrivilegedExecutor::execute(instruction, &mut pc, &mut x, csr, &mut mode, &mut bus)
+                .map(|_| mode)
+        };
+        let illegal = Err(Exception::IllegalInstruction);
+        assert_eq!(
+            execute(PrivilegedOpcodeR::Mret, PrivilegeMode::User, &mut csr),
+            illegal
+        );
+        assert_eq!(
+            execute(PrivilegedOpcodeR::Mret, PrivilegeMode::Supervisor, &mut csr),
+            illegal
+        );
+        assert_eq!(csr.read(MSTATUS) & MSTATUS_MIE, 0);
+        assert_eq!(
+            execute(PrivilegedOpcodeR::Sret, PrivilegeMode::User, &mut csr),
+            illegal
+        );
+        csr.write(MSTATUS, MSTATUS_TSR);
+        assert_eq!(
+            execute(PrivilegedOpcodeR::Sret, PrivilegeMode::Supervisor, &mut csr),
+            illegal
+        );
+        // M-mode may execute sret whatever mstatus.TSR is
+        assert_eq!(
+            execute(PrivilegedOpcodeR::Sret, PrivilegeMode::Machine, &mut csr),
+            Ok(PrivilegeMode::User)
+        );
+        csr.write(MSTATUS, MSTATUS_MPP | MSTATUS_MPIE);
+        assert_eq!(
+            execute(PrivilegedOpcodeR::Mret, PrivilegeMode::Machine, &mut csr),
+            Ok(PrivilegeMode::Machine)
+        );
+        assert_ne!(csr.read(MSTATUS) & MSTATUS_MIE, 0);
     }
 }
@@ -1,10 +1,12 @@
 use crate::{
     emulator::{
-        bus::SystemBus,
+        bus::{Size, SystemBus},
         cpu::{
             csr::ControlAndStatusRegister,
-            executor::{Executor, MASK_5BIT},
+            executor::{jump_target, load_address, store_address, Executor, MASK_5BIT},
+            mode::PrivilegeMode,
             pc::ProgramCounter,
+            trap::Exception,
             x::IntegerRegister,
         },
     },
@@ -38,8 +40,9 @@ impl Executor for Rv32iExecutor {
         pc: &mut ProgramCounter,
         x: &mut IntegerRegister,
         _: &mut ControlAndStatusRegister,
+        mode: &mut PrivilegeMode,
         bus: &mut SystemBus,
-    ) {
+    ) -> Result<(), Exception> {
         match instruction {
             Instruction::TypeR {
                 opcode,
@@ -75,31 +78,55 @@ impl Executor for Rv32iExecutor {
                 Rv32iOpcodeI::Sltiu => x.writeu(rd, if x.readu(rs1) < imm { 1 } else { 0 }),
                 Rv32iOpcodeI::Jalr => {
                     let last = pc.read();
-                    pc.jump((x.readi(rs1).wrapping_add(imm as i64) & !1) as u64);
+                    pc.jump(jump_target(
+                        (x.readi(rs1).wrapping_add(imm as i64) & !1) as u64,
+                    )?);
                     x.writeu(rd, last.wrapping_add(4));
                 }
-                Rv32iOpcodeI::Fence => {}  // not yet supported
-                Rv32iOpcodeI::Ecall => {}  // not yet supported
-                Rv32iOpcodeI::Ebreak => {} // not yet supported
+                // the harts take turns on the bus and carry out each access before the next, so
+                // that their accesses are in the order of the program already
+                Rv32iOpcodeI::Fence => {}
+                Rv32iOpcodeI::Ecall => return Err(Exception::environment_call(*mode)),
+                Rv32iOpcodeI::Ebreak => return Err(Exception::Breakpoint),
                 Rv32iOpcodeI::Lb => x.writei(
                     rd,
-                    bus.load8(x.readi(rs1).wrapping_add(imm as i64) as u64) as i64,
+                    bus.load8(load_address(
+                        bus,
+                        x.readi(rs1).wrapping_add(imm as i64) as u64,
+                        Size::Byte,
+                    )?) as i64,
                 ),
                 Rv32iOpcodeI::Lh => x.writei(
                     rd,
-                    bus.load16(x.readi(rs1).wrapping_add(imm as i64) as u64) as i64,
+                    bus.load16(load_address(
+                        bus,
+                        x.readi(rs1).wrapping_add(imm as i64) as u64,
+                        Size::Halfword,
+                    )?) as i64,
                 ),
                 Rv32iOpcodeI::Lbu => x.writeu(
                     rd,
-                    bus.load8(x.readi(rs1).wrapping_add(imm as i64) as u64) as u64,
+                    bus.load8(load_address(
+                        bus,
+                        x.readi(rs1).wrapping_add(imm as i64) as u64,
+                        Size::Byte,
+                    )?) as u64,
                 ),
                 Rv32iOpcodeI::Lhu => x.writeu(
                     rd,
-                    bus.load16(x.readi(rs1).wrapping_add(imm as i64) as u64) as u64,
+                    bus.load16(load_address(
+                        bus,
+                        x.readi(rs1).wrapping_add(imm as i64) as u64,
+                        Size::Halfword,
+                    )?) as u64,
                 ),
                 Rv32iOpcodeI::Lw => x.writeu(
                     rd,
-                    bus.load32(x.readi(rs1).wrapping_add(imm as i64) as u64) as u64,
+                    bus.load32(load_address(
+                        bus,
+                        x.readi(rs1).wrapping_add(imm as i64) as u64,
+                        Size::Word,
+                    )?) as u64,
                 ),
             },
             Instruction::TypeS {
@@ -109,15 +136,27 @@ impl Executor for Rv32iExecutor {
                 imm,
             } => match opcode {
                 Rv32iOpcodeS::Sb => bus.store8(
-                    x.readi(rs1).wrapping_add(imm as i64) as u64,
+                    store_address(
+                        bus,
+                        x.readi(rs1).wrapping_add(imm as i64) as u64,
+                        Size::Byte,
+                    )?,
                     x.readu(rs2) as u8,
                 ),
                 Rv32iOpcodeS::Sh => bus.store16(
-                    x.readi(rs1).wrapping_add(imm as i64) as u64,
+                    store_address(
+                        bus,
+                        x.readi(rs1).wrapping_add(imm as i64) as u64,
+                        Size::Halfword,
+                    )?,
                     x.readu(rs2) as u16,
                 ),
                 Rv32iOpcodeS::Sw => bus.store32(
-                    x.readi(rs1).wrapping_add(imm as i64) as u64,
+                    store_address(
+                        bus,
+                        x.readi(rs1).wrapping_add(imm as i64) as u64,
+                        Size::Word,
+                    )?,
                     x.readu(rs2) as u32,
                 ),
             },
@@ -126,48 +165,31 @@ impl Executor for Rv32iExecutor {
                 rs1,
                 rs2,
                 imm,
-            } => match opcode {
-                Rv32iOpcodeB::Beq => {
-                    if x.readu(rs1) == x.readu(rs2) {
-                        pc.jumpr(imm as i64);
-                    }
-                }
-                Rv32iOpcodeB::Bne => {
-                    if x.readu(rs1) != x.readu(rs2) {
-                        pc.jumpr(imm as i64);
-                    }
-                }
-                Rv32iOpcodeB::Blt => {
-                    if x.readi(rs1) < x.readi(rs2) {
-                        pc.jumpr(imm as i64);
-                    }
-                }
-                Rv32iOpcodeB::Bge => {
-                    if x.readi(rs1) >= x.readi(rs2) {
-                        pc.jumpr(imm as i64);
-                    }
-                }
-                Rv32iOpcodeB::Bltu => {
-                    if x.readu(rs1) < x.readu(rs2) {
-                        pc.jumpr(imm as i64);
-                    }
+            } => {
+                let taken = match opcode {
+                    Rv32iOpcodeB::Beq => x.readu(rs1) == x.readu(rs2),
+                    Rv32iOpcodeB::Bne => x.readu(rs1) != x.readu(rs2),
+                    Rv32iOpcodeB::Blt => x.readi(rs1) < x.readi(rs2),
+                    Rv32iOpcodeB::Bge => x.readi(rs1) >= x.readi(rs2),
+                    Rv32iOpcodeB::Bltu => x.readu(rs1) < x.readu(rs2),
+                    Rv32iOpcodeB::Bgeu => x.readu(rs1) >= x.readu(rs2),
+                };
+                if taken {
+                    pc.jump(jump_target(pc.read().wrapping_add(imm))?);
                 }
-                Rv32iOpcodeB::Bgeu => {
-                    if x.readu(rs1) >= x.readu(rs2) {
-                        pc.jumpr(imm as i64);
-                    }
-                }
-            },
+            }
             Instruction::TypeU { opcode, rd, imm } => match opcode {
                 Rv32iOpcodeU::Lui => x.writeu(rd, imm),
                 Rv32iOpcodeU::Auipc => x.writeu(rd, pc.read().wrapping_add(imm)),
             },
             Instruction::TypeJ { opcode, rd, imm } => match opcode {
                 Rv32iOpcodeJ::Jal => {
-                    x.writeu(rd, pc.read().wrapping_add(4));
-                    pc.jumpr(imm as i64);
+                    let last = pc.read();
+                    pc.jump(jump_target(last.wrapping_add(imm))?);
+                    x.writeu(rd, last.wrapping_add(4));
                 }
             },
         }
+        Ok(())
     }
 }
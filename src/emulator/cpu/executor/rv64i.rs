@@ -1,10 +1,12 @@
 use crate::{
     emulator::{
-        bus::SystemBus,
+        bus::{Size, SystemBus},
         cpu::{
             csr::ControlAndStatusRegister,
-            executor::{Executor, MASK_5BIT},
+            executor::{load_address, store_address, Executor, MASK_5BIT},
+            mode::PrivilegeMode,
             pc::ProgramCounter,
+            trap::Exception,
             x::IntegerRegister,
         },
     },
@@ -38,8 +40,9 @@ impl Executor for Rv64iExecutor {
         _: &mut ProgramCounter,
         x: &mut IntegerRegister,
         _: &mut ControlAndStatusRegister,
+        _: &mut PrivilegeMode,
         bus: &mut SystemBus,
-    ) {
+    ) -> Result<(), Exception> {
         match instruction {
             Instruction::TypeR {
                 opcode,
@@ -86,11 +89,20 @@ impl Executor for Rv64iExecutor {
                 Rv64iOpcodeI::Addiw => x.writei(rd, x.readu(rs1).wrapping_add(imm) as i32 as i64),
                 Rv64iOpcodeI::Lwu => x.writeu(
                     rd,
-                    bus.load32(x.readi(rs1).wrapping_add(imm as i64) as u64) as u64,
+                    bus.load32(load_address(
+                        bus,
+                        x.readi(rs1).wrapping_add(imm as i64) as u64,
+                        Size::Word,
+                    )?) as u64,
+                ),
+                Rv64iOpcodeI::Ld => x.writeu(
+                    rd,
+                    bus.load64(load_address(
+                        bus,
+                        x.readi(rs1).wrapping_add(imm as i64) as u64,
+                        Size::Doubleword,
+                    )?),
                 ),
-                Rv64iOpcodeI::Ld => {
-                    x.writeu(rd, bus.load64(x.readi(rs1).wrapping_add(imm as i64) as u64))
-                }
             },
             Instruction::TypeS {
                 opcode,
@@ -98,9 +110,14 @@ impl Executor for Rv64iExecutor {
                 rs2,
                 imm,
             } => match opcode {
-                Rv64iOpcodeS::Sd => {
-                    bus.store64(x.readi(rs1).wrapping_add(imm as i64) as u64, x.readu(rs2))
-                }
+                Rv64iOpcodeS::Sd => bus.store64(
+                    store_address(
+                        bus,
+                        x.readi(rs1).wrapping_add(imm as i64) as u64,
+                        Size::Doubleword,
+                    )?,
+                    x.readu(rs2),
+                ),
             },
             Instruction::TypeB {
                 opcode: _,
@@ -119,5 +136,6 @@ impl Executor for Rv64iExecutor {
                 imm: _,
             } => {}
         }
+        Ok(())
     }
 }
@@ -2,8 +2,8 @@ use crate::{
     emulator::{
         bus::SystemBus,
         cpu::{
-            csr::ControlAndStatusRegister, executor::Executor, pc::ProgramCounter,
-            x::IntegerRegister,
+            csr::ControlAndStatusRegister, executor::Executor, mode::PrivilegeMode,
+            pc::ProgramCounter, trap::Exception, x::IntegerRegister,
         },
     },
     isa::instruction::{
@@ -36,8 +36,9 @@ impl Executor for Rv64mExecutor {
         _: &mut ProgramCounter,
         x: &mut IntegerRegister,
         _: &mut ControlAndStatusRegister,
+        _: &mut PrivilegeMode,
         _: &mut SystemBus,
-    ) {
+    ) -> Result<(), Exception> {
         match instruction {
             Instruction::TypeR {
                 opcode,
@@ -127,5 +128,6 @@ impl Executor for Rv64mExecutor {
                 imm: _,
             } => {}
         }
+        Ok(())
     }
 }
@@ -4,7 +4,9 @@ use crate::{
         cpu::{
             csr::ControlAndStatusRegister,
             executor::{Executor, MASK_12BIT},
+            mode::PrivilegeMode,
             pc::ProgramCounter,
+            trap::Exception,
             x::IntegerRegister,
         },
     },
@@ -38,8 +40,10 @@ impl Executor for ZicsrExecutor {
         _: &mut ProgramCounter,
         x: &mut IntegerRegister,
         csr: &mut ControlAndStatusRegister,
-        _: &mut SystemBus,
-    ) {
+        mode: &mut PrivilegeMode,
+        bus: &mut SystemBus,
+    ) -> Result<(), Exception> {
+        csr.counters.set_time(bus.clint.mtime());
         match instruction {
             Instruction::TypeR {
                 opcode: _,
@@ -47,6 +51,26 @@ impl Executor for ZicsrExecutor {
                 rs2: _,
                 rd: _,
             } => {}
+            Instruction::TypeI {
+                opcode: _,
+                rs1: _,
+                rd: _,
+                imm,
+            } if !csr.contains(imm & MASK_12BIT) => return Err(Exception::IllegalInstruction),
+            // a CSRRS or a CSRRC with x0 or a zero immediate reads the CSR without writing it
+            Instruction::TypeI {
+                opcode,
+                rs1,
+                rd: _,
+                imm,
+            } if !csr.permits(
+                imm & MASK_12BIT,
+                *mode,
+                matches!(opcode, ZicsrOpcodeI::Csrrw | ZicsrOpcodeI::Csrrwi) || rs1 != 0,
+            ) =>
+            {
+                return Err(Exception::IllegalInstruction)
+            }
             Instruction::TypeI {
                 opcode,
                 rs1,
@@ -83,5 +107,61 @@ impl Executor for ZicsrExecutor {
                 imm: _,
             } => {}
         }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::{
+        cpu::csr::machine_level::{MSCRATCH, MSTATUS},
+        memory::{Memory, MEMORY_BASE_ADDRESS, MEMORY_SIZE},
+    };
+
+    #[test]
+    fn privilege_ok() {
+        let mut pc = ProgramCounter::default();
+        let mut x = IntegerRegister::default();
+        let mut csr = ControlAndStatusRegister::default();
+        let mut bus = SystemBus::new(1, Memory::new(MEMORY_BASE_ADDRESS, MEMORY_SIZE));
+        // csrrw a0, mscratch, t1 in U-mode, csrrs a0, mstatus, zero in S-mode and both in M-mode
+        for (address, opcode, mode, result) in [
+            (
+                MSCRATCH,
+                ZicsrOpcodeI::Csrrw,
+                PrivilegeMode::User,
+                Err(Exception::IllegalInstruction),
+            ),
+            (
+                MSTATUS,
+                ZicsrOpcodeI::Csrrs,
+                PrivilegeMode::Supervisor,
+                Err(Exception::IllegalInstruction),
+            ),
+            (
+                MSCRATCH,
+                ZicsrOpcodeI::Csrrw,
+                PrivilegeMode::Machine,
+                Ok(()),
+            ),
+            (MSTATUS, ZicsrOpcodeI::Csrrs, PrivilegeMode::Machine, Ok(())),
+        ]
+        .iter()
+        {
+            x.writeu(6, 0x1234);
+            let instruction = Instruction::TypeI {
+                opcode: *opcode,
+                rs1: if *opcode == ZicsrOpcodeI::Csrrw { 6 } else { 0 },
+                rd: 10,
+                imm: *address,
+            };
+            let mut mode = *mode;
+            assert_eq!(
+                ZicsrExecutor::execute(instruction, &mut pc, &mut x, &mut csr, &mut mode, &mut bus),
+                *result
+            );
+        }
+        assert_eq!(csr.read(MSCRATCH), 0x1234);
     }
 }
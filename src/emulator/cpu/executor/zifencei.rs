@@ -2,8 +2,8 @@ use crate::{
     emulator::{
         bus::SystemBus,
         cpu::{
-            csr::ControlAndStatusRegister, executor::Executor, pc::ProgramCounter,
-            x::IntegerRegister,
+            csr::ControlAndStatusRegister, executor::Executor, mode::PrivilegeMode,
+            pc::ProgramCounter, trap::Exception, x::IntegerRegister,
         },
     },
     isa::instruction::{
@@ -37,8 +37,9 @@ impl Executor for ZifenceiExecutor {
         _: &mut ProgramCounter,
         _: &mut IntegerRegister,
         _: &mut ControlAndStatusRegister,
+        _: &mut PrivilegeMode,
         _: &mut SystemBus,
-    ) {
+    ) -> Result<(), Exception> {
         match instruction {
             Instruction::TypeR {
                 opcode: _,
@@ -52,7 +53,8 @@ impl Executor for ZifenceiExecutor {
                 rd: _,
                 imm: _,
             } => match opcode {
-                ZifenceiOpcodeI::FenceI => {} // not yet supported
+                // the hart throws away the blocks it has decoded once it has executed it
+                ZifenceiOpcodeI::FenceI => {}
             },
             Instruction::TypeS {
                 opcode: _,
@@ -77,5 +79,6 @@ impl Executor for ZifenceiExecutor {
                 imm: _,
             } => {}
         }
+        Ok(())
     }
 }
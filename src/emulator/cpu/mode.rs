@@ -0,0 +1,18 @@
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub enum PrivilegeMode {
+    User = 0b00,
+    Supervisor = 0b01,
+    #[default]
+    Machine = 0b11,
+}
+
+impl From<u64> for PrivilegeMode {
+    fn from(value: u64) -> Self {
+        match value & 0b11 {
+            0b00 => PrivilegeMode::User,
+            0b01 => PrivilegeMode::Supervisor,
+            _ => PrivilegeMode::Machine,
+        }
+    }
+}
@@ -2,12 +2,15 @@ use crate::emulator::memory::MEMORY_BASE_ADDRESS;
 
 pub struct ProgramCounter {
     pc: u64,
+    // whether the pc has jumped, even to where it was, since it was last asked
+    jumped: bool,
 }
 
 impl Default for ProgramCounter {
     fn default() -> Self {
         Self {
             pc: MEMORY_BASE_ADDRESS,
+            jumped: false,
         }
     }
 }
@@ -23,10 +26,12 @@ impl ProgramCounter {
 
     pub fn jump(&mut self, address: u64) {
         self.pc = address;
+        self.jumped = true;
     }
 
-    pub fn jumpr(&mut self, relative_address: i64) {
-        self.pc = (self.pc as i64 + relative_address) as u64;
+    // whether the pc has jumped since this was last called
+    pub fn take_jumped(&mut self) -> bool {
+        std::mem::take(&mut self.jumped)
     }
 
     #[allow(dead_code)]
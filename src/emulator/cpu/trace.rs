@@ -0,0 +1,227 @@
+use crate::emulator::cpu::{csr, disassembler::disassemble, mode::PrivilegeMode};
+use std::cell::RefCell;
+use std::fmt;
+use std::io::Write;
+
+const INTERRUPT_BIT: u64 = 1 << 63;
+
+// the names Spike gives to the exceptions
+const EXCEPTION_NAMES: [&str; 16] = [
+    "trap_instruction_address_misaligned",
+    "trap_instruction_access_fault",
+    "trap_illegal_instruction",
+    "trap_breakpoint",
+    "trap_load_address_misaligned",
+    "trap_load_access_fault",
+    "trap_store_address_misaligned",
+    "trap_store_access_fault",
+    "trap_user_ecall",
+    "trap_supervisor_ecall",
+    "trap_hypervisor_ecall",
+    "trap_machine_ecall",
+    "trap_instruction_page_fault",
+    "trap_load_page_fault",
+    "trap_reserved",
+    "trap_store_page_fault",
+];
+
+// Records the accesses of an instruction while the hart is traced. Nothing is recorded
+// otherwise. Loads are recorded through a shared reference to the bus.
+pub struct Log<T> {
+    entries: RefCell<Option<Vec<T>>>,
+}
+
+impl<T> Default for Log<T> {
+    fn default() -> Self {
+        Self {
+            entries: RefCell::new(None),
+        }
+    }
+}
+
+impl<T> Log<T> {
+    pub fn start(&mut self) {
+        self.entries.replace(Some(Vec::new()));
+    }
+
+    pub fn recording(&self) -> bool {
+        self.entries.borrow().is_some()
+    }
+
+    pub fn record(&self, entry: T) {
+        if let Some(entries) = self.entries.borrow_mut().as_mut() {
+            entries.push(entry);
+        }
+    }
+
+    pub fn stop(&mut self) -> Vec<T> {
+        self.entries.take().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Load { address: u64 },
+    Store { address: u64, value: u64, size: u64 },
+}
+
+// An instruction the hart has retired with what it has written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Commit {
+    pub hartid: usize,
+    pub mode: PrivilegeMode,
+    pub pc: u64,
+    pub instruction: u32,
+    pub registers: Vec<(usize, u64)>,
+    pub csrs: Vec<(u64, u64)>,
+    pub accesses: Vec<Access>,
+}
+
+// Observes the harts, which report each instruction they retire and each trap they take.
+pub trait Tracer {
+    fn commit(&mut self, commit: &Commit);
+    fn trap(&mut self, hartid: usize, cause: u64, epc: u64, tval: u64);
+}
+
+// Writes what the harts do in the format of `spike -l --log-commits`.
+pub struct CommitLog<W> {
+    output: W,
+}
+
+impl<W: Write> CommitLog<W> {
+    pub fn new(output: W) -> Self {
+        Self { output }
+    }
+}
+
+impl<W: Write> Tracer for CommitLog<W> {
+    fn commit(&mut self, commit: &Commit) {
+        let _ = writeln!(self.output, "{}\n{}", commit.disassembly(), commit);
+    }
+
+    fn trap(&mut self, hartid: usize, cause: u64, epc: u64, tval: u64) {
+        let _ = writeln!(self.output, "{}", trap(hartid, cause, epc, tval));
+    }
+}
+
+impl Commit {
+    // the line Spike logs with -l before executing the instruction
+    pub fn disassembly(&self) -> String {
+        let disassembly = disassemble(self.instruction, self.pc);
+        let disassembly = match disassembly.split_once(' ') {
+            Some((mnemonic, operands)) => format!("{:<7} {}", mnemonic, operands),
+            None => disassembly,
+        };
+        format!(
+            "core{:4}: {:#018x} ({:#010x}) {}",
+            self.hartid, self.pc, self.instruction, disassembly
+        )
+    }
+}
+
+// Shows the instruction as `spike --log-commits` does: the privilege level, the writes to the
+// registers and the CSRs, and the memory accesses.
+impl fmt::Display for Commit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "core{:4}: {} {:#018x} ({:#010x})",
+            self.hartid, self.mode as u64, self.pc, self.instruction
+        )?;
+        for (register, value) in &self.registers {
+            write!(f, " x{:<2} {:#018x}", register, value)?;
+        }
+        for (address, value) in &self.csrs {
+            let name = csr::name(*address).unwrap_or_else(|| format!("{:#x}", address));
+            write!(f, " c{}_{} {:#018x}", address, name, value)?;
+        }
+        for access in &self.accesses {
+            if let Access::Load { address } = access {
+                write!(f, " mem {:#018x}", address)?;
+            }
+        }
+        for access in &self.accesses {
+            if let Access::Store {
+                address,
+                value,
+                size,
+            } = access
+            {
+                write!(
+                    f,
+                    " mem {:#018x} {:#0width$x}",
+                    address,
+                    value,
+                    width = *size as usize * 2 + 2
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Shows the trap the hart takes as Spike does.
+pub fn trap(hartid: usize, cause: u64, epc: u64, tval: u64) -> String {
+    if cause & INTERRUPT_BIT != 0 {
+        return format!(
+            "core{:4}: interrupt #{}, epc {:#018x}",
+            hartid,
+            cause & !INTERRUPT_BIT,
+            epc
+        );
+    }
+    let name = EXCEPTION_NAMES
+        .get(cause as usize)
+        .cloned()
+        .unwrap_or("trap_unknown");
+    format!(
+        "core{:4}: exception {}, epc {:#018x}\ncore{:4}:           tval {:#018x}",
+        hartid, name, epc, hartid, tval
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commit_ok() {
+        let commit = Commit {
+            hartid: 0,
+            mode: PrivilegeMode::Machine,
+            pc: 0x8000_000c,
+            instruction: 0x0182b283,
+            registers: vec![(5, 0x8000_0000)],
+            csrs: vec![],
+            accesses: vec![Access::Load {
+                address: 0x8000_0018,
+            }],
+        };
+        assert_eq!(
+            commit.disassembly(),
+            "core   0: 0x000000008000000c (0x0182b283) ld      t0, 24(t0)"
+        );
+        assert_eq!(
+            commit.to_string(),
+            "core   0: 3 0x000000008000000c (0x0182b283) x5  0x0000000080000000 mem 0x0000000080000018"
+        );
+        let commit = Commit {
+            registers: vec![],
+            csrs: vec![(0x305, 0x8000_0004)],
+            accesses: vec![Access::Store {
+                address: 0x8000_1000,
+                value: 1,
+                size: 4,
+            }],
+            ..commit
+        };
+        assert!(commit
+            .to_string()
+            .ends_with(" c773_mtvec 0x0000000080000004 mem 0x0000000080001000 0x00000001"));
+        assert_eq!(
+            trap(1, 2, 0x8000_0000, 0),
+            "core   1: exception trap_illegal_instruction, epc 0x0000000080000000\n\
+             core   1:           tval 0x0000000000000000"
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use crate::emulator::cpu::{
+    csr::{
+        machine_level::{
+            MCAUSE, MEDELEG, MEPC, MIDELEG, MIE, MIP, MSTATUS, MSTATUS_MIE, MSTATUS_MPIE,
+            MSTATUS_MPP, MSTATUS_SIE, MSTATUS_SPIE, MSTATUS_SPP, MTVAL, MTVEC,
+        },
+        supervisor_level::{SCAUSE, SEPC, STVAL, STVEC},
+        ControlAndStatusRegister,
+    },
+    mode::PrivilegeMode,
+    pc::ProgramCounter,
+};
+
+const INTERRUPT_BIT: u64 = 1 << 63;
+const TVEC_MODE_VECTORED: u64 = 0b01;
+const TVEC_MODE_MASK: u64 = 0b11;
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Exception {
+    InstructionAddressMisaligned = 0,
+    InstructionAccessFault = 1,
+    IllegalInstruction = 2,
+    Breakpoint = 3,
+    LoadAddressMisaligned = 4,
+    LoadAccessFault = 5,
+    StoreAddressMisaligned = 6,
+    StoreAccessFault = 7,
+    EnvironmentCallFromUMode = 8,
+    EnvironmentCallFromSMode = 9,
+    EnvironmentCallFromMMode = 11,
+    InstructionPageFault = 12,
+    LoadPageFault = 13,
+    StorePageFault = 15,
+}
+
+impl Exception {
+    pub fn cause(self) -> u64 {
+        self as u64
+    }
+
+    pub fn environment_call(mode: PrivilegeMode) -> Exception {
+        match mode {
+            PrivilegeMode::User => Exception::EnvironmentCallFromUMode,
+            PrivilegeMode::Supervisor => Exception::EnvironmentCallFromSMode,
+            PrivilegeMode::Machine => Exception::EnvironmentCallFromMMode,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interrupt {
+    SupervisorSoftware = 1,
+    MachineSoftware = 3,
+    SupervisorTimer = 5,
+    MachineTimer = 7,
+    SupervisorExternal = 9,
+    MachineExternal = 11,
+    LocalCounterOverflow = 13,
+}
+
+impl Interrupt {
+    // interrupts in the order of their priority
+    const PRIORITY: [Interrupt; 7] = [
+        Interrupt::MachineExternal,
+        Interrupt::MachineSoftware,
+        Interrupt::MachineTimer,
+        Interrupt::SupervisorExternal,
+        Interrupt::SupervisorSoftware,
+        Interrupt::SupervisorTimer,
+        Interrupt::LocalCounterOverflow,
+    ];
+
+    pub fn cause(self) -> u64 {
+        INTERRUPT_BIT | self as u64
+    }
+
+    pub fn pending(mode: PrivilegeMode, csr: &mut ControlAndStatusRegister) -> Option<Interrupt> {
+        let pending = csr.read(MIP) & csr.read(MIE);
+        if pending == 0 {
+            return None;
+        }
+        let mstatus = csr.read(MSTATUS);
+        let mideleg = csr.read(MIDELEG);
+        Self::PRIORITY.iter().cloned().find(|&i| {
+            let bit = 1 << i as u64;
+            if pending & bit == 0 {
+                false
+            } else if mideleg & bit == 0 {
+                mode < PrivilegeMode::Machine || mstatus & MSTATUS_MIE != 0
+            } else {
+                mode < PrivilegeMode::Supervisor
+                    || (mode == PrivilegeMode::Supervisor && mstatus & MSTATUS_SIE != 0)
+            }
+        })
+    }
+}
+
+// Whether the trap for the cause is taken in S-mode, which is when it is delegated and has been
+// taken below M-mode.
+fn delegated(cause: u64, csr: &mut ControlAndStatusRegister, mode: PrivilegeMode) -> bool {
+    let code = cause & !INTERRUPT_BIT;
+    let delegation = csr.read(if cause & INTERRUPT_BIT != 0 {
+        MIDELEG
+    } else {
+        MEDELEG
+    });
+    mode <= PrivilegeMode::Supervisor && (delegation >> code) & 1 == 1
+}
+
+// the address of the trap handler the hart enters for the cause
+pub fn handler(cause: u64, csr: &mut ControlAndStatusRegister, mode: PrivilegeMode) -> u64 {
+    let tvec = if delegated(cause, csr, mode) {
+        csr.read(STVEC)
+    } else {
+        csr.read(MTVEC)
+    };
+    let base = tvec & !TVEC_MODE_MASK;
+    if cause & INTERRUPT_BIT != 0 && tvec & TVEC_MODE_MASK == TVEC_MODE_VECTORED {
+        base + 4 * (cause & !INTERRUPT_BIT)
+    } else {
+        base
+    }
+}
+
+// Enters the trap handler for the cause, in S-mode when the trap is delegated and has been
+// taken below M-mode, otherwise in M-mode.
+pub fn take(
+    cause: u64,
+    tval: u64,
+    pc: &mut ProgramCounter,
+    csr: &mut ControlAndStatusRegister,
+    mode: &mut PrivilegeMode,
+) {
+    let address = handler(cause, csr, *mode);
+    let mstatus = csr.read(MSTATUS);
+    let status = if delegated(cause, csr, *mode) {
+        csr.write(SEPC, pc.read());
+        csr.write(SCAUSE, cause);
+        csr.write(STVAL, tval);
+        let mut status = mstatus & !(MSTATUS_SPIE | MSTATUS_SIE | MSTATUS_SPP);
+        if mstatus & MSTATUS_SIE != 0 {
+            status |= MSTATUS_SPIE;
+        }
+        if *mode == PrivilegeMode::Supervisor {
+            status |= MSTATUS_SPP;
+        }
+        *mode = PrivilegeMode::Supervisor;
+        status
+    } else {
+        csr.write(MEPC, pc.read());
+        csr.write(MCAUSE, cause);
+        csr.write(MTVAL, tval);
+        let mut status = mstatus & !(MSTATUS_MPIE | MSTATUS_MIE | MSTATUS_MPP);
+        if mstatus & MSTATUS_MIE != 0 {
+            status |= MSTATUS_MPIE;
+        }
+        status |= (*mode as u64) << MSTATUS_MPP.trailing_zeros();
+        *mode = PrivilegeMode::Machine;
+        status
+    };
+    csr.write(MSTATUS, status);
+    pc.jump(address);
+}
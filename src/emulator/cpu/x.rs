@@ -1,4 +1,4 @@
-use crate::emulator::memory::MEMORY_SIZE;
+use crate::emulator::{cpu::trace::Log, memory::MEMORY_SIZE};
 
 pub const ZERO: usize = 0;
 #[allow(dead_code)]
@@ -20,21 +20,14 @@ pub const S0: usize = 8;
 pub const FP: usize = 8;
 #[allow(dead_code)]
 pub const S1: usize = 9;
-#[allow(dead_code)]
 pub const A0: usize = 10;
-#[allow(dead_code)]
 pub const A1: usize = 11;
-#[allow(dead_code)]
 pub const A2: usize = 12;
-#[allow(dead_code)]
 pub const A3: usize = 13;
-#[allow(dead_code)]
 pub const A4: usize = 14;
-#[allow(dead_code)]
 pub const A5: usize = 15;
 #[allow(dead_code)]
 pub const A6: usize = 16;
-#[allow(dead_code)]
 pub const A7: usize = 17;
 #[allow(dead_code)]
 pub const S2: usize = 18;
@@ -65,15 +58,25 @@ pub const T5: usize = 30;
 #[allow(dead_code)]
 pub const T6: usize = 31;
 
+pub const ABI_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
+
 pub struct IntegerRegister {
     x: [u64; 32],
+    pub log: Log<(usize, u64)>,
 }
 
 impl Default for IntegerRegister {
     fn default() -> Self {
         let mut x = [0; 32];
         x[SP] = MEMORY_SIZE;
-        Self { x }
+        Self {
+            x,
+            log: Log::default(),
+        }
     }
 }
 
@@ -87,14 +90,13 @@ impl IntegerRegister {
     }
 
     pub fn writei(&mut self, register: usize, value: i64) {
-        if register != ZERO {
-            self.x[register] = value as u64;
-        }
+        self.writeu(register, value as u64);
     }
 
     pub fn writeu(&mut self, register: usize, value: u64) {
         if register != ZERO {
             self.x[register] = value;
+            self.log.record((register, value));
         }
     }
 }
@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result};
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const ELFCLASS32: u8 = 1;
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const EM_RISCV: u16 = 243;
+
+const PT_LOAD: u32 = 1;
+const PT_PHDR: u32 = 6;
+
+const SHT_SYMTAB: u32 = 2;
+
+// An executable parsed just enough to be loaded: the loadable segments, what the auxiliary
+// vector needs to know about the program headers and the addresses of the symbols.
+pub struct Elf {
+    pub entry: u64,
+    pub program_header_address: u64,
+    pub program_header_size: u64,
+    pub program_header_count: u64,
+    pub segments: Vec<Segment>,
+    pub symbols: HashMap<String, u64>,
+}
+
+pub struct Segment {
+    pub address: u64,
+    pub data: Vec<u8>,
+    // the size in memory, the part beyond the data is zero-filled
+    pub size: u64,
+}
+
+impl Elf {
+    pub fn parse(bytes: &[u8]) -> Result<Elf> {
+        if bytes.len() < 52 || &bytes[0..4] != ELF_MAGIC {
+            return Err(invalid("not an ELF file"));
+        }
+        if bytes[5] != ELFDATA2LSB {
+            return Err(invalid("not a little-endian ELF file"));
+        }
+        let reader = match bytes[4] {
+            ELFCLASS32 => Reader { bytes, wide: false },
+            ELFCLASS64 => Reader { bytes, wide: true },
+            _ => return Err(invalid("unknown ELF class")),
+        };
+        if reader.u16(18)? != EM_RISCV {
+            return Err(invalid("not a RISC-V executable"));
+        }
+        // the offsets of the fields following e_entry depend on the class
+        let (entry, phoff, phentsize, phnum) = if reader.wide {
+            (
+                reader.u64(24)?,
+                reader.u64(32)?,
+                reader.u16(54)?,
+                reader.u16(56)?,
+            )
+        } else {
+            (
+                reader.u32(24)? as u64,
+                reader.u32(28)? as u64,
+                reader.u16(42)?,
+                reader.u16(44)?,
+            )
+        };
+
+        let mut segments = Vec::new();
+        let mut program_header_address = None;
+        for i in 0..phnum as u64 {
+            let header = reader.offset(phoff, i * phentsize as u64)?;
+            let (kind, offset, address, file_size, memory_size) = if reader.wide {
+                (
+                    reader.u32(header)?,
+                    reader.u64(header + 8)?,
+                    reader.u64(header + 16)?,
+                    reader.u64(header + 32)?,
+                    reader.u64(header + 40)?,
+                )
+            } else {
+                (
+                    reader.u32(header)?,
+                    reader.u32(header + 4)? as u64,
+                    reader.u32(header + 8)? as u64,
+                    reader.u32(header + 16)? as u64,
+                    reader.u32(header + 20)? as u64,
+                )
+            };
+            match kind {
+                PT_LOAD => {
+                    let data = offset
+                        .checked_add(file_size)
+                        .filter(|end| *end <= bytes.len() as u64)
+                        .map(|end| &bytes[offset as usize..end as usize])
+                        .ok_or_else(|| invalid("a segment is out of the file"))?;
+                    let size = memory_size.max(file_size);
+                    if address.checked_add(size).is_none() {
+                        return Err(invalid("a segment is out of the address space"));
+                    }
+                    // the program headers are mapped as part of the segment starting at the
+                    // beginning of the file
+                    if program_header_address.is_none()
+                        && offset <= phoff
+                        && phoff < offset + file_size
+                    {
+                        program_header_address = Some(address + phoff - offset);
+                    }
+                    segments.push(Segment {
+                        address,
+                        data: data.to_vec(),
+                        size,
+                    });
+                }
+                PT_PHDR => program_header_address = Some(address),
+                _ => {}
+            }
+        }
+        Ok(Elf {
+            entry,
+            program_header_address: program_header_address.unwrap_or(0),
+            program_header_size: phentsize as u64,
+            program_header_count: phnum as u64,
+            segments,
+            symbols: reader.symbols()?,
+        })
+    }
+
+    pub fn symbol(&self, name: &str) -> Option<u64> {
+        self.symbols.get(name).cloned()
+    }
+
+    // the end of the highest segment, where the heap begins
+    pub fn end(&self) -> u64 {
+        self.segments
+            .iter()
+            .map(|s| s.address + s.size)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    wide: bool,
+}
+
+impl Reader<'_> {
+    // the symbols of the symbol table, which a stripped executable does not have
+    fn symbols(&self) -> Result<HashMap<String, u64>> {
+        let (shoff, shentsize, shnum) = if self.wide {
+            (self.u64(40)?, self.u16(58)?, self.u16(60)?)
+        } else {
+            (self.u32(32)? as u64, self.u16(46)?, self.u16(48)?)
+        };
+        // the offset, the size and the linked section of a section header
+        let section = |index: u64| -> Result<(u32, u64, u64, u64)> {
+            let header = self.offset(shoff, index * shentsize as u64)?;
+            if self.wide {
+                Ok((
+                    self.u32(header + 4)?,
+                    self.u64(header + 24)?,
+                    self.u64(header + 32)?,
+                    self.u32(header + 40)? as u64,
+                ))
+            } else {
+                Ok((
+                    self.u32(header + 4)?,
+                    self.u32(header + 16)? as u64,
+                    self.u32(header + 20)? as u64,
+                    self.u32(header + 24)? as u64,
+                ))
+            }
+        };
+        let mut symbols = HashMap::new();
+        for i in 0..shnum as u64 {
+            let (kind, offset, size, link) = section(i)?;
+            if kind != SHT_SYMTAB {
+                continue;
+            }
+            let (_, strings, _, _) = section(link)?;
+            let entry_size = if self.wide { 24 } else { 16 };
+            let end = self.offset(offset, size)?;
+            for symbol in (self.offset(offset, 0)?..end).step_by(entry_size) {
+                let name = self.u32(symbol)? as u64;
+                let value = if self.wide {
+                    self.u64(symbol + 8)?
+                } else {
+                    self.u32(symbol + 4)? as u64
+                };
+                let name = self.string(self.offset(strings, name)?)?;
+                if !name.is_empty() {
+                    symbols.insert(name, value);
+                }
+            }
+        }
+        Ok(symbols)
+    }
+
+    // the offset from the base, which is to be within the file
+    fn offset(&self, base: u64, offset: u64) -> Result<usize> {
+        base.checked_add(offset)
+            .filter(|offset| *offset <= self.bytes.len() as u64)
+            .map(|offset| offset as usize)
+            .ok_or_else(|| invalid("an offset is out of the file"))
+    }
+
+    fn string(&self, offset: usize) -> Result<String> {
+        let bytes = self
+            .bytes
+            .get(offset..)
+            .ok_or_else(|| invalid("truncated ELF file"))?;
+        let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+        Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    }
+
+    fn field<const N: usize>(&self, offset: usize) -> Result<[u8; N]> {
+        let mut field = [0; N];
+        field.copy_from_slice(
+            self.bytes
+                .get(offset..offset.saturating_add(N))
+                .ok_or_else(|| invalid("truncated ELF file"))?,
+        );
+        Ok(field)
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.field(offset)?))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.field(offset)?))
+    }
+
+    fn u64(&self, offset: usize) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.field(offset)?))
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    // Builds an ELF64 executable with a single segment mapping the headers and the program at
+    // the address, followed by a symbol table. The entry is the first instruction.
+    pub fn executable(address: u64, program: &[u32], symbols: &[(&str, u64)]) -> Vec<u8> {
+        let (header_size, program_header_size, section_header_size) = (64, 56, 64);
+        let text = header_size + program_header_size;
+        let size = text + program.len() as u64 * 4;
+        let mut strings = vec![0];
+        let mut table = vec![0; 24];
+        for (name, value) in symbols {
+            table.extend_from_slice(&(strings.len() as u32).to_le_bytes());
+            table.extend_from_slice(&[0; 4]);
+            table.extend_from_slice(&value.to_le_bytes());
+            table.extend_from_slice(&[0; 8]);
+            strings.extend_from_slice(name.as_bytes());
+            strings.push(0);
+        }
+        let strings_offset = size;
+        let table_offset = strings_offset + strings.len() as u64;
+        let sections_offset = table_offset + table.len() as u64;
+
+        let mut elf = b"\x7fELF\x02\x01\x01".to_vec();
+        elf.resize(16, 0);
+        elf.extend_from_slice(&2u16.to_le_bytes()); // e_type
+        elf.extend_from_slice(&EM_RISCV.to_le_bytes());
+        elf.extend_from_slice(&1u32.to_le_bytes()); // e_version
+        elf.extend_from_slice(&(address + text).to_le_bytes());
+        elf.extend_from_slice(&header_size.to_le_bytes()); // e_phoff
+        elf.extend_from_slice(&sections_offset.to_le_bytes());
+        elf.extend_from_slice(&0u32.to_le_bytes()); // e_flags
+        for half in &[
+            header_size,
+            program_header_size,
+            1,
+            section_header_size,
+            3,
+            0,
+        ] {
+            elf.extend_from_slice(&(*half as u16).to_le_bytes());
+        }
+        elf.extend_from_slice(&PT_LOAD.to_le_bytes());
+        elf.extend_from_slice(&5u32.to_le_bytes()); // PF_R | PF_X
+        for field in &[0, address, address, size, size, 0x1000] {
+            elf.extend_from_slice(&field.to_le_bytes());
+        }
+        for instruction in program {
+            elf.extend_from_slice(&instruction.to_le_bytes());
+        }
+        elf.extend_from_slice(&strings);
+        elf.extend_from_slice(&table);
+        // the null section, the string table and the symbol table linked to it
+        for (kind, offset, size, link) in &[
+            (0, 0, 0, 0),
+            (3, strings_offset, strings.len() as u64, 0),
+            (SHT_SYMTAB, table_offset, table.len() as u64, 1),
+        ] {
+            let mut header = vec![0; section_header_size as usize];
+            header[4..8].copy_from_slice(&kind.to_le_bytes());
+            header[24..32].copy_from_slice(&offset.to_le_bytes());
+            header[32..40].copy_from_slice(&size.to_le_bytes());
+            header[40..44].copy_from_slice(&(*link as u32).to_le_bytes());
+            elf.extend_from_slice(&header);
+        }
+        elf
+    }
+
+    #[test]
+    fn parse_ok() {
+        let bytes = executable(0x10000, &[0x00000013], &[("tohost", 0x11000)]);
+        let elf = Elf::parse(&bytes).unwrap();
+        assert_eq!(elf.entry, 0x10078);
+        assert_eq!(elf.program_header_address, 0x10040);
+        assert_eq!(elf.segments.len(), 1);
+        assert_eq!(elf.end(), 0x1007c);
+        assert_eq!(elf.symbol("tohost"), Some(0x11000));
+        assert_eq!(elf.symbol("fromhost"), None);
+        assert!(Elf::parse(b"\x7fELF").is_err());
+
+        // a segment whose end in the file overflows
+        let mut bytes = executable(0x10000, &[0x00000013], &[]);
+        bytes[64 + 8..64 + 16].copy_from_slice(&8u64.to_le_bytes());
+        bytes[64 + 32..64 + 40].copy_from_slice(&u64::MAX.to_le_bytes());
+        let error = Elf::parse(&bytes).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        // a segment which ends beyond the address space
+        let mut bytes = executable(0x10000, &[0x00000013], &[]);
+        bytes[64 + 40..64 + 48].copy_from_slice(&u64::MAX.to_le_bytes());
+        let error = Elf::parse(&bytes).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        // program headers which lie beyond the file
+        let mut bytes = executable(0x10000, &[0x00000013], &[]);
+        bytes[32..40].copy_from_slice(&(u64::MAX - 8).to_le_bytes());
+        let error = Elf::parse(&bytes).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
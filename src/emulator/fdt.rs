@@ -0,0 +1,276 @@
+use crate::emulator::clint::{CLINT_BASE_ADDRESS, CLINT_SIZE};
+
+const FDT_MAGIC: u32 = 0xd00dfeed;
+const FDT_VERSION: u32 = 17;
+const FDT_LAST_COMP_VERSION: u32 = 16;
+const FDT_HEADER_SIZE: usize = 40;
+const FDT_RESERVE_MAP_SIZE: usize = 16;
+
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_END: u32 = 0x9;
+
+const TIMEBASE_FREQUENCY: u32 = 10_000_000;
+// interrupt numbers as seen by the local interrupt controller of each hart
+const IRQ_M_SOFT: u32 = 3;
+const IRQ_M_TIMER: u32 = 7;
+
+#[derive(Default)]
+pub struct FdtBuilder {
+    structure: Vec<u8>,
+    strings: Vec<u8>,
+}
+
+impl FdtBuilder {
+    pub fn begin_node(&mut self, name: &str) {
+        self.push_u32(FDT_BEGIN_NODE);
+        self.structure.extend_from_slice(name.as_bytes());
+        self.structure.push(0);
+        self.align();
+    }
+
+    pub fn end_node(&mut self) {
+        self.push_u32(FDT_END_NODE);
+    }
+
+    pub fn property(&mut self, name: &str, value: &[u8]) {
+        let offset = self.string_offset(name);
+        self.push_u32(FDT_PROP);
+        self.push_u32(value.len() as u32);
+        self.push_u32(offset);
+        self.structure.extend_from_slice(value);
+        self.align();
+    }
+
+    pub fn property_null(&mut self, name: &str) {
+        self.property(name, &[]);
+    }
+
+    pub fn property_u32(&mut self, name: &str, value: u32) {
+        self.property(name, &value.to_be_bytes());
+    }
+
+    pub fn property_u64(&mut self, name: &str, value: u64) {
+        self.property(name, &value.to_be_bytes());
+    }
+
+    pub fn property_cells(&mut self, name: &str, cells: &[u32]) {
+        let value = cells
+            .iter()
+            .flat_map(|c| c.to_be_bytes().to_vec())
+            .collect::<Vec<_>>();
+        self.property(name, &value);
+    }
+
+    pub fn property_string(&mut self, name: &str, value: &str) {
+        self.property_strings(name, &[value]);
+    }
+
+    pub fn property_strings(&mut self, name: &str, values: &[&str]) {
+        let mut bytes = Vec::new();
+        for value in values {
+            bytes.extend_from_slice(value.as_bytes());
+            bytes.push(0);
+        }
+        self.property(name, &bytes);
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        self.push_u32(FDT_END);
+        let off_mem_rsvmap = FDT_HEADER_SIZE;
+        let off_dt_struct = off_mem_rsvmap + FDT_RESERVE_MAP_SIZE;
+        let off_dt_strings = off_dt_struct + self.structure.len();
+        let total_size = off_dt_strings + self.strings.len();
+        let mut blob = Vec::with_capacity(total_size);
+        for field in &[
+            FDT_MAGIC,
+            total_size as u32,
+            off_dt_struct as u32,
+            off_dt_strings as u32,
+            off_mem_rsvmap as u32,
+            FDT_VERSION,
+            FDT_LAST_COMP_VERSION,
+            0, // boot_cpuid_phys
+            self.strings.len() as u32,
+            self.structure.len() as u32,
+        ] {
+            blob.extend_from_slice(&field.to_be_bytes());
+        }
+        // an empty memory reservation block is terminated by a zeroed entry
+        blob.extend_from_slice(&[0; FDT_RESERVE_MAP_SIZE]);
+        blob.extend_from_slice(&self.structure);
+        blob.extend_from_slice(&self.strings);
+        blob
+    }
+
+    fn push_u32(&mut self, value: u32) {
+        self.structure.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn align(&mut self) {
+        let len = (self.structure.len() + 3) & !3;
+        self.structure.resize(len, 0);
+    }
+
+    fn string_offset(&mut self, name: &str) -> u32 {
+        let mut offset = 0;
+        for s in self.strings.split(|b| *b == 0) {
+            if s == name.as_bytes() {
+                return offset as u32;
+            }
+            offset += s.len() + 1;
+        }
+        let offset = self.strings.len();
+        self.strings.extend_from_slice(name.as_bytes());
+        self.strings.push(0);
+        offset as u32
+    }
+}
+
+pub struct Chosen<'a> {
+    pub bootargs: &'a str,
+    pub initrd: Option<(u64, u64)>,
+}
+
+// Describes the harts, the memory, which starts at the base and has the size, and the devices.
+pub fn generate(harts: usize, (base, size): (u64, u64), chosen: &Chosen) -> Vec<u8> {
+    // the phandle of the interrupt controller of each hart
+    let intc_phandle = |hartid: usize| hartid as u32 + 1;
+    let mut fdt = FdtBuilder::default();
+    fdt.begin_node("");
+    fdt.property_u32("#address-cells", 2);
+    fdt.property_u32("#size-cells", 2);
+    fdt.property_string("compatible", "plavelo,five");
+    fdt.property_string("model", "five");
+
+    fdt.begin_node("chosen");
+    fdt.property_string("bootargs", chosen.bootargs);
+    if let Some((start, end)) = chosen.initrd {
+        fdt.property_u64("linux,initrd-start", start);
+        fdt.property_u64("linux,initrd-end", end);
+    }
+    fdt.end_node();
+
+    fdt.begin_node("cpus");
+    fdt.property_u32("#address-cells", 1);
+    fdt.property_u32("#size-cells", 0);
+    fdt.property_u32("timebase-frequency", TIMEBASE_FREQUENCY);
+    for hartid in 0..harts {
+        fdt.begin_node(&format!("cpu@{}", hartid));
+        fdt.property_string("device_type", "cpu");
+        fdt.property_u32("reg", hartid as u32);
+        fdt.property_string("status", "okay");
+        fdt.property_string("compatible", "riscv");
+        fdt.property_string("riscv,isa", "rv64im_zicsr_zifencei_sscofpmf");
+        // the harts translate no addresses
+        fdt.property_string("mmu-type", "riscv,none");
+        fdt.begin_node("interrupt-controller");
+        fdt.property_u32("#interrupt-cells", 1);
+        fdt.property_null("interrupt-controller");
+        fdt.property_string("compatible", "riscv,cpu-intc");
+        fdt.property_u32("phandle", intc_phandle(hartid));
+        fdt.end_node();
+        fdt.end_node();
+    }
+    fdt.end_node();
+
+    fdt.begin_node(&format!("memory@{:x}", base));
+    fdt.property_string("device_type", "memory");
+    fdt.property_cells("reg", &reg(base, size));
+    fdt.end_node();
+
+    fdt.begin_node("soc");
+    fdt.property_u32("#address-cells", 2);
+    fdt.property_u32("#size-cells", 2);
+    fdt.property_string("compatible", "simple-bus");
+    fdt.property_null("ranges");
+    fdt.begin_node(&format!("clint@{:x}", CLINT_BASE_ADDRESS));
+    fdt.property_strings("compatible", &["sifive,clint0", "riscv,clint0"]);
+    fdt.property_cells("reg", &reg(CLINT_BASE_ADDRESS, CLINT_SIZE));
+    fdt.property_cells(
+        "interrupts-extended",
+        &(0..harts)
+            .flat_map(|h| vec![intc_phandle(h), IRQ_M_SOFT, intc_phandle(h), IRQ_M_TIMER])
+            .collect::<Vec<_>>(),
+    );
+    fdt.end_node();
+    fdt.end_node();
+
+    fdt.end_node();
+    fdt.finish()
+}
+
+// a reg property with two address cells and two size cells
+fn reg(address: u64, size: u64) -> [u32; 4] {
+    [
+        (address >> 32) as u32,
+        address as u32,
+        (size >> 32) as u32,
+        size as u32,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finish_ok() {
+        let mut fdt = FdtBuilder::default();
+        fdt.begin_node("");
+        fdt.property_u32("a", 1);
+        fdt.property_string("b", "c");
+        fdt.property_u32("a", 2);
+        fdt.end_node();
+        let blob = fdt.finish();
+        assert_eq!(&blob[0..4], &FDT_MAGIC.to_be_bytes());
+        assert_eq!(&blob[4..8], &(blob.len() as u32).to_be_bytes());
+        // property names are deduplicated in the strings block
+        assert_eq!(&blob[blob.len() - 4..], b"a\0b\0");
+    }
+
+    #[test]
+    fn generate_ok() {
+        let contains = |blob: &[u8], bytes: &[u8]| blob.windows(bytes.len()).any(|w| w == bytes);
+        let blob = generate(
+            2,
+            (0x8000_0000, 0x4000_0000),
+            &Chosen {
+                bootargs: "console=hvc0",
+                initrd: Some((0x8100_0000, 0x8100_1000)),
+            },
+        );
+        assert_eq!(&blob[0..4], &FDT_MAGIC.to_be_bytes());
+        assert_eq!(&blob[4..8], &(blob.len() as u32).to_be_bytes());
+        assert!(contains(&blob, b"console=hvc0\0"));
+        assert!(contains(&blob, &0x8100_0000u64.to_be_bytes()));
+        assert!(contains(&blob, &0x8100_1000u64.to_be_bytes()));
+        // a node for each hart, and the memory with its range
+        assert!(contains(&blob, b"cpu@0\0"));
+        assert!(contains(&blob, b"cpu@1\0"));
+        assert!(contains(&blob, b"riscv,none\0"));
+        assert!(!contains(&blob, b"cpu@2\0"));
+        assert!(contains(&blob, b"memory@80000000\0"));
+        let reg = reg(0x8000_0000, 0x4000_0000)
+            .iter()
+            .flat_map(|cell| cell.to_be_bytes().to_vec())
+            .collect::<Vec<_>>();
+        assert!(contains(&blob, &reg));
+        // the interrupts of the CLINT reach the controller of each hart
+        let interrupts = [1, IRQ_M_SOFT, 1, IRQ_M_TIMER, 2, IRQ_M_SOFT, 2, IRQ_M_TIMER]
+            .iter()
+            .flat_map(|cell| cell.to_be_bytes().to_vec())
+            .collect::<Vec<_>>();
+        assert!(contains(&blob, &interrupts));
+        let blob = generate(
+            1,
+            (0x8000_0000, 0x4000_0000),
+            &Chosen {
+                bootargs: "",
+                initrd: None,
+            },
+        );
+        assert!(!contains(&blob, b"linux,initrd-start"));
+    }
+}
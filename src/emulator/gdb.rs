@@ -0,0 +1,910 @@
+use crate::emulator::{
+    cpu::{csr, trap::Exception, x::ABI_NAMES},
+    replay, snapshot,
+    syscall::{read_bytes, write_bytes},
+    Emulator, EmulatorError, RunOutcome,
+};
+use std::collections::HashSet;
+use std::io::{ErrorKind, Read, Result, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+const INTERRUPT: u8 = 0x03;
+const PACKET_SIZE: usize = 0x4000;
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGBUS: u8 = 7;
+const SIGSEGV: u8 = 11;
+
+// the numbers of the registers in the target description
+const PC: usize = 32;
+const CSR_BASE: usize = 65;
+
+// the number of instructions between the checks for an interrupt request
+const INTERRUPT_INTERVAL: u64 = 0x2000;
+
+// the instructions between the checkpoints at first, and the most checkpoints kept, beyond
+// which every other one is dropped and the interval doubles
+const CHECKPOINT_INTERVAL: u64 = 0x10_0000;
+const MAX_CHECKPOINTS: usize = 64;
+
+// A stream to the debugger, which can tell whether the debugger has requested an interrupt
+// while the program is running.
+pub trait Connection: Read + Write {
+    fn interrupted(&mut self) -> Result<bool>;
+}
+
+impl Connection for TcpStream {
+    fn interrupted(&mut self) -> Result<bool> {
+        self.set_nonblocking(true)?;
+        let interrupted = read_interrupt(self);
+        self.set_nonblocking(false)?;
+        interrupted
+    }
+}
+
+#[cfg(unix)]
+impl Connection for UnixStream {
+    fn interrupted(&mut self) -> Result<bool> {
+        self.set_nonblocking(true)?;
+        let interrupted = read_interrupt(self);
+        self.set_nonblocking(false)?;
+        interrupted
+    }
+}
+
+fn read_interrupt(stream: &mut impl Read) -> Result<bool> {
+    let mut byte = [0];
+    match stream.read(&mut byte) {
+        Ok(read) => Ok(read == 1 && byte[0] == INTERRUPT),
+        Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+// Debugs the program loaded into the emulator with GDB over the remote serial protocol until
+// the debugger detaches or kills the program, and returns how the run has come to an end unless
+// the program has been killed. Harts are the threads of the debugger. The debugger can also run
+// the program backwards, unless the inputs it takes from the host cannot be recorded, as for an
+// emulated kernel or semihosting.
+pub fn serve(
+    emulator: &mut Emulator,
+    connection: impl Connection,
+) -> std::result::Result<Option<RunOutcome>, EmulatorError> {
+    let harts = emulator.harts.len();
+    let unrecorded = replay::record(emulator).err().map(|e| e.to_string());
+    let mut stub = Stub {
+        emulator,
+        unrecorded,
+        connection,
+        acknowledge: true,
+        thread: 0,
+        software: HashSet::new(),
+        hardware: HashSet::new(),
+        pending: vec![None; harts],
+        skip: vec![false; harts],
+        stop: Stop::Signal(0, SIGTRAP),
+        position: 0,
+        checkpoints: Vec::new(),
+        interval: CHECKPOINT_INTERVAL,
+    };
+    stub.checkpoint();
+    stub.serve()
+}
+
+#[derive(Clone, Copy)]
+enum Stop {
+    Signal(usize, u8),
+    Breakpoint(usize, &'static str),
+    // the hart has run backwards to the beginning of the history
+    Beginning(usize),
+    Exited(u64),
+}
+
+// the machine at a position of the session, with the events of the history which have happened
+// and the exceptions pending then
+struct Checkpoint {
+    position: u64,
+    machine: Vec<u8>,
+    events: usize,
+    pending: Vec<Option<Exception>>,
+}
+
+struct Stub<'a, C> {
+    emulator: &'a mut Emulator,
+    // why the run cannot be recorded, which keeps the debugger from running it backwards
+    unrecorded: Option<String>,
+    connection: C,
+    acknowledge: bool,
+    // the hart the registers and memory are accessed through
+    thread: usize,
+    software: HashSet<u64>,
+    hardware: HashSet<u64>,
+    // the exceptions reported to the debugger, which the harts take when they resume
+    pending: Vec<Option<Exception>>,
+    // whether the hart resumes without stopping at a breakpoint on the first instruction
+    skip: Vec<bool>,
+    stop: Stop,
+    // the instructions the harts have executed in the session, including the ones which have
+    // raised an exception
+    position: u64,
+    checkpoints: Vec<Checkpoint>,
+    interval: u64,
+}
+
+impl<C: Connection> Stub<'_, C> {
+    fn serve(mut self) -> std::result::Result<Option<RunOutcome>, EmulatorError> {
+        while let Some(packet) = self.receive()? {
+            // the packet is decoded lossily, so its command may be any character
+            let (command, arguments) = match packet.chars().next() {
+                Some(c) => packet.split_at(c.len_utf8()),
+                None => ("", ""),
+            };
+            let reply = match command {
+                "?" => self.stop_reply(),
+                "q" => self.query(arguments),
+                "Q" if arguments == "StartNoAckMode" => {
+                    self.send("OK")?;
+                    self.acknowledge = false;
+                    continue;
+                }
+                "H" => match arguments.get(1..) {
+                    Some(arguments) => self.set_thread(arguments),
+                    None => "E01".to_string(),
+                },
+                "T" => match thread(arguments) {
+                    Some(_) => "OK".to_string(),
+                    None => "E01".to_string(),
+                },
+                "g" => self.read_registers(),
+                "p" => self.read_register(arguments),
+                "m" => self.read_memory(arguments),
+                "G" | "P" | "M" => {
+                    let reply = match command {
+                        "G" => self.write_registers(arguments),
+                        "P" => self.write_register(arguments),
+                        _ => self.write_memory(arguments),
+                    };
+                    if reply == "OK" {
+                        self.rewrite();
+                    }
+                    reply
+                }
+                "Z" | "z" => self.breakpoint(command == "Z", arguments),
+                "c" | "s" => {
+                    if let Some(address) = hex(arguments) {
+                        self.emulator.harts[self.thread].write_pc(address);
+                        self.rewrite();
+                    }
+                    self.stop = if command == "c" {
+                        self.resume()?
+                    } else {
+                        self.single_step()
+                    };
+                    self.stop_reply()
+                }
+                "b" if self.emulator.history.is_some() && matches!(arguments, "c" | "s") => {
+                    self.stop = if arguments == "c" {
+                        self.reverse_continue()?
+                    } else {
+                        self.reverse_step()?
+                    };
+                    self.stop_reply()
+                }
+                "b" if matches!(arguments, "c" | "s") => match &self.unrecorded {
+                    Some(reason) => format!("E.{}", reason),
+                    None => "E01".to_string(),
+                },
+                "D" => {
+                    self.send("OK")?;
+                    self.take_pending();
+                    return self.emulator.run().map(Some);
+                }
+                "k" => return Ok(None),
+                _ => String::new(),
+            };
+            self.send(&reply)?;
+            if let Stop::Exited(status) = self.stop {
+                return Ok(Some(RunOutcome::Exited(status)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn query(&mut self, arguments: &str) -> String {
+        if arguments.starts_with("Supported") {
+            let reverse = if self.emulator.history.is_some() {
+                ";ReverseStep+;ReverseContinue+"
+            } else {
+                ""
+            };
+            return format!(
+                "PacketSize={:x};qXfer:features:read+;swbreak+;hwbreak+;QStartNoAckMode+{}",
+                PACKET_SIZE, reverse
+            );
+        }
+        if let Some(annex) = arguments.strip_prefix("Xfer:features:read:target.xml:") {
+            let (offset, length) = match pair(annex, ',') {
+                Some(range) => range,
+                None => return "E01".to_string(),
+            };
+            let description = self.target_description();
+            let start = (offset as usize).min(description.len());
+            let end = (start + length as usize).min(description.len());
+            let kind = if end == description.len() { "l" } else { "m" };
+            return format!("{}{}", kind, &description[start..end]);
+        }
+        match arguments {
+            "Attached" => "1".to_string(),
+            "C" => format!("QC{:x}", self.thread + 1),
+            "fThreadInfo" => {
+                let threads = (1..=self.emulator.harts.len())
+                    .map(|thread| format!("{:x}", thread))
+                    .collect::<Vec<_>>();
+                format!("m{}", threads.join(","))
+            }
+            "sThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    // the integer registers, the pc and the control and status registers the harts implement
+    fn target_description(&self) -> String {
+        let mut description = String::from(
+            "<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+             <target version=\"1.0\"><architecture>riscv:rv64</architecture>\
+             <feature name=\"org.gnu.gdb.riscv.cpu\">",
+        );
+        for (regnum, name) in ABI_NAMES.iter().enumerate() {
+            let kind = match *name {
+                "ra" => "code_ptr",
+                "sp" | "gp" | "tp" => "data_ptr",
+                _ => "int",
+            };
+            description.push_str(&format!(
+                "<reg name=\"{}\" bitsize=\"64\" type=\"{}\" regnum=\"{}\"/>",
+                name, kind, regnum
+            ));
+        }
+        description.push_str(&format!(
+            "<reg name=\"pc\" bitsize=\"64\" type=\"code_ptr\" regnum=\"{}\"/>\
+             </feature><feature name=\"org.gnu.gdb.riscv.csr\">",
+            PC
+        ));
+        for address in self.emulator.harts[0].csr_addresses() {
+            let name = csr::name(address).unwrap_or_else(|| format!("csr{:x}", address));
+            description.push_str(&format!(
+                "<reg name=\"{}\" bitsize=\"64\" type=\"int\" regnum=\"{}\" group=\"csr\"/>",
+                name,
+                CSR_BASE + address as usize
+            ));
+        }
+        description.push_str("</feature></target>");
+        description
+    }
+
+    fn set_thread(&mut self, arguments: &str) -> String {
+        // -1 and 0 stand for any thread
+        if arguments == "-1" || arguments == "0" {
+            return "OK".to_string();
+        }
+        match thread(arguments).filter(|hartid| *hartid < self.emulator.harts.len()) {
+            Some(hartid) => {
+                self.thread = hartid;
+                "OK".to_string()
+            }
+            None => "E01".to_string(),
+        }
+    }
+
+    fn read_registers(&mut self) -> String {
+        (0..=PC)
+            .map(|regnum| self.register(regnum).map(encode).unwrap_or_default())
+            .collect()
+    }
+
+    fn write_registers(&mut self, arguments: &str) -> String {
+        for (regnum, value) in arguments.as_bytes().chunks(16).enumerate().take(PC + 1) {
+            match std::str::from_utf8(value).ok().and_then(decode) {
+                Some(value) => self.set_register(regnum, value),
+                None => return "E01".to_string(),
+            };
+        }
+        "OK".to_string()
+    }
+
+    fn read_register(&mut self, arguments: &str) -> String {
+        match hex(arguments).and_then(|regnum| self.register(regnum as usize)) {
+            Some(value) => encode(value),
+            None => "E01".to_string(),
+        }
+    }
+
+    fn write_register(&mut self, arguments: &str) -> String {
+        let (regnum, value) = match arguments.split_once('=') {
+            Some((regnum, value)) => (hex(regnum), decode(value)),
+            None => return "E01".to_string(),
+        };
+        match (regnum, value) {
+            (Some(regnum), Some(value)) if self.set_register(regnum as usize, value) => {
+                "OK".to_string()
+            }
+            _ => "E01".to_string(),
+        }
+    }
+
+    fn register(&mut self, regnum: usize) -> Option<u64> {
+        let hart = &mut self.emulator.harts[self.thread];
+        match regnum {
+            0..=31 => Some(hart.read_register(regnum)),
+            PC => Some(hart.read_pc()),
+            _ if regnum >= CSR_BASE => hart.read_csr((regnum - CSR_BASE) as u64),
+            _ => None,
+        }
+    }
+
+    fn set_register(&mut self, regnum: usize, value: u64) -> bool {
+        let hart = &mut self.emulator.harts[self.thread];
+        match regnum {
+            0..=31 => hart.registers().writeu(regnum, value),
+            PC => hart.write_pc(value),
+            _ if regnum >= CSR_BASE => return hart.write_csr((regnum - CSR_BASE) as u64, value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn read_memory(&mut self, arguments: &str) -> String {
+        match pair(arguments, ',')
+            .map(|(address, length)| read_bytes(&self.emulator.bus, address, length))
+        {
+            Some(Ok(bytes)) => bytes.iter().map(|byte| format!("{:02x}", byte)).collect(),
+            _ => "E14".to_string(),
+        }
+    }
+
+    fn write_memory(&mut self, arguments: &str) -> String {
+        let (range, data) = match arguments.split_once(':') {
+            Some(parts) => parts,
+            None => return "E01".to_string(),
+        };
+        let bytes = (0..data.len() / 2)
+            .map(|i| u8::from_str_radix(&data[i * 2..i * 2 + 2], 16).ok())
+            .collect::<Option<Vec<_>>>();
+        match (pair(range, ','), bytes) {
+            (Some((address, _)), Some(bytes))
+                if write_bytes(&mut self.emulator.bus, address, &bytes).is_ok() =>
+            {
+                "OK".to_string()
+            }
+            _ => "E14".to_string(),
+        }
+    }
+
+    // Z0 and Z1 insert software and hardware breakpoints, z0 and z1 remove them. Software
+    // breakpoints are kept out of the memory of the program like the hardware ones.
+    fn breakpoint(&mut self, insert: bool, arguments: &str) -> String {
+        let mut fields = arguments.split(',');
+        let breakpoints = match fields.next() {
+            Some("0") => &mut self.software,
+            Some("1") => &mut self.hardware,
+            _ => return String::new(),
+        };
+        match fields.next().and_then(hex) {
+            Some(address) => {
+                if insert {
+                    breakpoints.insert(address);
+                } else {
+                    breakpoints.remove(&address);
+                }
+                "OK".to_string()
+            }
+            None => "E01".to_string(),
+        }
+    }
+
+    // Runs the harts in turn as the emulator does until a hart stops at a breakpoint or an
+    // exception, the program exits or the debugger requests an interrupt.
+    fn resume(&mut self) -> Result<Stop> {
+        self.take_pending();
+        self.skip.iter_mut().for_each(|skip| *skip = true);
+        let mut executed = 0u64;
+        loop {
+            let hartid = self.emulator.turn();
+            if !std::mem::take(&mut self.skip[hartid]) {
+                if let Some(stop) = self.breakpoint_at(hartid) {
+                    return Ok(stop);
+                }
+            }
+            if let Some(stop) = self.advance() {
+                return Ok(stop);
+            }
+            executed += 1;
+            if executed.is_multiple_of(INTERRUPT_INTERVAL) && self.connection.interrupted()? {
+                return Ok(Stop::Signal(self.thread, SIGINT));
+            }
+        }
+    }
+
+    // Runs the harts in turn until the hart of the thread has executed an instruction.
+    fn single_step(&mut self) -> Stop {
+        self.take_pending();
+        let thread = self.thread;
+        loop {
+            let hartid = self.emulator.turn();
+            if let Some(stop) = self.advance() {
+                return stop;
+            }
+            if hartid == thread {
+                return Stop::Signal(thread, SIGTRAP);
+            }
+        }
+    }
+
+    fn breakpoint_at(&self, hartid: usize) -> Option<Stop> {
+        let pc = self.emulator.harts[hartid].read_pc();
+        if self.software.contains(&pc) {
+            return Some(Stop::Breakpoint(hartid, "swbreak"));
+        }
+        if self.hardware.contains(&pc) {
+            return Some(Stop::Breakpoint(hartid, "hwbreak"));
+        }
+        None
+    }
+
+    // Executes an instruction of the hart whose turn it is, which is the step the history of the
+    // session goes by, and returns the stop of an exception reported to the debugger or of the
+    // exit of the program.
+    fn advance(&mut self) -> Option<Stop> {
+        let hartid = self.emulator.turn();
+        if let Some(status) = self.emulator.exit_code {
+            return Some(Stop::Exited(status));
+        }
+        if self.position.is_multiple_of(self.interval) {
+            self.checkpoint();
+        }
+        self.position += 1;
+        self.emulator.turn.executed += 1;
+        if let Err(exception) = self.emulator.harts[hartid].step(&mut self.emulator.bus) {
+            // an exception ends the turn of the hart
+            self.emulator.turn.executed = self.emulator.quantum;
+            let exception = self.emulator.service(hartid, exception)?;
+            match signal(exception) {
+                Some(signal) => {
+                    self.pending[hartid] = Some(exception);
+                    return Some(Stop::Signal(hartid, signal));
+                }
+                None => self.emulator.harts[hartid].trap(exception, &self.emulator.bus),
+            }
+        }
+        None
+    }
+
+    // Runs backwards to the last instruction the hart of the thread has executed.
+    fn reverse_step(&mut self) -> Result<Stop> {
+        let thread = self.thread;
+        match self.last(|_, hartid| hartid == thread)? {
+            Some((position, _)) => {
+                self.travel(position)?;
+                Ok(Stop::Signal(thread, SIGTRAP))
+            }
+            None => self.rewind(),
+        }
+    }
+
+    // Runs backwards to the last time a hart has stopped at a breakpoint.
+    fn reverse_continue(&mut self) -> Result<Stop> {
+        match self.last(|stub, hartid| stub.breakpoint_at(hartid).is_some())? {
+            Some((position, hartid)) => {
+                self.travel(position)?;
+                Ok(self
+                    .breakpoint_at(hartid)
+                    .unwrap_or(Stop::Signal(hartid, SIGTRAP)))
+            }
+            None => self.rewind(),
+        }
+    }
+
+    fn rewind(&mut self) -> Result<Stop> {
+        let beginning = self
+            .checkpoints
+            .first()
+            .map_or(0, |checkpoint| checkpoint.position);
+        self.travel(beginning)?;
+        Ok(Stop::Beginning(self.thread))
+    }
+
+    // The last position before the current one at which the hart whose turn it is meets the
+    // condition, found by executing the instructions from the checkpoints again, the latest
+    // first.
+    fn last(&mut self, condition: impl Fn(&Self, usize) -> bool) -> Result<Option<(u64, usize)>> {
+        let end = self.position;
+        for index in (0..self.checkpoints.len()).rev() {
+            let start = self.checkpoints[index].position;
+            if start >= end {
+                continue;
+            }
+            self.travel(start)?;
+            let mut last = None;
+            while self.position < end {
+                let hartid = self.emulator.turn();
+                if condition(self, hartid) {
+                    last = Some((self.position, hartid));
+                }
+                self.take_pending();
+                self.advance();
+            }
+            if last.is_some() {
+                return Ok(last);
+            }
+        }
+        Ok(None)
+    }
+
+    // Brings the machine to the position by restoring the last checkpoint before it and
+    // executing the instructions from there again, with the inputs from the host replayed.
+    fn travel(&mut self, position: u64) -> Result<()> {
+        let checkpoint = match self
+            .checkpoints
+            .iter()
+            .rev()
+            .find(|checkpoint| checkpoint.position <= position)
+        {
+            Some(checkpoint) => checkpoint,
+            None => return Ok(()),
+        };
+        snapshot::apply(self.emulator, &checkpoint.machine)?;
+        if let Some(history) = &mut self.emulator.history {
+            history.rewind(checkpoint.events);
+        }
+        self.pending = checkpoint.pending.clone();
+        self.position = checkpoint.position;
+        while self.position < position {
+            self.take_pending();
+            self.advance();
+        }
+        Ok(())
+    }
+
+    // Saves the machine to go back to, unless it has been saved at the position.
+    fn checkpoint(&mut self) {
+        let events = match &self.emulator.history {
+            Some(history) => history.events(),
+            None => return,
+        };
+        if self
+            .checkpoints
+            .last()
+            .is_some_and(|checkpoint| checkpoint.position >= self.position)
+        {
+            return;
+        }
+        self.checkpoints.push(Checkpoint {
+            position: self.position,
+            machine: snapshot::capture(self.emulator),
+            events,
+            pending: self.pending.clone(),
+        });
+        if self.checkpoints.len() > MAX_CHECKPOINTS {
+            let mut index = 0;
+            self.checkpoints.retain(|_| {
+                index += 1;
+                index % 2 == 1
+            });
+            self.interval *= 2;
+        }
+    }
+
+    // The debugger has changed the machine, which the history cannot bring about, so the
+    // history begins again from here.
+    fn rewrite(&mut self) {
+        if let Some(history) = &mut self.emulator.history {
+            history.truncate();
+        }
+        self.checkpoints.clear();
+        self.checkpoint();
+    }
+
+    // the harts take the exceptions reported to the debugger as traps
+    fn take_pending(&mut self) {
+        for (hartid, pending) in self.pending.iter_mut().enumerate() {
+            if let Some(exception) = pending.take() {
+                self.emulator.harts[hartid].trap(exception, &self.emulator.bus);
+            }
+        }
+    }
+
+    fn stop_reply(&mut self) -> String {
+        match self.stop {
+            Stop::Signal(hartid, signal) => {
+                self.thread = hartid;
+                format!("T{:02x}thread:{:x};", signal, hartid + 1)
+            }
+            Stop::Breakpoint(hartid, kind) => {
+                self.thread = hartid;
+                format!("T{:02x}thread:{:x};{}:;", SIGTRAP, hartid + 1, kind)
+            }
+            Stop::Beginning(hartid) => {
+                format!("T{:02x}thread:{:x};replaylog:begin;", SIGTRAP, hartid + 1)
+            }
+            Stop::Exited(status) => format!("W{:02x}", status as u8),
+        }
+    }
+
+    // Reads the next packet, skipping acknowledgments and interrupt requests received while
+    // the program is stopped. Returns None when the debugger has closed the connection.
+    fn receive(&mut self) -> Result<Option<String>> {
+        loop {
+            let mut byte = [0];
+            loop {
+                if self.connection.read(&mut byte)? == 0 {
+                    return Ok(None);
+                }
+                if byte[0] == b'$' {
+                    break;
+                }
+            }
+            let mut data = Vec::new();
+            loop {
+                if self.connection.read(&mut byte)? == 0 {
+                    return Ok(None);
+                }
+                if byte[0] == b'#' {
+                    break;
+                }
+                data.push(byte[0]);
+            }
+            let mut checksum = [0; 2];
+            self.connection.read_exact(&mut checksum)?;
+            let valid = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|checksum| u8::from_str_radix(checksum, 16).ok())
+                == Some(checksum_of(&data));
+            if self.acknowledge {
+                self.connection.write_all(if valid { b"+" } else { b"-" })?;
+            }
+            if valid && !data.is_empty() {
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+        }
+    }
+
+    fn send(&mut self, data: &str) -> Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        self.connection.write_all(packet.as_bytes())?;
+        self.connection.flush()
+    }
+}
+
+fn signal(exception: Exception) -> Option<u8> {
+    match exception {
+        Exception::Breakpoint => Some(SIGTRAP),
+        Exception::IllegalInstruction => Some(SIGILL),
+        Exception::InstructionAddressMisaligned
+        | Exception::LoadAddressMisaligned
+        | Exception::StoreAddressMisaligned => Some(SIGBUS),
+        Exception::InstructionAccessFault
+        | Exception::LoadAccessFault
+        | Exception::StoreAccessFault
+        | Exception::InstructionPageFault
+        | Exception::LoadPageFault
+        | Exception::StorePageFault => Some(SIGSEGV),
+        // the environment calls are how the program asks for its trap handler
+        _ => None,
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn hex(text: &str) -> Option<u64> {
+    u64::from_str_radix(text, 16).ok()
+}
+
+fn pair(text: &str, separator: char) -> Option<(u64, u64)> {
+    let (first, second) = text.split_once(separator)?;
+    Some((hex(first)?, hex(second)?))
+}
+
+// a thread id of the debugger, which counts the harts from one
+fn thread(text: &str) -> Option<usize> {
+    hex(text)
+        .filter(|thread| *thread > 0)
+        .map(|thread| thread as usize - 1)
+}
+
+// registers are sent in the byte order of the target
+fn encode(value: u64) -> String {
+    value
+        .to_le_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn decode(text: &str) -> Option<u64> {
+    if text.len() != 16 {
+        return None;
+    }
+    let mut bytes = [0; 8];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(text.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::{memory::MEMORY_BASE_ADDRESS, tests::loaded};
+    use std::io::Cursor;
+
+    struct Debugger {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Debugger {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Debugger {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Connection for &mut Debugger {
+        fn interrupted(&mut self) -> Result<bool> {
+            Ok(false)
+        }
+    }
+
+    fn packets(commands: &[&str]) -> Vec<u8> {
+        commands
+            .iter()
+            .flat_map(|command| {
+                format!("${}#{:02x}", command, checksum_of(command.as_bytes())).into_bytes()
+            })
+            .collect()
+    }
+
+    // Serves the packets of the commands, which end the session, and returns the replies.
+    fn replies(emulator: &mut Emulator, commands: &[&str]) -> Vec<String> {
+        let mut debugger = Debugger {
+            input: Cursor::new(packets(commands)),
+            output: Vec::new(),
+        };
+        assert_eq!(serve(emulator, &mut debugger).unwrap(), None);
+        let output = String::from_utf8(debugger.output).unwrap();
+        output
+            .trim_start_matches('+')
+            .split('$')
+            .skip(1)
+            .map(|packet| packet.split('#').next().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn serve_ok() {
+        // addi a0, zero, 5; addi a0, a0, 1; ebreak
+        let program = [0x00500513, 0x00150513, 0x00100073];
+        let mut emulator = loaded(&program, &[]);
+
+        let entry = MEMORY_BASE_ADDRESS + 0x78;
+        let replies = replies(
+            &mut emulator,
+            &[
+                "QStartNoAckMode",
+                "?",
+                &format!("Z0,{:x},4", entry + 4),
+                "c",
+                "pa",
+                "s",
+                "pa",
+                &format!("m{:x},4", entry),
+                "c",
+                "P20=0000000000000000",
+                "k",
+            ],
+        );
+        assert_eq!(
+            replies,
+            [
+                "OK",
+                "T05thread:1;",
+                "OK",
+                "T05thread:1;swbreak:;",
+                "0500000000000000",
+                "T05thread:1;",
+                "0600000000000000",
+                "13055000",
+                // the ebreak of the program
+                "T05thread:1;",
+                "OK",
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_ok() {
+        let program = [0x00100073];
+        let mut emulator = loaded(&program, &[]);
+        assert_eq!(
+            replies(
+                &mut emulator,
+                &["QStartNoAckMode", "\u{e9}1", "", "H", "H\u{e9}", "k"]
+            ),
+            ["OK", "", "E01", "E01"]
+        );
+
+        // a run which cannot be recorded is not run backwards, for the reason given
+        let mut emulator = loaded(&program, &[]);
+        emulator.enable_semihosting("");
+        let error = "E.a program serviced by an emulated kernel or semihosting cannot be saved";
+        assert_eq!(
+            replies(&mut emulator, &["QStartNoAckMode", "bc", "bs", "k"]),
+            ["OK", error, error]
+        );
+    }
+
+    #[test]
+    fn reverse_ok() {
+        // addi a0, zero, 5; addi a0, a0, 1; addi a0, a0, 1; ebreak
+        let program = [0x00500513, 0x00150513, 0x00150513, 0x00100073];
+        let mut emulator = loaded(&program, &[("tohost", 0x80000100)]);
+
+        let entry = MEMORY_BASE_ADDRESS + 0x78;
+        let replies = replies(
+            &mut emulator,
+            &[
+                "QStartNoAckMode",
+                "qSupported",
+                &format!("Z0,{:x},4", entry + 4),
+                "c",
+                "s",
+                "s",
+                "pa",
+                "bs",
+                "pa",
+                "bc",
+                "pa",
+                "bc",
+                "pa",
+                "c",
+                "k",
+            ],
+        );
+        assert_eq!(
+            replies,
+            [
+                "OK",
+                "PacketSize=4000;qXfer:features:read+;swbreak+;hwbreak+;QStartNoAckMode+;\
+                 ReverseStep+;ReverseContinue+",
+                "OK",
+                "T05thread:1;swbreak:;",
+                "T05thread:1;",
+                "T05thread:1;",
+                "0700000000000000",
+                "T05thread:1;",
+                "0600000000000000",
+                "T05thread:1;swbreak:;",
+                "0500000000000000",
+                "T05thread:1;replaylog:begin;",
+                "0000000000000000",
+                // forwards again from the beginning
+                "T05thread:1;swbreak:;",
+            ]
+        );
+    }
+}
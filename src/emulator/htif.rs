@@ -0,0 +1,115 @@
+use crate::emulator::{
+    bus::SystemBus,
+    snapshot::{Reader, Snapshot, Writer},
+    syscall::newlib::Newlib,
+};
+use std::io::{stdout, Result, Write};
+
+const DEVICE_SYSCALL: u64 = 0;
+const DEVICE_CONSOLE: u64 = 1;
+const COMMAND_SYSCALL: u64 = 0;
+const COMMAND_PUTCHAR: u64 = 1;
+const PAYLOAD_MASK: u64 = (1 << 48) - 1;
+
+// Host-Target Interface of Spike, through which a bare-metal program reaches the host by
+// writing commands to the `tohost` symbol: the exit status, a pointer to the magic memory of a
+// system call, or a character for the console. The host acknowledges a command in `fromhost`.
+pub struct Htif {
+    tohost: u64,
+    fromhost: Option<u64>,
+    newlib: Newlib,
+}
+
+impl Htif {
+    pub fn new(tohost: u64, fromhost: Option<u64>, newlib: Newlib) -> Self {
+        Self {
+            tohost,
+            fromhost,
+            newlib,
+        }
+    }
+
+    pub fn tohost(&self) -> u64 {
+        self.tohost
+    }
+
+    pub fn fromhost(&self) -> Option<u64> {
+        self.fromhost
+    }
+
+    // whether the program has written a command to tohost
+    pub fn pending(&self, bus: &SystemBus) -> bool {
+        bus.load64(self.tohost) != 0
+    }
+
+    // Handles the command the program has written to tohost, if any, and returns the exit
+    // status when the program has finished.
+    pub fn poll(&mut self, bus: &mut SystemBus) -> Option<u64> {
+        let command = bus.load64(self.tohost);
+        if command == 0 {
+            return None;
+        }
+        bus.store64(self.tohost, 0);
+        let device = command >> 56;
+        let cmd = (command >> 48) & 0xff;
+        let payload = command & PAYLOAD_MASK;
+        match (device, cmd) {
+            (DEVICE_SYSCALL, COMMAND_SYSCALL) if payload & 1 == 1 => return Some(payload >> 1),
+            (DEVICE_SYSCALL, COMMAND_SYSCALL) => {
+                if let Some(code) = self.newlib.magic_mem(payload, bus) {
+                    return Some(code);
+                }
+            }
+            (DEVICE_CONSOLE, COMMAND_PUTCHAR) => {
+                let mut out = stdout();
+                let _ = out.write_all(&[payload as u8]);
+                let _ = out.flush();
+            }
+            _ => {}
+        }
+        if let Some(fromhost) = self.fromhost {
+            bus.store64(fromhost, (device << 56) | (cmd << 48) | 1);
+        }
+        None
+    }
+}
+
+impl Snapshot for Htif {
+    fn save(&mut self, snapshot: &mut Writer) {
+        snapshot.u64(self.tohost);
+        snapshot.option(self.fromhost);
+        self.newlib.save(snapshot);
+    }
+
+    fn restore(&mut self, snapshot: &mut Reader) -> Result<()> {
+        self.tohost = snapshot.u64()?;
+        self.fromhost = snapshot.option()?;
+        self.newlib.restore(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::memory::{Memory, MEMORY_BASE_ADDRESS};
+
+    #[test]
+    fn poll_ok() {
+        let mut bus = SystemBus::new(1, Memory::default());
+        let tohost = MEMORY_BASE_ADDRESS + 0x1000;
+        let fromhost = tohost + 8;
+        let mut htif = Htif::new(tohost, Some(fromhost), Newlib::new(0, 0));
+        assert_eq!(htif.poll(&mut bus), None);
+        // an unknown system call through the magic memory fails with ENOSYS
+        let magic_mem = MEMORY_BASE_ADDRESS + 0x2000;
+        bus.store64(magic_mem, 0xffff);
+        bus.store64(tohost, magic_mem);
+        assert_eq!(htif.poll(&mut bus), None);
+        assert_eq!(bus.load64(magic_mem) as i64, -38);
+        assert_eq!(bus.load64(tohost), 0);
+        assert_eq!(bus.load64(fromhost), 1);
+        // the exit status is shifted by one with the lowest bit set
+        bus.store64(tohost, 3 << 1 | 1);
+        assert_eq!(htif.poll(&mut bus), Some(3));
+    }
+}
@@ -0,0 +1,383 @@
+use crate::emulator::{
+    cpu::{
+        mode::PrivilegeMode,
+        trace::{Access, Commit, Tracer},
+        x::ABI_NAMES,
+    },
+    Emulator, EmulatorError, RunOutcome, Step,
+};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{BufRead, Result};
+use std::rc::Rc;
+
+// The result of running the program in lockstep with a reference commit log.
+pub enum Verdict {
+    // every instruction both have retired has matched, until the program has finished or the
+    // reference has ended, in which case there is no outcome
+    Matched {
+        instructions: u64,
+        outcome: Option<RunOutcome>,
+    },
+    Mismatched(Box<Mismatch>),
+}
+
+// The first instruction on which the emulator has diverged from the reference.
+pub struct Mismatch {
+    pub hartid: usize,
+    // the number of the instructions of the hart which have matched before
+    pub index: u64,
+    pub reason: String,
+    pub expected: Commit,
+    pub actual: Commit,
+    pub registers: [u64; 32],
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "hart {} diverged at instruction {}: {}",
+            self.hartid, self.index, self.reason
+        )?;
+        writeln!(f, "expected: {}", self.expected)?;
+        writeln!(f, "actual:   {}", self.actual)?;
+        writeln!(f, "          {}", self.actual.disassembly())?;
+        for (i, chunk) in self.registers.chunks(4).enumerate() {
+            let row = chunk
+                .iter()
+                .enumerate()
+                .map(|(j, value)| format!("{:>4} {:#018x}", ABI_NAMES[i * 4 + j], value))
+                .collect::<Vec<_>>();
+            writeln!(f, "{}", row.join("  "))?;
+        }
+        Ok(())
+    }
+}
+
+// keeps the last instruction the hart has retired, passing everything to the tracer attached
+// before
+struct Recorder {
+    commit: Option<Commit>,
+    inner: Option<Rc<RefCell<dyn Tracer>>>,
+}
+
+impl Tracer for Recorder {
+    fn commit(&mut self, commit: &Commit) {
+        self.commit = Some(commit.clone());
+        if let Some(inner) = &self.inner {
+            inner.borrow_mut().commit(commit);
+        }
+    }
+
+    fn trap(&mut self, hartid: usize, cause: u64, epc: u64, tval: u64) {
+        if let Some(inner) = &self.inner {
+            inner.borrow_mut().trap(hartid, cause, epc, tval);
+        }
+    }
+}
+
+// the commits of the reference, read as the harts need them
+struct Reference<R> {
+    lines: R,
+    // the commits read ahead for each hart
+    pending: Vec<VecDeque<Expected>>,
+    // whether the commits of each hart have been aligned with the first instruction
+    aligned: Vec<bool>,
+}
+
+struct Expected {
+    commit: Commit,
+    // the registers the reference shows, narrower for RV32
+    mask: u64,
+}
+
+impl<R: BufRead> Reference<R> {
+    fn next(&mut self, hartid: usize, pc: u64) -> Result<Option<Expected>> {
+        loop {
+            while let Some(expected) = self.pending[hartid].pop_front() {
+                // skips the instructions Spike executes in its boot ROM before the program
+                if self.aligned[hartid] || expected.commit.pc & expected.mask == pc & expected.mask
+                {
+                    self.aligned[hartid] = true;
+                    return Ok(Some(expected));
+                }
+            }
+            let mut line = String::new();
+            if self.lines.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            if let Some(expected) = parse(&line) {
+                if let Some(pending) = self.pending.get_mut(expected.commit.hartid) {
+                    pending.push_back(expected);
+                }
+            }
+        }
+    }
+}
+
+// Runs the program an instruction at a time, comparing the pc, the instruction, the registers it
+// writes and the memory it stores to with the commit log of Spike for each hart.
+pub fn run(
+    emulator: &mut Emulator,
+    reference: impl BufRead,
+) -> std::result::Result<Verdict, EmulatorError> {
+    let harts = emulator.harts.len();
+    let mut reference = Reference {
+        lines: reference,
+        pending: (0..harts).map(|_| VecDeque::new()).collect(),
+        aligned: vec![false; harts],
+    };
+    let recorders = emulator
+        .harts
+        .iter_mut()
+        .map(|hart| {
+            let recorder = Rc::new(RefCell::new(Recorder {
+                commit: None,
+                inner: None,
+            }));
+            recorder.borrow_mut().inner = hart.trace(Some(recorder.clone()));
+            recorder
+        })
+        .collect::<Vec<_>>();
+    let verdict = compare(emulator, &mut reference, &recorders);
+    for (hart, recorder) in emulator.harts.iter_mut().zip(recorders) {
+        let inner = recorder.borrow_mut().inner.take();
+        hart.trace(inner);
+    }
+    verdict
+}
+
+fn compare(
+    emulator: &mut Emulator,
+    reference: &mut Reference<impl BufRead>,
+    recorders: &[Rc<RefCell<Recorder>>],
+) -> std::result::Result<Verdict, EmulatorError> {
+    let mut retired = vec![0; emulator.harts.len()];
+    loop {
+        for hartid in 0..emulator.harts.len() {
+            for _ in 0..emulator.quantum {
+                let step = emulator.step(hartid)?;
+                if let Some(actual) = recorders[hartid].borrow_mut().commit.take() {
+                    let expected = match reference.next(hartid, actual.pc)? {
+                        Some(expected) => expected,
+                        None => {
+                            return Ok(Verdict::Matched {
+                                instructions: retired.iter().sum(),
+                                outcome: None,
+                            })
+                        }
+                    };
+                    if let Some(reason) = difference(&expected, &actual) {
+                        let hart = &emulator.harts[hartid];
+                        let mut registers = [0; 32];
+                        for (register, value) in registers.iter_mut().enumerate() {
+                            *value = hart.read_register(register);
+                        }
+                        return Ok(Verdict::Mismatched(Box::new(Mismatch {
+                            hartid,
+                            index: retired[hartid],
+                            reason,
+                            expected: expected.commit,
+                            actual,
+                            registers,
+                        })));
+                    }
+                    retired[hartid] += 1;
+                }
+                let outcome = match step {
+                    Step::Exited(status) => RunOutcome::Exited(status),
+                    Step::Breakpoint => RunOutcome::Breakpoint {
+                        hartid,
+                        pc: emulator.harts[hartid].read_pc(),
+                    },
+                    Step::Retired | Step::Trapped(_) => continue,
+                };
+                return Ok(Verdict::Matched {
+                    instructions: retired.iter().sum(),
+                    outcome: Some(outcome),
+                });
+            }
+        }
+    }
+}
+
+// what the emulator has done differently from the reference, if anything
+fn difference(expected: &Expected, actual: &Commit) -> Option<String> {
+    let mask = expected.mask;
+    let expected = &expected.commit;
+    if expected.pc & mask != actual.pc & mask {
+        return Some(format!(
+            "pc {:#x} instead of {:#x}",
+            actual.pc & mask,
+            expected.pc
+        ));
+    }
+    if expected.instruction != actual.instruction {
+        return Some(format!(
+            "instruction {:#010x} instead of {:#010x}",
+            actual.instruction, expected.instruction
+        ));
+    }
+    let registers = |commit: &Commit| {
+        commit
+            .registers
+            .iter()
+            .map(|(register, value)| format!("x{} {:#x}", register, value & mask))
+            .collect::<Vec<_>>()
+    };
+    if registers(expected) != registers(actual) {
+        return Some(format!(
+            "wrote {} instead of {}",
+            list(registers(actual)),
+            list(registers(expected))
+        ));
+    }
+    let stores = |commit: &Commit| {
+        commit
+            .accesses
+            .iter()
+            .filter_map(|access| match *access {
+                Access::Store {
+                    address,
+                    value,
+                    size,
+                } => Some(format!(
+                    "{} bytes {:#x} at {:#x}",
+                    size,
+                    value,
+                    address & mask
+                )),
+                Access::Load { .. } => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    if stores(expected) != stores(actual) {
+        return Some(format!(
+            "stored {} instead of {}",
+            list(stores(actual)),
+            list(stores(expected))
+        ));
+    }
+    None
+}
+
+fn list(items: Vec<String>) -> String {
+    if items.is_empty() {
+        "nothing".to_string()
+    } else {
+        items.join(", ")
+    }
+}
+
+// Parses a line of the commit log of Spike, ignoring the other lines such as the disassembly
+// and the traps:
+// core   0: 3 0x0000000080000000 (0x00000297) x5  0x0000000080000000 mem 0x0000000080001000 0x01
+fn parse(line: &str) -> Option<Expected> {
+    let mut tokens = line.split_whitespace().peekable();
+    if tokens.next()? != "core" {
+        return None;
+    }
+    let hartid = tokens.next()?.strip_suffix(':')?.parse().ok()?;
+    let mode = tokens.next()?;
+    if mode.len() != 1 {
+        return None;
+    }
+    let mode = PrivilegeMode::from(mode.parse::<u64>().ok()?);
+    let pc = tokens.next()?;
+    let mask = if pc.len() <= 10 { 0xffff_ffff } else { !0 };
+    let pc = hex(pc)?;
+    let instruction = hex(tokens.next()?.strip_prefix('(')?.strip_suffix(')')?)? as u32;
+    let mut commit = Commit {
+        hartid,
+        mode,
+        pc,
+        instruction,
+        registers: vec![],
+        csrs: vec![],
+        accesses: vec![],
+    };
+    while let Some(key) = tokens.next() {
+        if key == "mem" {
+            let address = hex(tokens.next()?)?;
+            // a store shows the value in as many digits as its size
+            match tokens.peek().filter(|value| value.starts_with("0x")) {
+                Some(value) => {
+                    let size = (value.len() as u64 - 2) / 2;
+                    let value = hex(value)?;
+                    tokens.next();
+                    commit.accesses.push(Access::Store {
+                        address,
+                        value,
+                        size,
+                    });
+                }
+                None => commit.accesses.push(Access::Load { address }),
+            }
+        } else if let Some(register) = key.strip_prefix('x') {
+            commit
+                .registers
+                .push((register.parse().ok()?, hex(tokens.next()?)?));
+        } else if let Some(csr) = key.strip_prefix('c') {
+            let (address, _) = csr.split_once('_')?;
+            commit
+                .csrs
+                .push((address.parse().ok()?, hex(tokens.next()?)?));
+        } else {
+            // the registers of the extensions five does not implement
+            tokens.next();
+        }
+    }
+    Some(Expected { commit, mask })
+}
+
+fn hex(text: &str) -> Option<u64> {
+    u64::from_str_radix(text.strip_prefix("0x")?, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::{memory::MEMORY_BASE_ADDRESS, tests::loaded};
+    use std::io::Cursor;
+
+    fn load() -> Emulator {
+        // li a0, 5; addi a0, a0, 1; sd a0, 8(sp)
+        let program = [0x00500513, 0x00150513, 0x00a13423];
+        let mut emulator = loaded(&program, &[]);
+        emulator.harts[0].registers().writeu(2, MEMORY_BASE_ADDRESS);
+        emulator
+    }
+
+    #[test]
+    fn run_ok() {
+        // the boot ROM of Spike comes first
+        let reference = "\
+            core   0: 0x0000000000001000 (0x00000297) auipc   t0, 0x0\n\
+            core   0: 3 0x0000000000001000 (0x00000297) x5  0x0000000000001000\n\
+            core   0: 3 0x0000000080000078 (0x00500513) x10 0x0000000000000005\n\
+            core   0: 3 0x000000008000007c (0x00150513) x10 0x0000000000000006\n";
+        let mut emulator = load();
+        match run(&mut emulator, Cursor::new(reference)).unwrap() {
+            Verdict::Matched { instructions, .. } => assert_eq!(instructions, 2),
+            Verdict::Mismatched(mismatch) => panic!("{}", mismatch),
+        }
+
+        let reference = "\
+            core   0: 3 0x0000000080000078 (0x00500513) x10 0x0000000000000005\n\
+            core   0: 3 0x000000008000007c (0x00150513) x10 0x0000000000000006\n\
+            core   0: 3 0x0000000080000080 (0x00a13423) mem 0x0000000080000010 0x0000000000000006\n";
+        let mut emulator = load();
+        match run(&mut emulator, Cursor::new(reference)).unwrap() {
+            Verdict::Matched { .. } => panic!("the store should not match"),
+            Verdict::Mismatched(mismatch) => {
+                assert_eq!(mismatch.index, 2);
+                assert_eq!(
+                    mismatch.reason,
+                    "stored 8 bytes 0x6 at 0x80000008 instead of 8 bytes 0x6 at 0x80000010"
+                );
+            }
+        }
+    }
+}
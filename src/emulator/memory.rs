@@ -1,34 +1,268 @@
-use crate::emulator::bus::Size;
+use crate::emulator::{
+    bus::Size,
+    snapshot::{restore_pages, save_pages, Reader, Snapshot, Writer},
+};
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind, Result};
 
 pub const MEMORY_SIZE: u64 = 1024 * 1024 * 1024;
 pub const MEMORY_BASE_ADDRESS: u64 = 0x8000_0000;
+pub const PAGE_SIZE: u64 = 4096;
 
+type Page = [u8; PAGE_SIZE as usize];
+
+// the pages of the last code writes the memory keeps, by which a hart throws away only the
+// blocks of those pages
+const CODE_PAGES: usize = 64;
+
+// The memory, whose pages are allocated when they are first stored to, so that what has never
+// been written costs nothing and reads as zero.
 pub struct Memory {
-    pub memory: Vec<u8>,
+    base: u64,
+    size: u64,
+    pages: Vec<Option<Box<Page>>>,
+    // a bit for each page which holds instructions the harts have decoded into blocks
+    code: Vec<u64>,
+    // the number of stores to the pages which hold decoded instructions
+    code_writes: u64,
+    // the pages the last code writes have hit, the latest last
+    code_pages: VecDeque<u64>,
 }
 
 impl Default for Memory {
     fn default() -> Self {
-        Self {
-            memory: vec![0; MEMORY_SIZE as usize],
-        }
+        Self::new(MEMORY_BASE_ADDRESS, MEMORY_SIZE)
     }
 }
 
 impl Memory {
+    pub fn new(base: u64, size: u64) -> Self {
+        Self {
+            base,
+            size,
+            pages: vec![None; size.div_ceil(PAGE_SIZE) as usize],
+            code: Vec::new(),
+            code_writes: 0,
+            code_pages: VecDeque::new(),
+        }
+    }
+
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
     pub fn size(&self) -> u64 {
-        MEMORY_BASE_ADDRESS + MEMORY_SIZE
+        self.size
+    }
+
+    pub fn end(&self) -> u64 {
+        self.base + self.size
+    }
+
+    pub fn contains(&self, address: u64) -> bool {
+        (self.base..self.end()).contains(&address)
     }
 
     pub fn load(&self, address: u64, size: Size) -> u64 {
-        (0..size as usize).fold(0, |acc, i| {
-            acc | (self.memory[(address - MEMORY_BASE_ADDRESS) as usize + i] as u64) << (8 * i)
-        })
+        let offset = address - self.base;
+        let within = (offset % PAGE_SIZE) as usize;
+        if within + size as usize > PAGE_SIZE as usize {
+            // the access straddles two pages
+            return (0..size as u64).fold(0, |value, i| {
+                value | self.load(address + i, Size::Byte) << (8 * i)
+            });
+        }
+        let page = match &self.pages[(offset / PAGE_SIZE) as usize] {
+            Some(page) => page,
+            None => return 0,
+        };
+        let bytes = &page[within..];
+        match size {
+            Size::Byte => bytes[0] as u64,
+            Size::Halfword => u16::from_le_bytes([bytes[0], bytes[1]]) as u64,
+            Size::Word => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as u64,
+            Size::Doubleword => u64::from_le_bytes([
+                bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+            ]),
+        }
     }
 
     pub fn store(&mut self, address: u64, value: u64, size: Size) {
-        for i in 0..size as usize {
-            self.memory[(address - MEMORY_BASE_ADDRESS) as usize + i] = (value >> (i * 8)) as u8;
+        let offset = address - self.base;
+        let within = (offset % PAGE_SIZE) as usize;
+        if within + size as usize > PAGE_SIZE as usize {
+            // the access straddles two pages
+            for i in 0..size as u64 {
+                self.store(address + i, value >> (8 * i), Size::Byte);
+            }
+            return;
         }
+        let page = offset / PAGE_SIZE;
+        let entry = &mut self.pages[page as usize];
+        // zero is what a page which has not been allocated holds already
+        if entry.is_none() && value & mask(size) == 0 {
+            return;
+        }
+        let bytes = entry.get_or_insert_with(|| Box::new([0; PAGE_SIZE as usize]));
+        bytes[within..within + size as usize]
+            .copy_from_slice(&value.to_le_bytes()[..size as usize]);
+        self.written(page);
+    }
+
+    // Reads the bytes from the address a page at a time.
+    pub fn load_bytes(&self, address: u64, bytes: &mut [u8]) {
+        let mut start = 0;
+        let mut offset = address - self.base;
+        while start < bytes.len() {
+            let (page, within) = (offset / PAGE_SIZE, (offset % PAGE_SIZE) as usize);
+            let length = (PAGE_SIZE as usize - within).min(bytes.len() - start);
+            let target = &mut bytes[start..start + length];
+            match &self.pages[page as usize] {
+                Some(source) => target.copy_from_slice(&source[within..within + length]),
+                None => target.fill(0),
+            }
+            start += length;
+            offset += length as u64;
+        }
+    }
+
+    // Writes the bytes from the address a page at a time, leaving a page which has not been
+    // allocated alone where the bytes are zero.
+    pub fn store_bytes(&mut self, address: u64, bytes: &[u8]) {
+        let mut start = 0;
+        let mut offset = address - self.base;
+        while start < bytes.len() {
+            let (page, within) = (offset / PAGE_SIZE, (offset % PAGE_SIZE) as usize);
+            let length = (PAGE_SIZE as usize - within).min(bytes.len() - start);
+            let source = &bytes[start..start + length];
+            let entry = &mut self.pages[page as usize];
+            if entry.is_some() || source.iter().any(|&byte| byte != 0) {
+                let target = entry.get_or_insert_with(|| Box::new([0; PAGE_SIZE as usize]));
+                target[within..within + length].copy_from_slice(source);
+                self.written(page);
+            }
+            start += length;
+            offset += length as u64;
+        }
+    }
+
+    // Marks the page of the address as holding instructions a hart has decoded, so that a
+    // store to it is counted in the code writes.
+    pub fn decoded(&mut self, address: u64) {
+        let page = (address - self.base) / PAGE_SIZE;
+        let index = page as usize / 64;
+        if index >= self.code.len() {
+            self.code.resize(index + 1, 0);
+        }
+        self.code[index] |= 1 << (page % 64);
+    }
+
+    // the number of stores to pages holding decoded instructions, which tells a hart its
+    // blocks may be stale
+    pub fn code_writes(&self) -> u64 {
+        self.code_writes
+    }
+
+    // The pages the code writes since the number given have hit, or None when the memory has
+    // not kept as many.
+    pub fn code_pages(&self, code_writes: u64) -> Option<Vec<u64>> {
+        let count = self.code_writes.checked_sub(code_writes)? as usize;
+        if count > self.code_pages.len() {
+            return None;
+        }
+        Some(self.code_pages.iter().rev().take(count).copied().collect())
+    }
+
+    // counts a store to the page in the code writes if the page holds decoded instructions
+    fn written(&mut self, page: u64) {
+        if let Some(bits) = self.code.get_mut(page as usize / 64) {
+            if *bits & (1 << (page % 64)) != 0 {
+                *bits &= !(1 << (page % 64));
+                self.code_writes += 1;
+                if self.code_pages.len() == CODE_PAGES {
+                    self.code_pages.pop_front();
+                }
+                self.code_pages.push_back(page);
+            }
+        }
+    }
+}
+
+fn mask(size: Size) -> u64 {
+    match size {
+        Size::Doubleword => u64::MAX,
+        _ => (1 << (8 * size as u64)) - 1,
+    }
+}
+
+impl Snapshot for Memory {
+    fn save(&mut self, snapshot: &mut Writer) {
+        snapshot.u64(self.base);
+        snapshot.u64(self.size);
+        let pages = self
+            .pages
+            .iter()
+            .enumerate()
+            .filter_map(|(index, page)| Some((index as u64, &page.as_deref()?[..])));
+        save_pages(pages, snapshot);
+    }
+
+    fn restore(&mut self, snapshot: &mut Reader) -> Result<()> {
+        // the memory is allocated as configured rather than as the snapshot says
+        if snapshot.u64()? != self.base || snapshot.u64()? != self.size {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "the snapshot has another memory",
+            ));
+        }
+        *self = Self::new(self.base, self.size);
+        restore_pages(snapshot, PAGE_SIZE as usize, |index, bytes| {
+            let page = self
+                .pages
+                .get_mut(index as usize)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, "a page is out of memory"))?;
+            let mut allocated = Box::new([0; PAGE_SIZE as usize]);
+            allocated.copy_from_slice(&bytes);
+            *page = Some(allocated);
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_ok() {
+        let mut memory = Memory::new(0x1000_0000, 0x10_0000);
+        let allocated = |memory: &Memory| memory.pages.iter().filter(|page| page.is_some()).count();
+        assert_eq!(memory.load(0x1000_0000, Size::Doubleword), 0);
+        memory.store(0x1000_0000, 0, Size::Doubleword);
+        assert_eq!(allocated(&memory), 0);
+        // a store across two pages
+        memory.store(0x1000_0ffc, 0x0123_4567_89ab_cdef, Size::Doubleword);
+        assert_eq!(allocated(&memory), 2);
+        assert_eq!(
+            memory.load(0x1000_0ffc, Size::Doubleword),
+            0x0123_4567_89ab_cdef
+        );
+        assert_eq!(memory.load(0x1000_1000, Size::Word), 0x0123_4567);
+        assert_eq!(memory.load(0x1000_0ffe, Size::Halfword), 0x89ab);
+        assert_eq!(memory.end(), 0x1010_0000);
+    }
+
+    #[test]
+    fn store_bytes_ok() {
+        let mut memory = Memory::new(0x1000_0000, 0x10_0000);
+        let bytes = (0..0x3000).map(|i| (i / 0x1000) as u8).collect::<Vec<_>>();
+        memory.store_bytes(0x1000_0800, &bytes);
+        // the first page of the bytes is zero, and is not allocated
+        assert!(memory.pages[0].is_none());
+        assert!(memory.pages[1].is_some());
+        let mut loaded = vec![0xff; 0x3000];
+        memory.load_bytes(0x1000_0800, &mut loaded);
+        assert_eq!(loaded, bytes);
+        assert_eq!(memory.load(0x1000_17fe, Size::Word), 0x0101_0000);
     }
 }
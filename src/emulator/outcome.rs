@@ -0,0 +1,104 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+// how a run has come to an end
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunOutcome {
+    // the program has exited with the status
+    Exited(u64),
+    // the harts have executed the instructions they were allowed to
+    BudgetExhausted,
+    // the run has taken as long as it was allowed to on the host
+    TimedOut,
+    // the host has asked the run to stop
+    Interrupted,
+    // the hart has reached the instruction it was to stop at, which it has not executed
+    Reached {
+        hartid: usize,
+        pc: u64,
+    },
+    // the instruction of the hart has stored to the address it was to stop at
+    Written {
+        hartid: usize,
+        pc: u64,
+        address: u64,
+    },
+    // the hart has stopped at a breakpoint which has no trap handler, with the pc left at it
+    Breakpoint {
+        hartid: usize,
+        pc: u64,
+    },
+}
+
+// What stops a run because the machine cannot go on. The hart raising the exception is left at
+// the instruction without taking the trap.
+#[derive(Debug)]
+pub enum EmulatorError {
+    // the instruction cannot be decoded or is not allowed, and has no trap handler
+    IllegalInstruction {
+        hartid: usize,
+        pc: u64,
+        instruction: u32,
+    },
+    // nothing is mapped at the address the instruction accesses, and it has no trap handler
+    UnmappedAccess {
+        hartid: usize,
+        pc: u64,
+        address: u64,
+    },
+    // the instruction has raised another exception which has no trap handler
+    Unhandled {
+        hartid: usize,
+        pc: u64,
+        cause: u64,
+    },
+    // the host has failed to read or write what the emulator needs
+    Io(io::Error),
+}
+
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EmulatorError::IllegalInstruction {
+                hartid,
+                pc,
+                instruction,
+            } => write!(
+                f,
+                "hart {} executed the illegal instruction {:#010x} at {:#x}",
+                hartid, instruction, pc
+            ),
+            EmulatorError::UnmappedAccess {
+                hartid,
+                pc,
+                address,
+            } => write!(
+                f,
+                "hart {} accessed {:#x}, where nothing is mapped, at {:#x}",
+                hartid, address, pc
+            ),
+            EmulatorError::Unhandled { hartid, pc, cause } => write!(
+                f,
+                "hart {} raised the exception {} at {:#x}, which has no trap handler",
+                hartid, cause, pc
+            ),
+            EmulatorError::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl error::Error for EmulatorError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            EmulatorError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for EmulatorError {
+    fn from(error: io::Error) -> Self {
+        EmulatorError::Io(error)
+    }
+}
@@ -0,0 +1,176 @@
+use crate::emulator::{
+    bus::SystemBus,
+    cpu::trace::Access,
+    htif::Htif,
+    snapshot::{self, Reader, Writer},
+    Config, Emulator,
+};
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+const MAGIC: &[u8] = b"FIVEREPL";
+const VERSION: u32 = 1;
+
+// what a command to HTIF has done: the stores of the host to the memory and the exit status
+#[derive(Debug, Clone, PartialEq)]
+struct Event {
+    stores: Vec<(u64, u64, u64)>,
+    exit_code: Option<u64>,
+}
+
+// The inputs a program has taken from the host, which are the effects of the commands it has
+// written to HTIF. The rest of the machine is deterministic, as the time of the CLINT advances
+// with the instructions, so applying them instead of reaching the host again replays a run
+// exactly.
+pub struct History {
+    // the machine when the recording has started
+    start: Vec<u8>,
+    events: Vec<Event>,
+    // the number of the events which have happened, the ones after which are replayed
+    next: usize,
+}
+
+impl History {
+    // Handles the command the program has written to tohost, replaying its effects when the
+    // command has been recorded and recording them otherwise, and returns the exit status when
+    // the program has finished.
+    pub fn poll(&mut self, htif: &mut Htif, bus: &mut SystemBus) -> Option<u64> {
+        if !htif.pending(bus) {
+            return None;
+        }
+        let exit_code = match self.events.get(self.next) {
+            Some(event) => {
+                for &(address, value, size) in &event.stores {
+                    bus.store_bytes(address, &value.to_le_bytes()[..size as usize]);
+                }
+                event.exit_code
+            }
+            None => {
+                bus.log.start();
+                let exit_code = htif.poll(bus);
+                let stores = bus
+                    .log
+                    .stop()
+                    .into_iter()
+                    .filter_map(|access| match access {
+                        Access::Store {
+                            address,
+                            value,
+                            size,
+                        } => Some((address, value, size)),
+                        Access::Load { .. } => None,
+                    })
+                    .collect();
+                self.events.push(Event { stores, exit_code });
+                exit_code
+            }
+        };
+        self.next += 1;
+        exit_code
+    }
+
+    // the number of the events which have happened
+    pub fn events(&self) -> usize {
+        self.next
+    }
+
+    // goes back to when the events have happened, so that the ones after are replayed
+    pub fn rewind(&mut self, events: usize) {
+        self.next = events;
+    }
+
+    // Forgets the events which have not happened, which a machine changed by the debugger may
+    // not bring about again.
+    pub fn truncate(&mut self) {
+        self.events.truncate(self.next);
+    }
+}
+
+// Starts recording the inputs the program takes from the host, unless they are already recorded
+// or replayed. A run which cannot be saved in a snapshot cannot be recorded either.
+pub fn record(emulator: &mut Emulator) -> Result<()> {
+    if emulator.history.is_none() {
+        let mut start = Vec::new();
+        snapshot::save(emulator, &mut start)?;
+        emulator.history = Some(History {
+            start,
+            events: Vec::new(),
+            next: 0,
+        });
+    }
+    Ok(())
+}
+
+// Saves the machine when the recording has started and the events recorded since.
+pub fn save(emulator: &Emulator, mut output: impl Write) -> Result<()> {
+    let history = emulator
+        .history
+        .as_ref()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "the run has not been recorded"))?;
+    let mut recording = Writer::default();
+    recording.magic(MAGIC, VERSION);
+    recording.bytes(&history.start);
+    recording.u64(history.events.len() as u64);
+    for event in &history.events {
+        recording.u64(event.stores.len() as u64);
+        for &(address, value, size) in &event.stores {
+            recording.u64(address);
+            recording.u64(value);
+            recording.u64(size);
+        }
+        recording.option(event.exit_code);
+    }
+    output.write_all(&recording.into_bytes())?;
+    output.flush()
+}
+
+// Makes an emulator of the configured machine which replays a recording from where it has
+// started. Once the events have been replayed, the program reaches the host again and what it
+// takes is recorded further.
+pub fn restore(config: Config, mut input: impl Read) -> Result<Emulator> {
+    let mut bytes = Vec::new();
+    input.read_to_end(&mut bytes)?;
+    let mut recording = Reader::new(&bytes);
+    recording.magic(MAGIC, VERSION)?;
+    let start = recording.bytes()?.to_vec();
+    let mut emulator = snapshot::restore(config, &start[..])?;
+    let mut events = Vec::new();
+    for _ in 0..recording.u64()? {
+        let mut stores = Vec::new();
+        for _ in 0..recording.u64()? {
+            stores.push((recording.u64()?, recording.u64()?, recording.u64()?));
+        }
+        let exit_code = recording.option()?;
+        events.push(Event { stores, exit_code });
+    }
+    emulator.history = Some(History {
+        start,
+        events,
+        next: 0,
+    });
+    Ok(emulator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::{tests::reports_mcause, RunOutcome};
+
+    #[test]
+    fn replay_ok() {
+        let mut emulator = reports_mcause();
+        record(&mut emulator).unwrap();
+        assert_eq!(emulator.run().unwrap(), RunOutcome::Exited(11));
+        let mut bytes = Vec::new();
+        save(&emulator, &mut bytes).unwrap();
+
+        let mut replayed = restore(Config::default(), &bytes[..]).unwrap();
+        let history = replayed.history.as_mut().unwrap();
+        assert_eq!(history.events.len(), 1);
+        // the exit status comes from the recording rather than the host
+        history.events[0].exit_code = Some(42);
+        assert_eq!(replayed.run().unwrap(), RunOutcome::Exited(42));
+
+        assert!(save(&Emulator::default(), Vec::new()).is_err());
+        assert!(restore(Config::default(), &bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,387 @@
+use crate::emulator::{htif::Htif, syscall::newlib::Newlib, Config, Emulator, Misaligned, Turn};
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+const MAGIC: &[u8] = b"FIVESNAP";
+const VERSION: u32 = 4;
+
+// the page in which memory is saved, leaving out the pages which are all zero
+const PAGE_SIZE: usize = 4096;
+const END_OF_PAGES: u64 = u64::MAX;
+
+// the longest run and the longest literal a control byte of the compression describes
+const MAX_RUN: usize = 130;
+const MAX_LITERAL: usize = 128;
+
+// A part of the machine which can be saved to a snapshot and restored from it.
+pub trait Snapshot {
+    fn save(&mut self, snapshot: &mut Writer);
+    fn restore(&mut self, snapshot: &mut Reader) -> Result<()>;
+}
+
+#[derive(Default)]
+pub struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    pub fn magic(&mut self, magic: &[u8], version: u32) {
+        self.bytes.extend_from_slice(magic);
+        self.u32(version);
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn option(&mut self, value: Option<u64>) {
+        self.u8(value.is_some() as u8);
+        self.u64(value.unwrap_or_default());
+    }
+
+    // bytes preceded by their length
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.u64(bytes.len() as u64);
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    pub fn magic(&mut self, magic: &[u8], version: u32) -> Result<()> {
+        if self.take(magic.len())? != magic || self.u32()? != version {
+            return Err(invalid("the file is not of this version of the format"));
+        }
+        Ok(())
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        let mut value = [0; 4];
+        value.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(value))
+    }
+
+    pub fn u64(&mut self) -> Result<u64> {
+        let mut value = [0; 8];
+        value.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(value))
+    }
+
+    pub fn option(&mut self) -> Result<Option<u64>> {
+        let some = self.u8()? != 0;
+        let value = self.u64()?;
+        Ok(if some { Some(value) } else { None })
+    }
+
+    pub fn bytes(&mut self) -> Result<&'a [u8]> {
+        let size = self.u64()?;
+        self.take(size as usize)
+    }
+
+    fn take(&mut self, size: usize) -> Result<&'a [u8]> {
+        if size > self.bytes.len() {
+            return Err(invalid("the snapshot is truncated"));
+        }
+        let (taken, rest) = self.bytes.split_at(size);
+        self.bytes = rest;
+        Ok(taken)
+    }
+}
+
+// Saves the state of the harts, the memory and the devices to the output, so that the machine
+// can be restored from it. The host files a program has opened cannot be saved, so neither can
+// an emulated kernel nor semihosting.
+pub fn save(emulator: &mut Emulator, mut output: impl Write) -> Result<()> {
+    if emulator.kernel.is_some() || emulator.semihosting.is_some() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "a program serviced by an emulated kernel or semihosting cannot be saved",
+        ));
+    }
+    output.write_all(&capture(emulator))?;
+    output.flush()
+}
+
+// Makes an emulator of the configured machine from a snapshot, ready to resume from where it has
+// been saved. The snapshot is to have been taken of a machine with as many harts and the same
+// memory, and the quantum is the one it has been taken with.
+pub fn restore(config: Config, mut input: impl Read) -> Result<Emulator> {
+    let mut bytes = Vec::new();
+    input.read_to_end(&mut bytes)?;
+    let mut emulator = Emulator::new(config);
+    apply(&mut emulator, &bytes)?;
+    Ok(emulator)
+}
+
+// the snapshot of the machine, which leaves out what the host holds for the program
+pub fn capture(emulator: &mut Emulator) -> Vec<u8> {
+    let mut snapshot = Writer::default();
+    snapshot.magic(MAGIC, VERSION);
+    snapshot.u64(emulator.harts.len() as u64);
+    snapshot.u64(emulator.quantum);
+    snapshot.u64(emulator.turn.hartid as u64);
+    snapshot.u64(emulator.turn.executed);
+    for hart in emulator.harts.iter_mut() {
+        hart.save(&mut snapshot);
+    }
+    emulator.bus.clint.save(&mut snapshot);
+    emulator.bus.memory.save(&mut snapshot);
+    snapshot.u8(emulator.bus.misaligned as u8);
+    snapshot.option(emulator.exit_code);
+    snapshot.u8(emulator.htif.is_some() as u8);
+    if let Some(htif) = &mut emulator.htif {
+        htif.save(&mut snapshot);
+    }
+    let mut symbols = emulator.symbols.iter().collect::<Vec<_>>();
+    symbols.sort();
+    snapshot.u64(symbols.len() as u64);
+    for (name, address) in symbols {
+        snapshot.bytes(name.as_bytes());
+        snapshot.u64(*address);
+    }
+    snapshot.into_bytes()
+}
+
+// Brings the machine back to the snapshot, which has been taken of a machine with as many harts
+// and the same memory.
+pub fn apply(emulator: &mut Emulator, bytes: &[u8]) -> Result<()> {
+    let mut snapshot = header(bytes)?;
+    if snapshot.u64()? != emulator.harts.len() as u64 {
+        return Err(invalid("the snapshot has another number of harts"));
+    }
+    emulator.quantum = snapshot.u64()?;
+    emulator.turn = Turn {
+        hartid: snapshot.u64()? as usize,
+        executed: snapshot.u64()?,
+    };
+    if emulator.quantum == 0 || emulator.turn.hartid >= emulator.harts.len() {
+        return Err(invalid("the snapshot has no turn of a hart"));
+    }
+    for hart in emulator.harts.iter_mut() {
+        hart.restore(&mut snapshot)?;
+    }
+    emulator.bus.clint.restore(&mut snapshot)?;
+    emulator.bus.memory.restore(&mut snapshot)?;
+    emulator.bus.misaligned = match snapshot.u8()? {
+        0 => Misaligned::Emulate,
+        1 => Misaligned::Trap,
+        2 => Misaligned::Fault,
+        _ => {
+            return Err(invalid(
+                "the snapshot has no policy for misaligned accesses",
+            ))
+        }
+    };
+    emulator.exit_code = snapshot.option()?;
+    if snapshot.u8()? != 0 {
+        let htif = emulator
+            .htif
+            .get_or_insert_with(|| Htif::new(0, None, Newlib::new(0, 0)));
+        htif.restore(&mut snapshot)?;
+    } else {
+        emulator.htif = None;
+    }
+    emulator.symbols.clear();
+    for _ in 0..snapshot.u64()? {
+        let name = String::from_utf8(snapshot.bytes()?.to_vec())
+            .map_err(|_| invalid("a symbol is not UTF-8"))?;
+        let address = snapshot.u64()?;
+        emulator.symbols.insert(name, address);
+    }
+    Ok(())
+}
+
+fn header(bytes: &[u8]) -> Result<Reader<'_>> {
+    let mut snapshot = Reader::new(bytes);
+    snapshot.magic(MAGIC, VERSION)?;
+    Ok(snapshot)
+}
+
+// Saves the pages of the memory, given with their indexes, which are not all zero, each
+// compressed.
+pub fn save_pages<'a>(pages: impl Iterator<Item = (u64, &'a [u8])>, snapshot: &mut Writer) {
+    for (index, page) in pages {
+        if page != &[0; PAGE_SIZE][..page.len()] {
+            snapshot.u64(index);
+            snapshot.bytes(&compress(page));
+        }
+    }
+    snapshot.u64(END_OF_PAGES);
+}
+
+// Reads the pages save_pages has saved, each of the size, and hands each to restore with its
+// index.
+pub fn restore_pages(
+    snapshot: &mut Reader,
+    size: usize,
+    mut restore: impl FnMut(u64, Vec<u8>) -> Result<()>,
+) -> Result<()> {
+    loop {
+        let index = snapshot.u64()?;
+        if index == END_OF_PAGES {
+            return Ok(());
+        }
+        restore(index, decompress(snapshot.bytes()?, size)?)?;
+    }
+}
+
+// Compresses the bytes as PackBits does: a control byte below 0x80 is followed by that many
+// plus one literal bytes, and any other by a byte repeated that many minus 125 times.
+fn compress(bytes: &[u8]) -> Vec<u8> {
+    let mut compressed = Vec::new();
+    let mut literal = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let run = bytes[i..]
+            .iter()
+            .take(MAX_RUN)
+            .take_while(|&&byte| byte == bytes[i])
+            .count();
+        if run >= 3 {
+            flush(&mut compressed, &mut literal);
+            compressed.push((run + 125) as u8);
+            compressed.push(bytes[i]);
+            i += run;
+        } else {
+            literal.push(bytes[i]);
+            if literal.len() == MAX_LITERAL {
+                flush(&mut compressed, &mut literal);
+            }
+            i += 1;
+        }
+    }
+    flush(&mut compressed, &mut literal);
+    compressed
+}
+
+fn flush(compressed: &mut Vec<u8>, literal: &mut Vec<u8>) {
+    if !literal.is_empty() {
+        compressed.push((literal.len() - 1) as u8);
+        compressed.append(literal);
+    }
+}
+
+fn decompress(compressed: &[u8], size: usize) -> Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(size);
+    let mut i = 0;
+    while i < compressed.len() {
+        let control = compressed[i] as usize;
+        if control < 0x80 {
+            let literal = compressed
+                .get(i + 1..i + 2 + control)
+                .ok_or_else(|| invalid("a page is truncated"))?;
+            bytes.extend_from_slice(literal);
+            i += 2 + control;
+        } else {
+            let byte = *compressed
+                .get(i + 1)
+                .ok_or_else(|| invalid("a page is truncated"))?;
+            bytes.resize(bytes.len() + control - 125, byte);
+            i += 2;
+        }
+    }
+    if bytes.len() != size {
+        return Err(invalid("a page has a wrong size"));
+    }
+    Ok(bytes)
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::{
+        memory::{MEMORY_BASE_ADDRESS, MEMORY_SIZE},
+        tests::reports_mcause,
+        RunOutcome, Step, StopConditions,
+    };
+
+    #[test]
+    fn compress_ok() {
+        let mut page = vec![0; PAGE_SIZE];
+        page[..5].copy_from_slice(&[1, 2, 2, 3, 3]);
+        page[100..300].iter_mut().for_each(|byte| *byte = 7);
+        for (i, byte) in page[1000..1300].iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let compressed = compress(&page);
+        assert!(compressed.len() < 400);
+        assert_eq!(decompress(&compressed, PAGE_SIZE).unwrap(), page);
+        assert!(decompress(&compressed, PAGE_SIZE + 1).is_err());
+        assert!(decompress(&compressed[..compressed.len() - 1], PAGE_SIZE).is_err());
+    }
+
+    #[test]
+    fn restore_ok() {
+        let mut emulator = reports_mcause();
+        // stops at the trap handler
+        let handler = emulator.read_pc(0) + 0x10;
+        emulator.stop_when(StopConditions {
+            breakpoints: vec![handler],
+            ..StopConditions::default()
+        });
+        assert_eq!(
+            emulator.run().unwrap(),
+            RunOutcome::Reached {
+                hartid: 0,
+                pc: handler
+            }
+        );
+        let mut bytes = Vec::new();
+        save(&mut emulator, &mut bytes).unwrap();
+        // the memory other than the page of the program is left out
+        assert!(bytes.len() < 2 * PAGE_SIZE);
+
+        let mut restored = restore(Config::default(), &bytes[..]).unwrap();
+        assert_eq!(restored.read_pc(0), handler);
+        assert_eq!(restored.read_csr(0, 0x342), Some(11));
+        assert_eq!(restored.symbol("tohost"), emulator.symbol("tohost"));
+        assert_eq!(
+            restored.read_memory(MEMORY_BASE_ADDRESS, 0x100).unwrap(),
+            emulator.read_memory(MEMORY_BASE_ADDRESS, 0x100).unwrap()
+        );
+        assert_eq!(restored.step(0).unwrap(), Step::Retired);
+        assert_eq!(restored.run().unwrap(), RunOutcome::Exited(11));
+
+        assert!(restore(Config::default(), &bytes[..bytes.len() - 1]).is_err());
+        assert!(restore(Config::default(), &b"FIVESNAP\x01\x00\x00\x00"[..]).is_err());
+        // nor is a snapshot restored on another machine
+        let config = Config {
+            harts: 2,
+            ..Config::default()
+        };
+        let error = restore(config, &bytes[..]).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        let config = Config {
+            memory_size: MEMORY_SIZE / 2,
+            ..Config::default()
+        };
+        let error = restore(config, &bytes[..]).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,331 @@
+pub mod linux;
+pub mod newlib;
+pub mod semihosting;
+
+use crate::emulator::{
+    bus::SystemBus,
+    cpu::x::IntegerRegister,
+    syscall::{linux::Linux, newlib::Newlib},
+};
+use std::collections::{hash_map::RandomState, HashMap};
+use std::fmt;
+use std::fs::{File, Metadata, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{stderr, stdin, stdout, Error, ErrorKind, Read, Result, Write};
+
+const ENOENT: i32 = 2;
+const EINTR: i32 = 4;
+const EIO: i32 = 5;
+const EBADF: i32 = 9;
+const EAGAIN: i32 = 11;
+pub const ENOMEM: i32 = 12;
+const EACCES: i32 = 13;
+pub const EFAULT: i32 = 14;
+const EBUSY: i32 = 16;
+const EEXIST: i32 = 17;
+const EXDEV: i32 = 18;
+const ENOTDIR: i32 = 20;
+const EISDIR: i32 = 21;
+pub const EINVAL: i32 = 22;
+pub const ENOTTY: i32 = 25;
+const EFBIG: i32 = 27;
+const ENOSPC: i32 = 28;
+const ESPIPE: i32 = 29;
+const EROFS: i32 = 30;
+const EPIPE: i32 = 32;
+pub const ERANGE: i32 = 34;
+const ENAMETOOLONG: i32 = 36;
+pub const ENOSYS: i32 = 38;
+const ENOTEMPTY: i32 = 39;
+
+pub const PAGE_SIZE: u64 = 4096;
+
+// the size of the stack at the top of the address space of a user program
+pub const STACK_SIZE: u64 = 8 * 1024 * 1024;
+
+// flags of open shared by Linux and newlib
+const O_ACCMODE: u64 = 0o3;
+const O_WRONLY: u64 = 0o1;
+const O_RDWR: u64 = 0o2;
+const O_CREAT: u64 = 0o100;
+const O_EXCL: u64 = 0o200;
+const O_TRUNC: u64 = 0o1000;
+const O_APPEND: u64 = 0o2000;
+
+const STAT_SIZE: usize = 128;
+const S_IFCHR: u32 = 0o020000;
+#[cfg(not(unix))]
+const S_IFDIR: u32 = 0o040000;
+#[cfg(not(unix))]
+const S_IFREG: u32 = 0o100000;
+
+const STDIN: u64 = 0;
+const STDOUT: u64 = 1;
+const STDERR: u64 = 2;
+
+// The kernel emulated for a program running in U-mode.
+pub enum Kernel {
+    Linux(Linux),
+    // riscv-pk running a program linked against newlib
+    ProxyKernel(Newlib),
+}
+
+impl Kernel {
+    // Services an environment call from U-mode and returns the exit status when the program
+    // has exited.
+    pub fn ecall(&mut self, x: &mut IntegerRegister, bus: &mut SystemBus) -> Option<u64> {
+        match self {
+            Kernel::Linux(linux) => linux.ecall(x, bus),
+            Kernel::ProxyKernel(newlib) => newlib.ecall(x, bus),
+        }
+    }
+}
+
+// The host files opened by the guest, keyed by the file descriptors handed out to the guest.
+// The descriptors of the standard streams refer to the ones of the emulator.
+#[derive(Default)]
+pub struct FileTable {
+    files: HashMap<u64, File>,
+}
+
+impl FileTable {
+    // Hands out the lowest free descriptor for the file.
+    pub fn open(&mut self, file: File) -> u64 {
+        let fd = (STDERR + 1..)
+            .find(|fd| !self.files.contains_key(fd))
+            .unwrap();
+        self.files.insert(fd, file);
+        fd
+    }
+
+    pub fn close(&mut self, fd: u64) -> Result<()> {
+        match fd {
+            STDIN | STDOUT | STDERR => Ok(()),
+            _ => self
+                .files
+                .remove(&fd)
+                .map(|_| ())
+                .ok_or_else(bad_descriptor),
+        }
+    }
+
+    pub fn is_standard_stream(&self, fd: u64) -> bool {
+        fd <= STDERR
+    }
+
+    pub fn file(&mut self, fd: u64) -> Result<&mut File> {
+        self.files.get_mut(&fd).ok_or_else(bad_descriptor)
+    }
+
+    pub fn read(&mut self, fd: u64, buffer: &mut [u8]) -> Result<usize> {
+        match fd {
+            STDIN => stdin().read(buffer),
+            STDOUT | STDERR => Err(bad_descriptor()),
+            _ => self.file(fd)?.read(buffer),
+        }
+    }
+
+    pub fn write(&mut self, fd: u64, buffer: &[u8]) -> Result<usize> {
+        match fd {
+            STDIN => Err(bad_descriptor()),
+            STDOUT => {
+                let mut out = stdout();
+                out.write_all(buffer)?;
+                out.flush()?;
+                Ok(buffer.len())
+            }
+            STDERR => {
+                stderr().write_all(buffer)?;
+                Ok(buffer.len())
+            }
+            _ => self.file(fd)?.write(buffer),
+        }
+    }
+}
+
+// An errno of the generic Linux ABI which the emulator raises itself, as the host may number
+// the errors differently.
+#[derive(Debug)]
+struct Errno(i32);
+
+impl fmt::Display for Errno {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "errno {}", self.0)
+    }
+}
+
+impl std::error::Error for Errno {}
+
+// the negated errno returned to the guest for the error, the errors of the host being numbered
+// by their kind
+pub fn errno(error: &Error) -> i64 {
+    if let Some(Errno(errno)) = error.get_ref().and_then(|e| e.downcast_ref::<Errno>()) {
+        return -(*errno as i64);
+    }
+    let errno = match error.kind() {
+        ErrorKind::NotFound => ENOENT,
+        ErrorKind::PermissionDenied => EACCES,
+        ErrorKind::AlreadyExists => EEXIST,
+        ErrorKind::Interrupted => EINTR,
+        ErrorKind::WouldBlock => EAGAIN,
+        ErrorKind::InvalidInput => EINVAL,
+        ErrorKind::BrokenPipe => EPIPE,
+        ErrorKind::OutOfMemory => ENOMEM,
+        ErrorKind::Unsupported => ENOSYS,
+        ErrorKind::NotADirectory => ENOTDIR,
+        ErrorKind::IsADirectory => EISDIR,
+        ErrorKind::DirectoryNotEmpty => ENOTEMPTY,
+        ErrorKind::ReadOnlyFilesystem => EROFS,
+        ErrorKind::StorageFull => ENOSPC,
+        ErrorKind::NotSeekable => ESPIPE,
+        ErrorKind::FileTooLarge => EFBIG,
+        ErrorKind::ResourceBusy => EBUSY,
+        ErrorKind::CrossesDevices => EXDEV,
+        ErrorKind::InvalidFilename => ENAMETOOLONG,
+        _ => EIO,
+    };
+    -(errno as i64)
+}
+
+pub fn error(errno: i32) -> Error {
+    Error::other(Errno(errno))
+}
+
+fn bad_descriptor() -> Error {
+    error(EBADF)
+}
+
+pub fn check_range(bus: &SystemBus, address: u64, size: u64) -> Result<()> {
+    match address.checked_add(size) {
+        Some(end) if address >= bus.memory.base() && end <= bus.memory.end() => Ok(()),
+        _ => Err(error(EFAULT)),
+    }
+}
+
+pub fn read_bytes(bus: &SystemBus, address: u64, size: u64) -> Result<Vec<u8>> {
+    check_range(bus, address, size)?;
+    let mut bytes = vec![0; size as usize];
+    bus.load_bytes(address, &mut bytes);
+    Ok(bytes)
+}
+
+pub fn write_bytes(bus: &mut SystemBus, address: u64, bytes: &[u8]) -> Result<()> {
+    check_range(bus, address, bytes.len() as u64)?;
+    bus.store_bytes(address, bytes);
+    Ok(())
+}
+
+// Zeroes the bytes from the address a page at a time, rather than from a buffer of their size.
+pub fn zero_bytes(bus: &mut SystemBus, address: u64, size: u64) -> Result<()> {
+    check_range(bus, address, size)?;
+    let zeros = [0; PAGE_SIZE as usize];
+    let end = address + size;
+    let mut address = address;
+    while address < end {
+        let length = (PAGE_SIZE - address % PAGE_SIZE).min(end - address);
+        bus.store_bytes(address, &zeros[..length as usize]);
+        address += length;
+    }
+    Ok(())
+}
+
+// Reads a NUL-terminated string.
+pub fn read_string(bus: &SystemBus, address: u64) -> Result<String> {
+    let mut bytes = Vec::new();
+    loop {
+        check_range(bus, address + bytes.len() as u64, 1)?;
+        match bus.load8(address + bytes.len() as u64) {
+            0 => break,
+            byte => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| error(EINVAL))
+}
+
+pub fn open(path: &str, flags: u64) -> Result<File> {
+    let mut options = OpenOptions::new();
+    match flags & O_ACCMODE {
+        O_WRONLY => options.write(true),
+        O_RDWR => options.read(true).write(true),
+        _ => options.read(true),
+    };
+    if flags & O_APPEND != 0 {
+        options.append(true);
+    }
+    if flags & O_TRUNC != 0 {
+        options.truncate(true);
+    }
+    if flags & O_CREAT != 0 {
+        if flags & O_EXCL != 0 {
+            options.create_new(true);
+        } else {
+            options.create(true);
+        }
+    }
+    options.open(path)
+}
+
+// struct stat of the generic Linux ABI, describing a character device when there is no file
+pub fn stat(metadata: Option<&Metadata>) -> [u8; STAT_SIZE] {
+    let mut stat = [0; STAT_SIZE];
+    let mut put = |offset: usize, bytes: &[u8]| {
+        stat[offset..offset + bytes.len()].copy_from_slice(bytes);
+    };
+    match metadata {
+        #[cfg(unix)]
+        Some(metadata) => {
+            use std::os::unix::fs::MetadataExt;
+            put(0, &metadata.dev().to_le_bytes());
+            put(8, &metadata.ino().to_le_bytes());
+            put(16, &metadata.mode().to_le_bytes());
+            put(20, &(metadata.nlink() as u32).to_le_bytes());
+            put(24, &metadata.uid().to_le_bytes());
+            put(28, &metadata.gid().to_le_bytes());
+            put(32, &metadata.rdev().to_le_bytes());
+            put(48, &metadata.size().to_le_bytes());
+            put(56, &(metadata.blksize() as u32).to_le_bytes());
+            put(64, &metadata.blocks().to_le_bytes());
+            put(72, &metadata.atime().to_le_bytes());
+            put(80, &metadata.atime_nsec().to_le_bytes());
+            put(88, &metadata.mtime().to_le_bytes());
+            put(96, &metadata.mtime_nsec().to_le_bytes());
+            put(104, &metadata.ctime().to_le_bytes());
+            put(112, &metadata.ctime_nsec().to_le_bytes());
+        }
+        #[cfg(not(unix))]
+        Some(metadata) => {
+            let mode = if metadata.is_dir() {
+                S_IFDIR | 0o755
+            } else {
+                S_IFREG | 0o644
+            };
+            put(16, &mode.to_le_bytes());
+            put(20, &1u32.to_le_bytes());
+            put(48, &metadata.len().to_le_bytes());
+            put(56, &(PAGE_SIZE as u32).to_le_bytes());
+        }
+        None => {
+            put(16, &(S_IFCHR | 0o620).to_le_bytes());
+            put(20, &1u32.to_le_bytes());
+            put(56, &(PAGE_SIZE as u32).to_le_bytes());
+        }
+    }
+    stat
+}
+
+pub fn word(bytes: &[u8]) -> [u8; 8] {
+    let mut word = [0; 8];
+    word.copy_from_slice(bytes);
+    word
+}
+
+pub fn random_bytes(size: usize) -> Vec<u8> {
+    let state = RandomState::new();
+    (0..size)
+        .map(|i| {
+            let mut hasher = state.build_hasher();
+            hasher.write_usize(i);
+            hasher.finish() as u8
+        })
+        .collect()
+}
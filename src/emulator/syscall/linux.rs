@@ -0,0 +1,532 @@
+use crate::emulator::{
+    bus::SystemBus,
+    cpu::x::{IntegerRegister, A0, A1, A2, A3, A4, A5, A7},
+    elf::Elf,
+    syscall::{
+        check_range, errno, error, open, random_bytes, read_bytes, read_string, stat, word,
+        write_bytes, zero_bytes, FileTable, EINVAL, ENOMEM, ENOSYS, ENOTTY, ERANGE, PAGE_SIZE,
+        STACK_SIZE,
+    },
+};
+use std::fs::{metadata, Metadata};
+use std::io::{Read, Result, Seek, SeekFrom};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+// system call numbers of the generic Linux ABI
+const SYS_GETCWD: u64 = 17;
+const SYS_FCNTL: u64 = 25;
+const SYS_IOCTL: u64 = 29;
+const SYS_FACCESSAT: u64 = 48;
+const SYS_OPENAT: u64 = 56;
+const SYS_CLOSE: u64 = 57;
+const SYS_LSEEK: u64 = 62;
+const SYS_READ: u64 = 63;
+const SYS_WRITE: u64 = 64;
+const SYS_READV: u64 = 65;
+const SYS_WRITEV: u64 = 66;
+const SYS_NEWFSTATAT: u64 = 79;
+const SYS_FSTAT: u64 = 80;
+const SYS_EXIT: u64 = 93;
+const SYS_EXIT_GROUP: u64 = 94;
+const SYS_SET_TID_ADDRESS: u64 = 96;
+const SYS_FUTEX: u64 = 98;
+const SYS_SET_ROBUST_LIST: u64 = 99;
+const SYS_CLOCK_GETTIME: u64 = 113;
+const SYS_SCHED_YIELD: u64 = 124;
+const SYS_RT_SIGACTION: u64 = 134;
+const SYS_RT_SIGPROCMASK: u64 = 135;
+const SYS_UNAME: u64 = 160;
+const SYS_GETTIMEOFDAY: u64 = 169;
+const SYS_GETPID: u64 = 172;
+const SYS_GETPPID: u64 = 173;
+const SYS_GETUID: u64 = 174;
+const SYS_GETEUID: u64 = 175;
+const SYS_GETGID: u64 = 176;
+const SYS_GETEGID: u64 = 177;
+const SYS_GETTID: u64 = 178;
+const SYS_BRK: u64 = 214;
+const SYS_MUNMAP: u64 = 215;
+const SYS_MMAP: u64 = 222;
+const SYS_MPROTECT: u64 = 226;
+const SYS_MADVISE: u64 = 233;
+const SYS_GETRANDOM: u64 = 278;
+
+const AT_EMPTY_PATH: u64 = 0x1000;
+
+const SEEK_SET: u64 = 0;
+const SEEK_CUR: u64 = 1;
+const SEEK_END: u64 = 2;
+
+const MAP_FIXED: u64 = 0x10;
+const MAP_ANONYMOUS: u64 = 0x20;
+
+const CLOCK_REALTIME: u64 = 0;
+
+// the only process, which is also its only thread
+const PID: i64 = 1;
+
+// auxiliary vector entries
+const AT_NULL: u64 = 0;
+const AT_PHDR: u64 = 3;
+const AT_PHENT: u64 = 4;
+const AT_PHNUM: u64 = 5;
+const AT_PAGESZ: u64 = 6;
+const AT_ENTRY: u64 = 9;
+const AT_UID: u64 = 11;
+const AT_EUID: u64 = 12;
+const AT_GID: u64 = 13;
+const AT_EGID: u64 = 14;
+const AT_PLATFORM: u64 = 15;
+const AT_HWCAP: u64 = 16;
+const AT_CLKTCK: u64 = 17;
+const AT_SECURE: u64 = 23;
+const AT_RANDOM: u64 = 25;
+const AT_EXECFN: u64 = 31;
+
+const UTSNAME_FIELD_SIZE: usize = 65;
+
+// Emulates the Linux kernel for a static executable running in U-mode: its system calls are
+// serviced on the host and the process owns the whole memory.
+pub struct Linux {
+    files: FileTable,
+    brk_start: u64,
+    brk: u64,
+    // anonymous mappings are placed downwards from below the stack
+    mmap_top: u64,
+    // when the program has first read the monotonic clock, which is read only then, as there is
+    // none on wasm32
+    start: Option<Instant>,
+}
+
+impl Linux {
+    pub fn new(elf: &Elf, stack_top: u64) -> Self {
+        let brk = align_up(elf.end(), PAGE_SIZE);
+        Self {
+            files: FileTable::default(),
+            brk_start: brk,
+            brk,
+            mmap_top: stack_top - STACK_SIZE,
+            start: None,
+        }
+    }
+
+    // Services the system call the registers describe and returns the exit status when the
+    // program has exited.
+    pub fn ecall(&mut self, x: &mut IntegerRegister, bus: &mut SystemBus) -> Option<u64> {
+        let number = x.readu(A7);
+        let args = [A0, A1, A2, A3, A4, A5].map(|r| x.readu(r));
+        if number == SYS_EXIT || number == SYS_EXIT_GROUP {
+            return Some(args[0] & 0xff);
+        }
+        let result = match self.dispatch(number, args, bus) {
+            Ok(value) => value,
+            Err(e) => errno(&e),
+        };
+        x.writei(A0, result);
+        None
+    }
+
+    fn dispatch(&mut self, number: u64, args: [u64; 6], bus: &mut SystemBus) -> Result<i64> {
+        match number {
+            SYS_GETCWD => {
+                let mut cwd = std::env::current_dir()?
+                    .to_string_lossy()
+                    .into_owned()
+                    .into_bytes();
+                cwd.push(0);
+                if cwd.len() as u64 > args[1] {
+                    return Err(error(ERANGE));
+                }
+                write_bytes(bus, args[0], &cwd)?;
+                Ok(cwd.len() as i64)
+            }
+            SYS_FCNTL => Ok(0),
+            // no descriptor is a terminal
+            SYS_IOCTL => Err(error(ENOTTY)),
+            // paths relative to a directory descriptor are resolved against the working
+            // directory of the emulator
+            SYS_FACCESSAT => {
+                metadata(read_string(bus, args[1])?)?;
+                Ok(0)
+            }
+            SYS_OPENAT => {
+                let file = open(&read_string(bus, args[1])?, args[2])?;
+                Ok(self.files.open(file) as i64)
+            }
+            SYS_CLOSE => {
+                self.files.close(args[0])?;
+                Ok(0)
+            }
+            SYS_LSEEK => {
+                let position = match args[2] {
+                    SEEK_SET => SeekFrom::Start(args[1]),
+                    SEEK_CUR => SeekFrom::Current(args[1] as i64),
+                    SEEK_END => SeekFrom::End(args[1] as i64),
+                    _ => return Err(error(EINVAL)),
+                };
+                Ok(self.files.file(args[0])?.seek(position)? as i64)
+            }
+            SYS_READ => self.read(bus, args[0], args[1], args[2]),
+            SYS_WRITE => self.write(bus, args[0], args[1], args[2]),
+            SYS_READV | SYS_WRITEV => {
+                let mut total = 0;
+                for i in 0..args[2] {
+                    let iovec = args[1] + i * 16;
+                    let address = u64::from_le_bytes(word(&read_bytes(bus, iovec, 8)?));
+                    let size = u64::from_le_bytes(word(&read_bytes(bus, iovec + 8, 8)?));
+                    let transferred = if number == SYS_READV {
+                        self.read(bus, args[0], address, size)?
+                    } else {
+                        self.write(bus, args[0], address, size)?
+                    };
+                    total += transferred;
+                    if (transferred as u64) < size {
+                        break;
+                    }
+                }
+                Ok(total)
+            }
+            SYS_NEWFSTATAT => {
+                let metadata =
+                    if args[3] & AT_EMPTY_PATH != 0 && read_string(bus, args[1])?.is_empty() {
+                        self.metadata(args[0])?
+                    } else {
+                        Some(metadata(read_string(bus, args[1])?)?)
+                    };
+                write_bytes(bus, args[2], &stat(metadata.as_ref()))?;
+                Ok(0)
+            }
+            SYS_FSTAT => {
+                let metadata = self.metadata(args[0])?;
+                write_bytes(bus, args[1], &stat(metadata.as_ref()))?;
+                Ok(0)
+            }
+            SYS_SET_TID_ADDRESS | SYS_GETPID | SYS_GETTID => Ok(PID),
+            SYS_GETPPID | SYS_GETUID | SYS_GETEUID | SYS_GETGID | SYS_GETEGID => Ok(0),
+            // signals are never delivered and there are no other threads to wait for
+            SYS_FUTEX | SYS_SET_ROBUST_LIST | SYS_SCHED_YIELD | SYS_RT_SIGACTION
+            | SYS_RT_SIGPROCMASK => Ok(0),
+            SYS_CLOCK_GETTIME => {
+                let (seconds, nanoseconds) = self.clock(args[0]);
+                write_bytes(bus, args[1], &seconds.to_le_bytes())?;
+                write_bytes(bus, args[1] + 8, &nanoseconds.to_le_bytes())?;
+                Ok(0)
+            }
+            SYS_GETTIMEOFDAY => {
+                let (seconds, nanoseconds) = self.clock(CLOCK_REALTIME);
+                write_bytes(bus, args[0], &seconds.to_le_bytes())?;
+                write_bytes(bus, args[0] + 8, &(nanoseconds / 1000).to_le_bytes())?;
+                Ok(0)
+            }
+            SYS_UNAME => {
+                let mut utsname = Vec::new();
+                for field in &["Linux", "five", "5.15.0", "#1", "riscv64", ""] {
+                    let mut bytes = field.as_bytes().to_vec();
+                    bytes.resize(UTSNAME_FIELD_SIZE, 0);
+                    utsname.extend_from_slice(&bytes);
+                }
+                write_bytes(bus, args[0], &utsname)?;
+                Ok(0)
+            }
+            SYS_BRK => {
+                let brk = args[0];
+                if brk >= self.brk_start && brk <= self.mmap_top {
+                    // the memory given back earlier has to look fresh when it is reused
+                    if brk > self.brk {
+                        zero_bytes(bus, self.brk, brk - self.brk)?;
+                    }
+                    self.brk = brk;
+                }
+                Ok(self.brk as i64)
+            }
+            SYS_MMAP => {
+                if args[1] == 0 {
+                    return Err(error(EINVAL));
+                }
+                if args[1] > self.mmap_top {
+                    return Err(error(ENOMEM));
+                }
+                let size = align_up(args[1], PAGE_SIZE);
+                let address = if args[3] & MAP_FIXED != 0 {
+                    args[0]
+                } else {
+                    if self.mmap_top < self.brk + size {
+                        return Err(error(ENOMEM));
+                    }
+                    self.mmap_top -= size;
+                    self.mmap_top
+                };
+                zero_bytes(bus, address, size)?;
+                if args[3] & MAP_ANONYMOUS == 0 {
+                    let file = self.files.file(args[4])?;
+                    let position = file.stream_position()?;
+                    file.seek(SeekFrom::Start(args[5]))?;
+                    let mut bytes = Vec::new();
+                    file.take(args[1]).read_to_end(&mut bytes)?;
+                    file.seek(SeekFrom::Start(position))?;
+                    write_bytes(bus, address, &bytes)?;
+                }
+                Ok(address as i64)
+            }
+            // mappings are never reclaimed and the memory is not protected
+            SYS_MUNMAP | SYS_MPROTECT | SYS_MADVISE => Ok(0),
+            SYS_GETRANDOM => {
+                write_bytes(bus, args[0], &random_bytes(args[1] as usize))?;
+                Ok(args[1] as i64)
+            }
+            _ => Err(error(ENOSYS)),
+        }
+    }
+
+    fn read(&mut self, bus: &mut SystemBus, fd: u64, address: u64, size: u64) -> Result<i64> {
+        // validate the buffer before consuming any input
+        check_range(bus, address, size)?;
+        let mut buffer = vec![0; size as usize];
+        let size = self.files.read(fd, &mut buffer)?;
+        write_bytes(bus, address, &buffer[..size])?;
+        Ok(size as i64)
+    }
+
+    fn write(&mut self, bus: &SystemBus, fd: u64, address: u64, size: u64) -> Result<i64> {
+        let buffer = read_bytes(bus, address, size)?;
+        Ok(self.files.write(fd, &buffer)? as i64)
+    }
+
+    // the metadata of the file, or none for the standard streams
+    fn metadata(&mut self, fd: u64) -> Result<Option<Metadata>> {
+        if self.files.is_standard_stream(fd) {
+            return Ok(None);
+        }
+        Ok(Some(self.files.file(fd)?.metadata()?))
+    }
+
+    fn clock(&mut self, clock: u64) -> (i64, i64) {
+        let elapsed = if clock == CLOCK_REALTIME {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+        } else {
+            self.start.get_or_insert_with(Instant::now).elapsed()
+        };
+        (elapsed.as_secs() as i64, elapsed.subsec_nanos() as i64)
+    }
+}
+
+// Lays out the arguments, the environment and the auxiliary vector on the stack the way the
+// kernel does and returns the initial stack pointer, which points at argc.
+pub fn initial_stack(
+    bus: &mut SystemBus,
+    elf: &Elf,
+    stack_top: u64,
+    args: &[String],
+    envs: &[String],
+) -> Result<u64> {
+    let mut sp = stack_top;
+    let mut push = |bytes: &[u8]| -> Result<u64> {
+        sp -= bytes.len() as u64;
+        write_bytes(bus, sp, bytes)?;
+        Ok(sp)
+    };
+    let c_string = |s: &String| {
+        let mut bytes = s.as_bytes().to_vec();
+        bytes.push(0);
+        bytes
+    };
+    let random = push(&random_bytes(16))?;
+    let platform = push(b"riscv64\0")?;
+    let argv = args
+        .iter()
+        .map(|a| push(&c_string(a)))
+        .collect::<Result<Vec<_>>>()?;
+    let envp = envs
+        .iter()
+        .map(|e| push(&c_string(e)))
+        .collect::<Result<Vec<_>>>()?;
+    let hwcap = (1 << (b'I' - b'A')) | (1 << (b'M' - b'A'));
+    let auxv = [
+        (AT_PHDR, elf.program_header_address),
+        (AT_PHENT, elf.program_header_size),
+        (AT_PHNUM, elf.program_header_count),
+        (AT_PAGESZ, PAGE_SIZE),
+        (AT_ENTRY, elf.entry),
+        (AT_UID, 0),
+        (AT_EUID, 0),
+        (AT_GID, 0),
+        (AT_EGID, 0),
+        (AT_PLATFORM, platform),
+        (AT_HWCAP, hwcap),
+        (AT_CLKTCK, 100),
+        (AT_SECURE, 0),
+        (AT_RANDOM, random),
+        (AT_EXECFN, argv.first().cloned().unwrap_or(0)),
+        (AT_NULL, 0),
+    ];
+
+    let mut words = vec![argv.len() as u64];
+    words.extend_from_slice(&argv);
+    words.push(0);
+    words.extend_from_slice(&envp);
+    words.push(0);
+    for (key, value) in auxv.iter() {
+        words.push(*key);
+        words.push(*value);
+    }
+    let sp = (sp - words.len() as u64 * 8) & !0xf;
+    let bytes = words
+        .iter()
+        .flat_map(|w| w.to_le_bytes().to_vec())
+        .collect::<Vec<_>>();
+    write_bytes(bus, sp, &bytes)?;
+    Ok(sp)
+}
+
+fn align_up(value: u64, align: u64) -> u64 {
+    (value + align - 1) & !(align - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::{elf::tests::executable, memory::Memory};
+
+    const MEMORY_SIZE: u64 = 16 * 1024 * 1024;
+
+    fn machine() -> (Elf, SystemBus) {
+        let elf = Elf::parse(&executable(0x10000, &[0x00000013], &[])).unwrap();
+        let bus = SystemBus::new(1, Memory::new(0, MEMORY_SIZE));
+        (elf, bus)
+    }
+
+    fn load_word(bus: &SystemBus, address: u64) -> u64 {
+        u64::from_le_bytes(word(&read_bytes(bus, address, 8).unwrap()))
+    }
+
+    #[test]
+    fn initial_stack_ok() {
+        let (elf, mut bus) = machine();
+        let args = ["program".to_string(), "argument".to_string()];
+        let envs = ["HOME=/".to_string()];
+        let sp = initial_stack(&mut bus, &elf, MEMORY_SIZE, &args, &envs).unwrap();
+        assert_eq!(sp % 16, 0);
+        // argc, argv and envp, each followed by a null pointer
+        assert_eq!(load_word(&bus, sp), 2);
+        let argv = [load_word(&bus, sp + 8), load_word(&bus, sp + 16)];
+        assert_eq!(read_string(&bus, argv[0]).unwrap(), "program");
+        assert_eq!(read_string(&bus, argv[1]).unwrap(), "argument");
+        assert_eq!(load_word(&bus, sp + 24), 0);
+        assert_eq!(
+            read_string(&bus, load_word(&bus, sp + 32)).unwrap(),
+            "HOME=/"
+        );
+        assert_eq!(load_word(&bus, sp + 40), 0);
+        // the auxiliary vector up to AT_NULL
+        let mut auxv = Vec::new();
+        let mut entry = sp + 48;
+        loop {
+            let (key, value) = (load_word(&bus, entry), load_word(&bus, entry + 8));
+            auxv.push((key, value));
+            if key == AT_NULL {
+                break;
+            }
+            entry += 16;
+        }
+        let value = |key| auxv.iter().find(|(k, _)| *k == key).unwrap().1;
+        assert_eq!(value(AT_PHDR), elf.program_header_address);
+        assert_eq!(value(AT_PHNUM), elf.program_header_count);
+        assert_eq!(value(AT_PAGESZ), PAGE_SIZE);
+        assert_eq!(value(AT_ENTRY), elf.entry);
+        assert_eq!(value(AT_EXECFN), argv[0]);
+        assert_eq!(read_string(&bus, value(AT_PLATFORM)).unwrap(), "riscv64");
+        // the random bytes are at the top of the stack
+        assert_eq!(value(AT_RANDOM), MEMORY_SIZE - 16);
+        assert!(argv[0] < MEMORY_SIZE - 16);
+    }
+
+    #[test]
+    fn files_ok() {
+        let (elf, mut bus) = machine();
+        let mut linux = Linux::new(&elf, MEMORY_SIZE);
+        let path = std::env::temp_dir().join(format!("five-linux-files-{}", std::process::id()));
+        std::fs::write(&path, b"hello").unwrap();
+        let (name, buffer) = (0x100000, 0x101000);
+        let mut string = path.to_str().unwrap().as_bytes().to_vec();
+        string.push(0);
+        write_bytes(&mut bus, name, &string).unwrap();
+
+        // openat(AT_FDCWD, name, O_RDWR)
+        let at_fdcwd = -100i64 as u64;
+        let fd = linux
+            .dispatch(SYS_OPENAT, [at_fdcwd, name, 0o2, 0, 0, 0], &mut bus)
+            .unwrap() as u64;
+        assert!(fd > 2);
+        assert_eq!(
+            linux
+                .dispatch(SYS_READ, [fd, buffer, 3, 0, 0, 0], &mut bus)
+                .unwrap(),
+            3
+        );
+        assert_eq!(read_bytes(&bus, buffer, 3).unwrap(), b"hel");
+        write_bytes(&mut bus, buffer, b"LO").unwrap();
+        assert_eq!(
+            linux
+                .dispatch(SYS_WRITE, [fd, buffer, 2, 0, 0, 0], &mut bus)
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            linux
+                .dispatch(SYS_CLOSE, [fd, 0, 0, 0, 0, 0], &mut bus)
+                .unwrap(),
+            0
+        );
+        assert_eq!(std::fs::read(&path).unwrap(), b"helLO");
+        std::fs::remove_file(&path).unwrap();
+
+        // the descriptor is closed, and the errors are numbered as on Linux
+        let error = |linux: &mut Linux, bus: &mut SystemBus, number, args| {
+            errno(&linux.dispatch(number, args, bus).unwrap_err())
+        };
+        assert_eq!(
+            error(&mut linux, &mut bus, SYS_READ, [fd, buffer, 1, 0, 0, 0]),
+            -9
+        );
+        assert_eq!(
+            error(&mut linux, &mut bus, SYS_CLOSE, [fd, 0, 0, 0, 0, 0]),
+            -9
+        );
+        // ENOENT
+        let args = [at_fdcwd, name, 0, 0, 0, 0];
+        assert_eq!(error(&mut linux, &mut bus, SYS_OPENAT, args), -2);
+    }
+
+    #[test]
+    fn memory_ok() {
+        let (elf, mut bus) = machine();
+        let mut linux = Linux::new(&elf, MEMORY_SIZE);
+        let brk = linux.dispatch(SYS_BRK, [0; 6], &mut bus).unwrap() as u64;
+        assert_eq!(brk, align_up(elf.end(), PAGE_SIZE));
+        // the memory given back is zero once the break moves over it again
+        let end = brk + 3 * PAGE_SIZE;
+        assert_eq!(
+            linux
+                .dispatch(SYS_BRK, [end, 0, 0, 0, 0, 0], &mut bus)
+                .unwrap(),
+            end as i64
+        );
+        write_bytes(&mut bus, end - 8, &[1; 8]).unwrap();
+        linux
+            .dispatch(SYS_BRK, [brk, 0, 0, 0, 0, 0], &mut bus)
+            .unwrap();
+        linux
+            .dispatch(SYS_BRK, [end, 0, 0, 0, 0, 0], &mut bus)
+            .unwrap();
+        assert_eq!(load_word(&bus, end - 8), 0);
+
+        let anonymous = [0, PAGE_SIZE + 1, 0, MAP_ANONYMOUS, u64::MAX, 0];
+        let address = linux.dispatch(SYS_MMAP, anonymous, &mut bus).unwrap() as u64;
+        assert_eq!(address, MEMORY_SIZE - STACK_SIZE - 2 * PAGE_SIZE);
+        let empty = [0, 0, 0, MAP_ANONYMOUS, u64::MAX, 0];
+        let error = linux.dispatch(SYS_MMAP, empty, &mut bus).unwrap_err();
+        assert_eq!(errno(&error), -(EINVAL as i64));
+    }
+}
@@ -0,0 +1,184 @@
+use crate::emulator::{
+    bus::SystemBus,
+    cpu::x::{IntegerRegister, A0, A1, A2, A3, A4, A5, A7},
+    snapshot::{Reader, Snapshot, Writer},
+    syscall::{
+        check_range, errno, error, open, read_bytes, read_string, stat, write_bytes, FileTable,
+        EINVAL, ENOSYS,
+    },
+};
+use std::fs::metadata;
+use std::io::{Result, Seek, SeekFrom};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// system call numbers of riscv-pk, which newlib (libgloss) uses
+const SYS_OPENAT: u64 = 56;
+const SYS_CLOSE: u64 = 57;
+const SYS_LSEEK: u64 = 62;
+const SYS_READ: u64 = 63;
+const SYS_WRITE: u64 = 64;
+const SYS_FSTATAT: u64 = 79;
+const SYS_FSTAT: u64 = 80;
+const SYS_EXIT: u64 = 93;
+const SYS_EXIT_GROUP: u64 = 94;
+const SYS_GETTIMEOFDAY: u64 = 169;
+const SYS_BRK: u64 = 214;
+const SYS_OPEN: u64 = 1024;
+const SYS_STAT: u64 = 1038;
+
+const SEEK_SET: u64 = 0;
+const SEEK_CUR: u64 = 1;
+const SEEK_END: u64 = 2;
+
+// the number of doublewords of the magic memory: the system call number and the arguments
+const MAGIC_MEM_SIZE: u64 = 8;
+
+// Services the system calls of a program linked against newlib with the riscv-pk convention,
+// either made by ecall from U-mode as under the proxy kernel, or handed over through the magic
+// memory of HTIF by a bare-metal program.
+pub struct Newlib {
+    files: FileTable,
+    brk_start: u64,
+    brk: u64,
+    brk_limit: u64,
+}
+
+impl Newlib {
+    pub fn new(brk: u64, brk_limit: u64) -> Self {
+        Self {
+            files: FileTable::default(),
+            brk_start: brk,
+            brk,
+            brk_limit,
+        }
+    }
+
+    // Services the system call the registers describe and returns the exit status when the
+    // program has exited.
+    pub fn ecall(&mut self, x: &mut IntegerRegister, bus: &mut SystemBus) -> Option<u64> {
+        let number = x.readu(A7);
+        let args = [A0, A1, A2, A3, A4, A5].map(|r| x.readu(r));
+        let (result, exit) = self.syscall(number, args, bus);
+        x.writei(A0, result);
+        exit
+    }
+
+    // Services the system call the magic memory at the address describes and writes the result
+    // back to its first doubleword.
+    pub fn magic_mem(&mut self, address: u64, bus: &mut SystemBus) -> Option<u64> {
+        if check_range(bus, address, MAGIC_MEM_SIZE * 8).is_err() {
+            return None;
+        }
+        let number = bus.load64(address);
+        let mut args = [0; 6];
+        for (i, arg) in args.iter_mut().enumerate() {
+            *arg = bus.load64(address + (i as u64 + 1) * 8);
+        }
+        let (result, exit) = self.syscall(number, args, bus);
+        bus.store64(address, result as u64);
+        exit
+    }
+
+    fn syscall(&mut self, number: u64, args: [u64; 6], bus: &mut SystemBus) -> (i64, Option<u64>) {
+        if number == SYS_EXIT || number == SYS_EXIT_GROUP {
+            return (0, Some(args[0]));
+        }
+        match self.dispatch(number, args, bus) {
+            Ok(value) => (value, None),
+            Err(e) => (errno(&e), None),
+        }
+    }
+
+    fn dispatch(&mut self, number: u64, args: [u64; 6], bus: &mut SystemBus) -> Result<i64> {
+        match number {
+            // paths relative to a directory descriptor are resolved against the working
+            // directory of the emulator
+            SYS_OPENAT => self.open(bus, args[1], args[2]),
+            SYS_OPEN => self.open(bus, args[0], args[1]),
+            SYS_CLOSE => {
+                self.files.close(args[0])?;
+                Ok(0)
+            }
+            SYS_LSEEK => {
+                let position = match args[2] {
+                    SEEK_SET => SeekFrom::Start(args[1]),
+                    SEEK_CUR => SeekFrom::Current(args[1] as i64),
+                    SEEK_END => SeekFrom::End(args[1] as i64),
+                    _ => return Err(error(EINVAL)),
+                };
+                Ok(self.files.file(args[0])?.seek(position)? as i64)
+            }
+            SYS_READ => {
+                check_range(bus, args[1], args[2])?;
+                let mut buffer = vec![0; args[2] as usize];
+                let size = self.files.read(args[0], &mut buffer)?;
+                write_bytes(bus, args[1], &buffer[..size])?;
+                Ok(size as i64)
+            }
+            SYS_WRITE => {
+                let buffer = read_bytes(bus, args[1], args[2])?;
+                Ok(self.files.write(args[0], &buffer)? as i64)
+            }
+            SYS_FSTAT => {
+                let metadata = if self.files.is_standard_stream(args[0]) {
+                    None
+                } else {
+                    Some(self.files.file(args[0])?.metadata()?)
+                };
+                write_bytes(bus, args[1], &stat(metadata.as_ref()))?;
+                Ok(0)
+            }
+            SYS_FSTATAT => {
+                let metadata = metadata(read_string(bus, args[1])?)?;
+                write_bytes(bus, args[2], &stat(Some(&metadata)))?;
+                Ok(0)
+            }
+            SYS_STAT => {
+                let metadata = metadata(read_string(bus, args[0])?)?;
+                write_bytes(bus, args[1], &stat(Some(&metadata)))?;
+                Ok(0)
+            }
+            SYS_GETTIMEOFDAY => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default();
+                write_bytes(bus, args[0], &(now.as_secs() as i64).to_le_bytes())?;
+                write_bytes(
+                    bus,
+                    args[0] + 8,
+                    &(now.subsec_micros() as i64).to_le_bytes(),
+                )?;
+                Ok(0)
+            }
+            SYS_BRK => {
+                if args[0] >= self.brk_start && args[0] <= self.brk_limit {
+                    self.brk = args[0];
+                }
+                Ok(self.brk as i64)
+            }
+            _ => Err(error(ENOSYS)),
+        }
+    }
+
+    fn open(&mut self, bus: &SystemBus, path: u64, flags: u64) -> Result<i64> {
+        let file = open(&read_string(bus, path)?, flags)?;
+        Ok(self.files.open(file) as i64)
+    }
+}
+
+// The files the program has opened on the host are not saved, and those open when it is restored
+// stay open.
+impl Snapshot for Newlib {
+    fn save(&mut self, snapshot: &mut Writer) {
+        snapshot.u64(self.brk_start);
+        snapshot.u64(self.brk);
+        snapshot.u64(self.brk_limit);
+    }
+
+    fn restore(&mut self, snapshot: &mut Reader) -> Result<()> {
+        self.brk_start = snapshot.u64()?;
+        self.brk = snapshot.u64()?;
+        self.brk_limit = snapshot.u64()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,353 @@
+use crate::emulator::{
+    bus::SystemBus,
+    cpu::x::{IntegerRegister, A0, A1},
+    syscall::{check_range, errno, error, read_bytes, write_bytes, FileTable, EINVAL},
+};
+use std::fs::{remove_file, rename, OpenOptions};
+use std::io::{Result, Seek, SeekFrom};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// slli x0, x0, 0x1f and srai x0, x0, 7 around the ebreak
+const ENTRY: u32 = 0x01f01013;
+const EXIT: u32 = 0x40705013;
+
+const SYS_OPEN: u64 = 0x01;
+const SYS_CLOSE: u64 = 0x02;
+const SYS_WRITEC: u64 = 0x03;
+const SYS_WRITE0: u64 = 0x04;
+const SYS_WRITE: u64 = 0x05;
+const SYS_READ: u64 = 0x06;
+const SYS_READC: u64 = 0x07;
+const SYS_ISERROR: u64 = 0x08;
+const SYS_ISTTY: u64 = 0x09;
+const SYS_SEEK: u64 = 0x0a;
+const SYS_FLEN: u64 = 0x0c;
+const SYS_REMOVE: u64 = 0x0e;
+const SYS_RENAME: u64 = 0x0f;
+const SYS_CLOCK: u64 = 0x10;
+const SYS_TIME: u64 = 0x11;
+const SYS_ERRNO: u64 = 0x13;
+const SYS_GET_CMDLINE: u64 = 0x15;
+const SYS_HEAPINFO: u64 = 0x16;
+const SYS_EXIT: u64 = 0x18;
+const SYS_EXIT_EXTENDED: u64 = 0x20;
+const SYS_ELAPSED: u64 = 0x30;
+const SYS_TICKFREQ: u64 = 0x31;
+
+const ADP_STOPPED_APPLICATION_EXIT: u64 = 0x20026;
+
+// the file name standing for the console, whose open mode picks the stream
+const CONSOLE: &str = ":tt";
+const STDIN: u64 = 0;
+const STDOUT: u64 = 1;
+const STDERR: u64 = 2;
+
+// the fields of the parameter blocks are as wide as the registers
+const FIELD_SIZE: u64 = 8;
+const TICK_FREQUENCY: u64 = 1_000_000;
+
+// Implements the semihosting operations of ARM, which a program requests by the ebreak
+// surrounded by the entry and exit instructions with the operation in a0 and its parameter,
+// often a pointer to a block of fields, in a1.
+pub struct Semihosting {
+    files: FileTable,
+    command_line: String,
+    errno: i32,
+    // when the program has first read the clock, which is read only then, as there is none on
+    // wasm32
+    start: Option<Instant>,
+}
+
+impl Semihosting {
+    pub fn new(command_line: &str) -> Self {
+        Self {
+            files: FileTable::default(),
+            command_line: command_line.to_string(),
+            errno: 0,
+            start: None,
+        }
+    }
+
+    // the time since the program has first read the clock
+    fn elapsed(&mut self) -> Duration {
+        self.start.get_or_insert_with(Instant::now).elapsed()
+    }
+
+    // Whether the ebreak at the address is a semihosting call.
+    pub fn is_call(bus: &SystemBus, address: u64) -> bool {
+        address >= 4
+            && check_range(bus, address - 4, 12).is_ok()
+            && bus.load32(address - 4) == ENTRY
+            && bus.load32(address + 4) == EXIT
+    }
+
+    // Performs the operation the registers describe and returns the exit status when the
+    // program has exited.
+    pub fn call(&mut self, x: &mut IntegerRegister, bus: &mut SystemBus) -> Option<u64> {
+        let operation = x.readu(A0);
+        let parameter = x.readu(A1);
+        if operation == SYS_EXIT || operation == SYS_EXIT_EXTENDED {
+            let (reason, subcode) = (
+                field(bus, parameter, 0).unwrap_or(0),
+                field(bus, parameter, 1).unwrap_or(0),
+            );
+            return Some(if reason == ADP_STOPPED_APPLICATION_EXIT {
+                subcode
+            } else {
+                1
+            });
+        }
+        let result = match self.dispatch(operation, parameter, bus) {
+            Ok(value) => value,
+            Err(e) => {
+                self.errno = -errno(&e) as i32;
+                -1
+            }
+        };
+        x.writei(A0, result);
+        None
+    }
+
+    fn dispatch(&mut self, operation: u64, parameter: u64, bus: &mut SystemBus) -> Result<i64> {
+        let field = |index: u64| field(bus, parameter, index);
+        match operation {
+            SYS_OPEN => {
+                let (name, mode, length) = (field(0)?, field(1)?, field(2)?);
+                let name = String::from_utf8_lossy(&read_bytes(bus, name, length)?).into_owned();
+                if name == CONSOLE {
+                    return Ok(match mode / 4 {
+                        0 => STDIN,
+                        1 => STDOUT,
+                        _ => STDERR,
+                    } as i64);
+                }
+                // the modes of fopen: r, w and a, each in text and binary, with or without +
+                let mut options = OpenOptions::new();
+                match mode / 4 {
+                    0 => options.read(true),
+                    1 => options.write(true).create(true).truncate(true),
+                    _ => options.append(true).create(true),
+                };
+                if mode % 4 >= 2 {
+                    options.read(true).write(true);
+                }
+                Ok(self.files.open(options.open(name)?) as i64)
+            }
+            SYS_CLOSE => {
+                self.files.close(field(0)?)?;
+                Ok(0)
+            }
+            SYS_WRITEC => {
+                self.files.write(STDOUT, &read_bytes(bus, parameter, 1)?)?;
+                Ok(0)
+            }
+            SYS_WRITE0 => {
+                let mut end = parameter;
+                while read_bytes(bus, end, 1)?[0] != 0 {
+                    end += 1;
+                }
+                self.files
+                    .write(STDOUT, &read_bytes(bus, parameter, end - parameter)?)?;
+                Ok(0)
+            }
+            // the results of reading and writing are the numbers of the bytes not transferred
+            SYS_WRITE => {
+                let (handle, buffer, length) = (field(0)?, field(1)?, field(2)?);
+                let written = self
+                    .files
+                    .write(handle, &read_bytes(bus, buffer, length)?)?;
+                Ok((length - written as u64) as i64)
+            }
+            SYS_READ => {
+                let (handle, buffer, length) = (field(0)?, field(1)?, field(2)?);
+                check_range(bus, buffer, length)?;
+                let mut bytes = vec![0; length as usize];
+                let read = self.files.read(handle, &mut bytes)?;
+                write_bytes(bus, buffer, &bytes[..read])?;
+                Ok((length - read as u64) as i64)
+            }
+            SYS_READC => {
+                let mut byte = [0];
+                self.files.read(STDIN, &mut byte)?;
+                Ok(byte[0] as i64)
+            }
+            SYS_ISERROR => Ok(((field(0)? as i64) < 0) as i64),
+            SYS_ISTTY => Ok(self.files.is_standard_stream(field(0)?) as i64),
+            SYS_SEEK => {
+                let (handle, position) = (field(0)?, field(1)?);
+                self.files.file(handle)?.seek(SeekFrom::Start(position))?;
+                Ok(0)
+            }
+            SYS_FLEN => Ok(self.files.file(field(0)?)?.metadata()?.len() as i64),
+            SYS_REMOVE => {
+                let name = read_bytes(bus, field(0)?, field(1)?)?;
+                remove_file(String::from_utf8_lossy(&name).into_owned())?;
+                Ok(0)
+            }
+            SYS_RENAME => {
+                let from = read_bytes(bus, field(0)?, field(1)?)?;
+                let to = read_bytes(bus, field(2)?, field(3)?)?;
+                rename(
+                    String::from_utf8_lossy(&from).into_owned(),
+                    String::from_utf8_lossy(&to).into_owned(),
+                )?;
+                Ok(0)
+            }
+            // in centiseconds
+            SYS_CLOCK => Ok((self.elapsed().as_millis() / 10) as i64),
+            SYS_TIME => Ok(SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs() as i64),
+            SYS_ERRNO => Ok(self.errno as i64),
+            SYS_GET_CMDLINE => {
+                let (buffer, length) = (field(0)?, field(1)?);
+                let mut command_line = self.command_line.as_bytes().to_vec();
+                command_line.push(0);
+                if command_line.len() as u64 > length {
+                    return Err(error(EINVAL));
+                }
+                write_bytes(bus, buffer, &command_line)?;
+                write_bytes(
+                    bus,
+                    parameter + FIELD_SIZE,
+                    &(command_line.len() as u64 - 1).to_le_bytes(),
+                )?;
+                Ok(0)
+            }
+            // zeros let the runtime place the heap and the stack with its own symbols
+            SYS_HEAPINFO => {
+                write_bytes(bus, field(0)?, &[0; 4 * FIELD_SIZE as usize])?;
+                Ok(0)
+            }
+            SYS_ELAPSED => {
+                let ticks = self.elapsed().as_micros() as u64;
+                write_bytes(bus, parameter, &ticks.to_le_bytes())?;
+                Ok(0)
+            }
+            SYS_TICKFREQ => Ok(TICK_FREQUENCY as i64),
+            _ => Err(error(EINVAL)),
+        }
+    }
+}
+
+fn field(bus: &SystemBus, block: u64, index: u64) -> Result<u64> {
+    let address = block + index * FIELD_SIZE;
+    check_range(bus, address, FIELD_SIZE)?;
+    Ok(bus.load64(address))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::memory::{Memory, MEMORY_BASE_ADDRESS};
+
+    const BLOCK: u64 = MEMORY_BASE_ADDRESS;
+    const NAME: u64 = MEMORY_BASE_ADDRESS + 0x100;
+    const BUFFER: u64 = MEMORY_BASE_ADDRESS + 0x1000;
+
+    fn bus() -> SystemBus {
+        SystemBus::new(1, Memory::new(MEMORY_BASE_ADDRESS, 0x10_0000))
+    }
+
+    // Performs the operation with its parameter block and returns the result in a0.
+    fn call(
+        semihosting: &mut Semihosting,
+        bus: &mut SystemBus,
+        operation: u64,
+        fields: &[u64],
+    ) -> i64 {
+        for (i, value) in fields.iter().enumerate() {
+            write_bytes(bus, BLOCK + i as u64 * FIELD_SIZE, &value.to_le_bytes()).unwrap();
+        }
+        let mut x = IntegerRegister::default();
+        x.writeu(A0, operation);
+        x.writeu(A1, BLOCK);
+        assert_eq!(semihosting.call(&mut x, bus), None);
+        x.readi(A0)
+    }
+
+    #[test]
+    fn files_ok() {
+        let mut semihosting = Semihosting::new("");
+        let mut bus = bus();
+        // the console is opened as the stream its mode picks
+        write_bytes(&mut bus, NAME, CONSOLE.as_bytes()).unwrap();
+        for (mode, stream) in [(0, STDIN), (4, STDOUT), (8, STDERR)].iter() {
+            let fields = [NAME, *mode, CONSOLE.len() as u64];
+            assert_eq!(
+                call(&mut semihosting, &mut bus, SYS_OPEN, &fields),
+                *stream as i64
+            );
+        }
+
+        let path = std::env::temp_dir().join(format!("five-semihosting-{}", std::process::id()));
+        let name = path.to_str().unwrap().as_bytes();
+        write_bytes(&mut bus, NAME, name).unwrap();
+        let length = name.len() as u64;
+        // w, written in full
+        let handle = call(&mut semihosting, &mut bus, SYS_OPEN, &[NAME, 4, length]) as u64;
+        assert!(handle > STDERR);
+        write_bytes(&mut bus, BUFFER, b"hello").unwrap();
+        let fields = [handle, BUFFER, 5];
+        assert_eq!(call(&mut semihosting, &mut bus, SYS_WRITE, &fields), 0);
+        assert_eq!(call(&mut semihosting, &mut bus, SYS_CLOSE, &[handle]), 0);
+        // r, of which the bytes beyond the end are not read
+        let handle = call(&mut semihosting, &mut bus, SYS_OPEN, &[NAME, 0, length]) as u64;
+        assert_eq!(call(&mut semihosting, &mut bus, SYS_FLEN, &[handle]), 5);
+        write_bytes(&mut bus, BUFFER, &[0; 8]).unwrap();
+        let fields = [handle, BUFFER, 8];
+        assert_eq!(call(&mut semihosting, &mut bus, SYS_READ, &fields), 3);
+        assert_eq!(read_bytes(&bus, BUFFER, 8).unwrap(), b"hello\0\0\0");
+        assert_eq!(call(&mut semihosting, &mut bus, SYS_READ, &fields), 8);
+        assert_eq!(call(&mut semihosting, &mut bus, SYS_CLOSE, &[handle]), 0);
+
+        // a handle which has been closed, and a file which does not exist
+        assert_eq!(call(&mut semihosting, &mut bus, SYS_CLOSE, &[handle]), -1);
+        assert_eq!(call(&mut semihosting, &mut bus, SYS_ERRNO, &[]), 9);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            call(&mut semihosting, &mut bus, SYS_OPEN, &[NAME, 0, length]),
+            -1
+        );
+        assert_eq!(call(&mut semihosting, &mut bus, SYS_ERRNO, &[]), 2);
+    }
+
+    #[test]
+    fn command_line_ok() {
+        let mut semihosting = Semihosting::new("program argument");
+        let mut bus = bus();
+        assert_eq!(
+            call(&mut semihosting, &mut bus, SYS_GET_CMDLINE, &[BUFFER, 64]),
+            0
+        );
+        assert_eq!(read_bytes(&bus, BUFFER, 17).unwrap(), b"program argument\0");
+        // the length excludes the NUL
+        assert_eq!(bus.load64(BLOCK + FIELD_SIZE), 16);
+        // the buffer is too small
+        let fields = [BUFFER, 16];
+        assert_eq!(
+            call(&mut semihosting, &mut bus, SYS_GET_CMDLINE, &fields),
+            -1
+        );
+        assert_eq!(
+            call(&mut semihosting, &mut bus, SYS_ERRNO, &[]),
+            EINVAL as i64
+        );
+    }
+
+    #[test]
+    fn clock_ok() {
+        let mut semihosting = Semihosting::new("");
+        let mut bus = bus();
+        // the clock is not read before the program asks for it
+        assert!(semihosting.start.is_none());
+        let centiseconds = call(&mut semihosting, &mut bus, SYS_CLOCK, &[]);
+        assert!((0..100).contains(&centiseconds));
+        assert!(semihosting.start.is_some());
+        assert_eq!(
+            call(&mut semihosting, &mut bus, SYS_TICKFREQ, &[]),
+            TICK_FREQUENCY as i64
+        );
+    }
+}
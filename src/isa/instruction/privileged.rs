@@ -1,6 +1,6 @@
 use std::fmt;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PrivilegedOpcodeR {
     Uret,
     Sret,
@@ -12,17 +12,56 @@ pub enum PrivilegedOpcodeR {
 impl fmt::Display for PrivilegedOpcodeR {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            PrivilegedOpcodeR::Uret => f.write_str("Privilege::Uret"),
-            PrivilegedOpcodeR::Sret => f.write_str("Privilege::Sret"),
-            PrivilegedOpcodeR::Mret => f.write_str("Privilege::Mret"),
-            PrivilegedOpcodeR::Wfi => f.write_str("Privilege::Wfi"),
-            PrivilegedOpcodeR::SfenceVma => f.write_str("Privilege::SfenceVma"),
+            PrivilegedOpcodeR::Uret => f.write_str("uret"),
+            PrivilegedOpcodeR::Sret => f.write_str("sret"),
+            PrivilegedOpcodeR::Mret => f.write_str("mret"),
+            PrivilegedOpcodeR::Wfi => f.write_str("wfi"),
+            PrivilegedOpcodeR::SfenceVma => f.write_str("sfence.vma"),
         }
     }
 }
 
+#[derive(Clone, Copy)]
 pub enum PrivilegedOpcodeI {}
+
+impl fmt::Display for PrivilegedOpcodeI {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum PrivilegedOpcodeS {}
+
+impl fmt::Display for PrivilegedOpcodeS {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum PrivilegedOpcodeB {}
+
+impl fmt::Display for PrivilegedOpcodeB {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum PrivilegedOpcodeU {}
+
+impl fmt::Display for PrivilegedOpcodeU {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum PrivilegedOpcodeJ {}
+
+impl fmt::Display for PrivilegedOpcodeJ {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
@@ -1,6 +1,6 @@
 use std::fmt;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Rv32iOpcodeR {
     Sll,
     Srl,
@@ -17,21 +17,21 @@ pub enum Rv32iOpcodeR {
 impl fmt::Display for Rv32iOpcodeR {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Rv32iOpcodeR::Sll => f.write_str("Rv32i::Sll"),
-            Rv32iOpcodeR::Srl => f.write_str("Rv32i::Srl"),
-            Rv32iOpcodeR::Sra => f.write_str("Rv32i::Sra"),
-            Rv32iOpcodeR::Add => f.write_str("Rv32i::Add"),
-            Rv32iOpcodeR::Sub => f.write_str("Rv32i::Sub"),
-            Rv32iOpcodeR::Xor => f.write_str("Rv32i::Xor"),
-            Rv32iOpcodeR::Or => f.write_str("Rv32i::Or"),
-            Rv32iOpcodeR::And => f.write_str("Rv32i::And"),
-            Rv32iOpcodeR::Slt => f.write_str("Rv32i::Slt"),
-            Rv32iOpcodeR::Sltu => f.write_str("Rv32i::Sltu"),
+            Rv32iOpcodeR::Sll => f.write_str("sll"),
+            Rv32iOpcodeR::Srl => f.write_str("srl"),
+            Rv32iOpcodeR::Sra => f.write_str("sra"),
+            Rv32iOpcodeR::Add => f.write_str("add"),
+            Rv32iOpcodeR::Sub => f.write_str("sub"),
+            Rv32iOpcodeR::Xor => f.write_str("xor"),
+            Rv32iOpcodeR::Or => f.write_str("or"),
+            Rv32iOpcodeR::And => f.write_str("and"),
+            Rv32iOpcodeR::Slt => f.write_str("slt"),
+            Rv32iOpcodeR::Sltu => f.write_str("sltu"),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Rv32iOpcodeI {
     Slli,
     Srli,
@@ -56,29 +56,29 @@ pub enum Rv32iOpcodeI {
 impl fmt::Display for Rv32iOpcodeI {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Rv32iOpcodeI::Slli => f.write_str("Rv32i::Slli"),
-            Rv32iOpcodeI::Srli => f.write_str("Rv32i::Srli"),
-            Rv32iOpcodeI::Srai => f.write_str("Rv32i::Srai"),
-            Rv32iOpcodeI::Addi => f.write_str("Rv32i::Addi"),
-            Rv32iOpcodeI::Xori => f.write_str("Rv32i::Xori"),
-            Rv32iOpcodeI::Ori => f.write_str("Rv32i::Ori"),
-            Rv32iOpcodeI::Andi => f.write_str("Rv32i::Andi"),
-            Rv32iOpcodeI::Slti => f.write_str("Rv32i::Slti"),
-            Rv32iOpcodeI::Sltiu => f.write_str("Rv32i::Sltiu"),
-            Rv32iOpcodeI::Jalr => f.write_str("Rv32i::Jalr"),
-            Rv32iOpcodeI::Fence => f.write_str("Rv32i::Fence"),
-            Rv32iOpcodeI::Ecall => f.write_str("Rv32i::Ecall"),
-            Rv32iOpcodeI::Ebreak => f.write_str("Rv32i::Ebreak"),
-            Rv32iOpcodeI::Lb => f.write_str("Rv32i::Lb"),
-            Rv32iOpcodeI::Lh => f.write_str("Rv32i::Lh"),
-            Rv32iOpcodeI::Lbu => f.write_str("Rv32i::Lbu"),
-            Rv32iOpcodeI::Lhu => f.write_str("Rv32i::Lhu"),
-            Rv32iOpcodeI::Lw => f.write_str("Rv32i::Lw"),
+            Rv32iOpcodeI::Slli => f.write_str("slli"),
+            Rv32iOpcodeI::Srli => f.write_str("srli"),
+            Rv32iOpcodeI::Srai => f.write_str("srai"),
+            Rv32iOpcodeI::Addi => f.write_str("addi"),
+            Rv32iOpcodeI::Xori => f.write_str("xori"),
+            Rv32iOpcodeI::Ori => f.write_str("ori"),
+            Rv32iOpcodeI::Andi => f.write_str("andi"),
+            Rv32iOpcodeI::Slti => f.write_str("slti"),
+            Rv32iOpcodeI::Sltiu => f.write_str("sltiu"),
+            Rv32iOpcodeI::Jalr => f.write_str("jalr"),
+            Rv32iOpcodeI::Fence => f.write_str("fence"),
+            Rv32iOpcodeI::Ecall => f.write_str("ecall"),
+            Rv32iOpcodeI::Ebreak => f.write_str("ebreak"),
+            Rv32iOpcodeI::Lb => f.write_str("lb"),
+            Rv32iOpcodeI::Lh => f.write_str("lh"),
+            Rv32iOpcodeI::Lbu => f.write_str("lbu"),
+            Rv32iOpcodeI::Lhu => f.write_str("lhu"),
+            Rv32iOpcodeI::Lw => f.write_str("lw"),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Rv32iOpcodeS {
     Sb,
     Sh,
@@ -88,14 +88,14 @@ pub enum Rv32iOpcodeS {
 impl fmt::Display for Rv32iOpcodeS {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Rv32iOpcodeS::Sb => f.write_str("Rv32i::Sb"),
-            Rv32iOpcodeS::Sh => f.write_str("Rv32i::Sh"),
-            Rv32iOpcodeS::Sw => f.write_str("Rv32i::Sw"),
+            Rv32iOpcodeS::Sb => f.write_str("sb"),
+            Rv32iOpcodeS::Sh => f.write_str("sh"),
+            Rv32iOpcodeS::Sw => f.write_str("sw"),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Rv32iOpcodeB {
     Beq,
     Bne,
@@ -108,17 +108,17 @@ pub enum Rv32iOpcodeB {
 impl fmt::Display for Rv32iOpcodeB {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Rv32iOpcodeB::Beq => f.write_str("Rv32i::Beq"),
-            Rv32iOpcodeB::Bne => f.write_str("Rv32i::Bne"),
-            Rv32iOpcodeB::Blt => f.write_str("Rv32i::Blt"),
-            Rv32iOpcodeB::Bge => f.write_str("Rv32i::Bge"),
-            Rv32iOpcodeB::Bltu => f.write_str("Rv32i::Bltu"),
-            Rv32iOpcodeB::Bgeu => f.write_str("Rv32i::Bgeu"),
+            Rv32iOpcodeB::Beq => f.write_str("beq"),
+            Rv32iOpcodeB::Bne => f.write_str("bne"),
+            Rv32iOpcodeB::Blt => f.write_str("blt"),
+            Rv32iOpcodeB::Bge => f.write_str("bge"),
+            Rv32iOpcodeB::Bltu => f.write_str("bltu"),
+            Rv32iOpcodeB::Bgeu => f.write_str("bgeu"),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Rv32iOpcodeU {
     Lui,
     Auipc,
@@ -127,13 +127,13 @@ pub enum Rv32iOpcodeU {
 impl fmt::Display for Rv32iOpcodeU {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Rv32iOpcodeU::Lui => f.write_str("Rv32i::Lui"),
-            Rv32iOpcodeU::Auipc => f.write_str("Rv32i::Auipc"),
+            Rv32iOpcodeU::Lui => f.write_str("lui"),
+            Rv32iOpcodeU::Auipc => f.write_str("auipc"),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Rv32iOpcodeJ {
     Jal,
 }
@@ -141,7 +141,7 @@ pub enum Rv32iOpcodeJ {
 impl fmt::Display for Rv32iOpcodeJ {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Rv32iOpcodeJ::Jal => f.write_str("Rv32i::Jal"),
+            Rv32iOpcodeJ::Jal => f.write_str("jal"),
         }
     }
 }
@@ -1,6 +1,6 @@
 use std::fmt;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Rv32mOpcodeR {
     Mul,
     Mulh,
@@ -15,20 +15,59 @@ pub enum Rv32mOpcodeR {
 impl fmt::Display for Rv32mOpcodeR {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Rv32mOpcodeR::Mul => f.write_str("Rv32m::Mul"),
-            Rv32mOpcodeR::Mulh => f.write_str("Rv32m::Mulh"),
-            Rv32mOpcodeR::Mulhsu => f.write_str("Rv32m::Mulhsu"),
-            Rv32mOpcodeR::Mulhu => f.write_str("Rv32m::Mulhu"),
-            Rv32mOpcodeR::Div => f.write_str("Rv32m::Div"),
-            Rv32mOpcodeR::Divu => f.write_str("Rv32m::Divu"),
-            Rv32mOpcodeR::Rem => f.write_str("Rv32m::Rem"),
-            Rv32mOpcodeR::Remu => f.write_str("Rv32m::Remu"),
+            Rv32mOpcodeR::Mul => f.write_str("mul"),
+            Rv32mOpcodeR::Mulh => f.write_str("mulh"),
+            Rv32mOpcodeR::Mulhsu => f.write_str("mulhsu"),
+            Rv32mOpcodeR::Mulhu => f.write_str("mulhu"),
+            Rv32mOpcodeR::Div => f.write_str("div"),
+            Rv32mOpcodeR::Divu => f.write_str("divu"),
+            Rv32mOpcodeR::Rem => f.write_str("rem"),
+            Rv32mOpcodeR::Remu => f.write_str("remu"),
         }
     }
 }
 
+#[derive(Clone, Copy)]
 pub enum Rv32mOpcodeI {}
+
+impl fmt::Display for Rv32mOpcodeI {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum Rv32mOpcodeS {}
+
+impl fmt::Display for Rv32mOpcodeS {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum Rv32mOpcodeB {}
+
+impl fmt::Display for Rv32mOpcodeB {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum Rv32mOpcodeU {}
+
+impl fmt::Display for Rv32mOpcodeU {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum Rv32mOpcodeJ {}
+
+impl fmt::Display for Rv32mOpcodeJ {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
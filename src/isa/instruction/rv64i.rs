@@ -1,6 +1,6 @@
 use std::fmt;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Rv64iOpcodeR {
     Sllw,
     Srlw,
@@ -12,16 +12,16 @@ pub enum Rv64iOpcodeR {
 impl fmt::Display for Rv64iOpcodeR {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Rv64iOpcodeR::Sllw => f.write_str("Rv64i::Sllw"),
-            Rv64iOpcodeR::Srlw => f.write_str("Rv64i::Srlw"),
-            Rv64iOpcodeR::Sraw => f.write_str("Rv64i::Sraw"),
-            Rv64iOpcodeR::Addw => f.write_str("Rv64i::Addw"),
-            Rv64iOpcodeR::Subw => f.write_str("Rv64i::Subw"),
+            Rv64iOpcodeR::Sllw => f.write_str("sllw"),
+            Rv64iOpcodeR::Srlw => f.write_str("srlw"),
+            Rv64iOpcodeR::Sraw => f.write_str("sraw"),
+            Rv64iOpcodeR::Addw => f.write_str("addw"),
+            Rv64iOpcodeR::Subw => f.write_str("subw"),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Rv64iOpcodeI {
     Slliw,
     Srliw,
@@ -34,17 +34,17 @@ pub enum Rv64iOpcodeI {
 impl fmt::Display for Rv64iOpcodeI {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Rv64iOpcodeI::Slliw => f.write_str("Rv64i::Slliw"),
-            Rv64iOpcodeI::Srliw => f.write_str("Rv64i::Srliw"),
-            Rv64iOpcodeI::Sraiw => f.write_str("Rv64i::Sraiw"),
-            Rv64iOpcodeI::Addiw => f.write_str("Rv64i::Addiw"),
-            Rv64iOpcodeI::Lwu => f.write_str("Rv64i::Lwu"),
-            Rv64iOpcodeI::Ld => f.write_str("Rv64i::Ld"),
+            Rv64iOpcodeI::Slliw => f.write_str("slliw"),
+            Rv64iOpcodeI::Srliw => f.write_str("srliw"),
+            Rv64iOpcodeI::Sraiw => f.write_str("sraiw"),
+            Rv64iOpcodeI::Addiw => f.write_str("addiw"),
+            Rv64iOpcodeI::Lwu => f.write_str("lwu"),
+            Rv64iOpcodeI::Ld => f.write_str("ld"),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Rv64iOpcodeS {
     Sd,
 }
@@ -52,11 +52,34 @@ pub enum Rv64iOpcodeS {
 impl fmt::Display for Rv64iOpcodeS {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Rv64iOpcodeS::Sd => f.write_str("Rv64i::Sd"),
+            Rv64iOpcodeS::Sd => f.write_str("sd"),
         }
     }
 }
 
+#[derive(Clone, Copy)]
 pub enum Rv64iOpcodeB {}
+
+impl fmt::Display for Rv64iOpcodeB {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum Rv64iOpcodeU {}
+
+impl fmt::Display for Rv64iOpcodeU {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum Rv64iOpcodeJ {}
+
+impl fmt::Display for Rv64iOpcodeJ {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
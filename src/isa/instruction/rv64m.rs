@@ -1,6 +1,6 @@
 use std::fmt;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Rv64mOpcodeR {
     Mulw,
     Divw,
@@ -12,17 +12,56 @@ pub enum Rv64mOpcodeR {
 impl fmt::Display for Rv64mOpcodeR {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Rv64mOpcodeR::Mulw => f.write_str("Rv64m::Mulw"),
-            Rv64mOpcodeR::Divw => f.write_str("Rv64m::Divw"),
-            Rv64mOpcodeR::Divuw => f.write_str("Rv64m::Divuw"),
-            Rv64mOpcodeR::Remw => f.write_str("Rv64m::Remw"),
-            Rv64mOpcodeR::Remuw => f.write_str("Rv64m::Remuw"),
+            Rv64mOpcodeR::Mulw => f.write_str("mulw"),
+            Rv64mOpcodeR::Divw => f.write_str("divw"),
+            Rv64mOpcodeR::Divuw => f.write_str("divuw"),
+            Rv64mOpcodeR::Remw => f.write_str("remw"),
+            Rv64mOpcodeR::Remuw => f.write_str("remuw"),
         }
     }
 }
 
+#[derive(Clone, Copy)]
 pub enum Rv64mOpcodeI {}
+
+impl fmt::Display for Rv64mOpcodeI {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum Rv64mOpcodeS {}
+
+impl fmt::Display for Rv64mOpcodeS {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum Rv64mOpcodeB {}
+
+impl fmt::Display for Rv64mOpcodeB {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum Rv64mOpcodeU {}
+
+impl fmt::Display for Rv64mOpcodeU {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum Rv64mOpcodeJ {}
+
+impl fmt::Display for Rv64mOpcodeJ {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
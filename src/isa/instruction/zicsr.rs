@@ -1,8 +1,15 @@
 use std::fmt;
 
+#[derive(Clone, Copy)]
 pub enum ZicsrOpcodeR {}
 
-#[derive(Debug, PartialEq)]
+impl fmt::Display for ZicsrOpcodeR {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ZicsrOpcodeI {
     Csrrw,
     Csrrs,
@@ -15,17 +22,48 @@ pub enum ZicsrOpcodeI {
 impl fmt::Display for ZicsrOpcodeI {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            ZicsrOpcodeI::Csrrw => f.write_str("Zicsr::Csrrw"),
-            ZicsrOpcodeI::Csrrs => f.write_str("Zicsr::Csrrs"),
-            ZicsrOpcodeI::Csrrc => f.write_str("Zicsr::Csrrc"),
-            ZicsrOpcodeI::Csrrwi => f.write_str("Zicsr::Csrrwi"),
-            ZicsrOpcodeI::Csrrsi => f.write_str("Zicsr::Csrrsi"),
-            ZicsrOpcodeI::Csrrci => f.write_str("Zicsr::Csrrci"),
+            ZicsrOpcodeI::Csrrw => f.write_str("csrrw"),
+            ZicsrOpcodeI::Csrrs => f.write_str("csrrs"),
+            ZicsrOpcodeI::Csrrc => f.write_str("csrrc"),
+            ZicsrOpcodeI::Csrrwi => f.write_str("csrrwi"),
+            ZicsrOpcodeI::Csrrsi => f.write_str("csrrsi"),
+            ZicsrOpcodeI::Csrrci => f.write_str("csrrci"),
         }
     }
 }
 
+#[derive(Clone, Copy)]
 pub enum ZicsrOpcodeS {}
+
+impl fmt::Display for ZicsrOpcodeS {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum ZicsrOpcodeB {}
+
+impl fmt::Display for ZicsrOpcodeB {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum ZicsrOpcodeU {}
+
+impl fmt::Display for ZicsrOpcodeU {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum ZicsrOpcodeJ {}
+
+impl fmt::Display for ZicsrOpcodeJ {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
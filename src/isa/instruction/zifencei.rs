@@ -1,8 +1,15 @@
 use std::fmt;
 
+#[derive(Clone, Copy)]
 pub enum ZifenceiOpcodeR {}
 
-#[derive(Debug, PartialEq)]
+impl fmt::Display for ZifenceiOpcodeR {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ZifenceiOpcodeI {
     FenceI,
 }
@@ -10,12 +17,43 @@ pub enum ZifenceiOpcodeI {
 impl fmt::Display for ZifenceiOpcodeI {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            ZifenceiOpcodeI::FenceI => f.write_str("Zifencei::FenceI"),
+            ZifenceiOpcodeI::FenceI => f.write_str("fence.i"),
         }
     }
 }
 
+#[derive(Clone, Copy)]
 pub enum ZifenceiOpcodeS {}
+
+impl fmt::Display for ZifenceiOpcodeS {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum ZifenceiOpcodeB {}
+
+impl fmt::Display for ZifenceiOpcodeB {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum ZifenceiOpcodeU {}
+
+impl fmt::Display for ZifenceiOpcodeU {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[derive(Clone, Copy)]
 pub enum ZifenceiOpcodeJ {}
+
+impl fmt::Display for ZifenceiOpcodeJ {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
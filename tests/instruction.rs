@@ -1,18 +1,42 @@
-use five::emulator::Emulator;
+use five::emulator::{
+    lockstep::{self, Verdict},
+    Emulator, RunOutcome, StopConditions,
+};
 use std::fs::File;
+use std::io::BufReader;
 use std::path::PathBuf;
+use std::time::Duration;
 
 fn run(name: &str) -> bool {
     let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
     path.push("riscv-tests");
     path.push("isa");
     path.push(name);
-    path.set_extension("bin");
-    let file = File::open(&path.as_path());
+    let file = File::open(path.as_path());
     let mut emulator = Emulator::default();
+    // a test which loops forever fails instead of hanging
+    emulator.stop_when(StopConditions {
+        instructions: Some(10_000_000),
+        timeout: Some(Duration::from_secs(10)),
+        ..StopConditions::default()
+    });
     if let Ok(f) = file {
-        let _ = emulator.load(f);
-        emulator.run() == 1
+        if emulator.load_elf(f).is_err() {
+            return false;
+        }
+        // the tests report the result through tohost, where 0 means success, and run in
+        // lockstep with the commit log of Spike when `cargo make reference` has made it
+        match File::open(path.with_extension("log")) {
+            Ok(reference) => match lockstep::run(&mut emulator, BufReader::new(reference)) {
+                Ok(Verdict::Matched { outcome, .. }) => outcome == Some(RunOutcome::Exited(0)),
+                Ok(Verdict::Mismatched(mismatch)) => {
+                    eprintln!("{}: {}", name, mismatch);
+                    false
+                }
+                Err(_) => false,
+            },
+            Err(_) => matches!(emulator.run(), Ok(RunOutcome::Exited(0))),
+        }
     } else {
         false
     }